@@ -6,6 +6,8 @@
 use std::f64::consts::PI;
 use std::fmt::{Display, Formatter, Result};
 
+use serde::{Deserialize, Serialize};
+
 pub const EARTH_CIRCUMFERENCE: f64 = 40_075_016.685_578_5;
 pub const EARTH_RADIUS: f64 = EARTH_CIRCUMFERENCE / 2.0 / PI;
 
@@ -14,13 +16,205 @@ pub const MAX_ZOOM: u8 = 30;
 mod decoders;
 pub use decoders::*;
 
-#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+/// Serialized as a `{"z":1,"x":2,"y":3}` struct, see [`tile_coord_array`] for the array form
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq, Serialize, Deserialize)]
 pub struct TileCoord {
     pub z: u8,
     pub x: u32,
     pub y: u32,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TileCoordError {
+    /// The value is not in the `z/x/y` or `z,x,y` form
+    InvalidFormat(String),
+    /// The `x` or `y` value is outside of `[0, 2^z)`
+    OutOfRange(TileCoord),
+}
+
+impl Display for TileCoordError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            Self::InvalidFormat(v) => {
+                write!(
+                    f,
+                    "Tile coordinates {v:?} must be in the z/x/y or z,x,y form"
+                )
+            }
+            Self::OutOfRange(xyz) => write!(
+                f,
+                "Tile coordinates {xyz:#} are out of range, x and y must be less than {}",
+                u64::from(max_tile_index(xyz.z)) + 1
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TileCoordError {}
+
+impl TileCoord {
+    /// Parse tile coordinates in the `z/x/y` or `z,x,y` form, i.e. the output of the
+    /// alternate and the regular [`Display`] formatting.
+    pub fn from_path(value: &str) -> std::result::Result<Self, TileCoordError> {
+        let separator = if value.contains('/') { '/' } else { ',' };
+        let invalid = || TileCoordError::InvalidFormat(value.to_string());
+        let mut parts = value.split(separator);
+        let (Some(z), Some(x), Some(y), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(invalid());
+        };
+        let xyz = Self {
+            z: z.parse().map_err(|_| invalid())?,
+            x: x.parse().map_err(|_| invalid())?,
+            y: y.parse().map_err(|_| invalid())?,
+        };
+        if !xyz.is_valid() {
+            return Err(TileCoordError::OutOfRange(xyz));
+        }
+        Ok(xyz)
+    }
+
+    /// Check that `x` and `y` are within `[0, 2^z)`
+    #[must_use]
+    pub fn is_valid(&self) -> bool {
+        let max = max_tile_index(self.z);
+        self.x <= max && self.y <= max
+    }
+
+    /// Get the ancestor tile at a lower zoom that contains this tile, together with the
+    /// position of this tile inside of it. Returns `None` if the zoom is higher than this tile's,
+    /// or if there are more than 2^31 tiles between the two zooms.
+    #[must_use]
+    pub fn ancestor(self, zoom: u8) -> Option<(Self, TileOffset)> {
+        let dz = self.z.checked_sub(zoom)?;
+        if dz >= 32 {
+            return None;
+        }
+        let mask = max_tile_index(dz);
+        let ancestor = Self {
+            z: zoom,
+            x: self.x >> dz,
+            y: self.y >> dz,
+        };
+        let offset = TileOffset {
+            x: self.x & mask,
+            y: self.y & mask,
+            scale: mask + 1,
+        };
+        Some((ancestor, offset))
+    }
+
+    /// Get the tile at the previous zoom that contains this tile, or `None` at zoom 0.
+    #[must_use]
+    pub fn parent(self) -> Option<Self> {
+        Some(Self {
+            z: self.z.checked_sub(1)?,
+            x: self.x >> 1,
+            y: self.y >> 1,
+        })
+    }
+
+    /// Get the four tiles at the next zoom that this tile consists of, in the
+    /// top-left, top-right, bottom-left, bottom-right order.
+    /// Tiles at zoom 31 and above have no valid children, see [`TileCoord::is_valid`].
+    #[must_use]
+    pub fn children(self) -> [Self; 4] {
+        let z = self.z.saturating_add(1);
+        let (x, y) = (self.x << 1, self.y << 1);
+        [
+            Self { z, x, y },
+            Self { z, x: x + 1, y },
+            Self { z, x, y: y + 1 },
+            Self {
+                z,
+                x: x + 1,
+                y: y + 1,
+            },
+        ]
+    }
+
+    /// Get the up to eight tiles at the same zoom that share an edge or a corner with this tile,
+    /// row by row from the top-left. The X axis wraps around at the antimeridian,
+    /// so the tiles in the first and the last column are neighbors. The Y axis does not wrap,
+    /// so the tiles in the first and the last row have no neighbors beyond the poles.
+    /// Each neighbor is only listed once, and a tile is never its own neighbor, e.g. at zoom 0.
+    #[must_use]
+    pub fn neighbors(self) -> Vec<Self> {
+        let max = i64::from(max_tile_index(self.z));
+        let mut result = Vec::with_capacity(8);
+        for dy in -1..=1 {
+            let Ok(y) = u32::try_from(i64::from(self.y) + dy) else {
+                continue;
+            };
+            if i64::from(y) > max {
+                continue;
+            }
+            for dx in -1..=1 {
+                let Ok(x) = u32::try_from((i64::from(self.x) + dx).rem_euclid(max + 1)) else {
+                    continue;
+                };
+                let tile = Self { z: self.z, x, y };
+                if tile != self && !result.contains(&tile) {
+                    result.push(tile);
+                }
+            }
+        }
+        result
+    }
+
+    /// Convert between XYZ and TMS (Y axis inverted) tile coordinates.
+    /// Out of range Y values are clamped to `0` instead of overflowing.
+    #[must_use]
+    pub fn flip_y(self) -> Self {
+        Self {
+            y: max_tile_index(self.z).saturating_sub(self.y),
+            ..self
+        }
+    }
+
+    /// Wrap the `x` coordinate around the antimeridian, i.e. `x` modulo `2^z`,
+    /// e.g. for clients that pan continuously across the globe. `y` is never wrapped.
+    #[must_use]
+    pub fn wrapped(self) -> Self {
+        if self.z >= 32 {
+            return self;
+        }
+        Self {
+            x: self.x % (1 << self.z),
+            ..self
+        }
+    }
+
+    /// All tiles at the given zoom that intersect `bounds`, i.e. `[min_lng, min_lat, max_lng, max_lat]`,
+    /// row by row from the top left.
+    pub fn tiles_in_bbox(bounds: [f64; 4], zoom: u8) -> impl Iterator<Item = Self> {
+        let [left, bottom, right, top] = bounds;
+        let (min_x, min_y, max_x, max_y) = bbox_to_xyz(left, bottom, right, top, zoom);
+        (min_y..=max_y).flat_map(move |y| (min_x..=max_x).map(move |x| Self { z: zoom, x, y }))
+    }
+}
+
+/// Position of a tile inside one of its ancestors, see [`TileCoord::ancestor`]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct TileOffset {
+    /// Column of the tile inside the ancestor, from the left
+    pub x: u32,
+    /// Row of the tile inside the ancestor, from the top
+    pub y: u32,
+    /// Number of tiles along each side of the ancestor, i.e. `2^dz`
+    pub scale: u32,
+}
+
+/// Largest `x` or `y` tile index at the given zoom, saturating at zoom 32 and above
+fn max_tile_index(zoom: u8) -> u32 {
+    if zoom >= 32 {
+        u32::MAX
+    } else {
+        (1u32 << zoom) - 1
+    }
+}
+
 impl Display for TileCoord {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
         if f.alternate() {
@@ -31,6 +225,23 @@ impl Display for TileCoord {
     }
 }
 
+/// Serialize a [`TileCoord`] as a `[z, x, y]` array instead of a struct,
+/// e.g. with `#[serde(with = "martin_tile_utils::tile_coord_array")]`.
+pub mod tile_coord_array {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use crate::TileCoord;
+
+    pub fn serialize<S: Serializer>(xyz: &TileCoord, serializer: S) -> Result<S::Ok, S::Error> {
+        [u32::from(xyz.z), xyz.x, xyz.y].serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<TileCoord, D::Error> {
+        let (z, x, y) = <(u8, u32, u32)>::deserialize(deserializer)?;
+        Ok(TileCoord { z, x, y })
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Format {
     Gif,
@@ -69,6 +280,19 @@ impl Format {
         }
     }
 
+    /// Get the usual file extension of tiles in this format, without the leading dot
+    #[must_use]
+    pub fn extension(&self) -> &'static str {
+        match *self {
+            Self::Gif => "gif",
+            Self::Jpeg => "jpg",
+            Self::Json => "json",
+            Self::Mvt => "pbf",
+            Self::Png => "png",
+            Self::Webp => "webp",
+        }
+    }
+
     #[must_use]
     pub fn content_type(&self) -> &str {
         match *self {
@@ -344,6 +568,239 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_format_extension() {
+        for format in [Format::Gif, Jpeg, Json, Format::Mvt, Png, Webp] {
+            assert_eq!(Format::parse(format.extension()), Some(format));
+        }
+        assert_eq!(Format::Mvt.extension(), "pbf");
+        assert_eq!(Png.extension(), "png");
+    }
+
+    #[test]
+    fn test_flip_y() {
+        let xyz = |z, x, y| TileCoord { z, x, y };
+        assert_eq!(xyz(0, 0, 0).flip_y(), xyz(0, 0, 0));
+        assert_eq!(xyz(1, 1, 0).flip_y(), xyz(1, 1, 1));
+        assert_eq!(xyz(2, 3, 1).flip_y(), xyz(2, 3, 2));
+        assert_eq!(xyz(2, 3, 1).flip_y().flip_y(), xyz(2, 3, 1));
+        assert_eq!(
+            xyz(MAX_ZOOM, 0, 0).flip_y(),
+            xyz(MAX_ZOOM, 0, (1 << 30) - 1)
+        );
+        assert_eq!(xyz(31, 0, 1).flip_y(), xyz(31, 0, (1 << 31) - 2));
+        assert_eq!(xyz(32, 0, 0).flip_y(), xyz(32, 0, u32::MAX));
+        assert_eq!(xyz(255, 0, 5).flip_y(), xyz(255, 0, u32::MAX - 5));
+        // out of range values do not overflow
+        assert_eq!(xyz(1, 0, 5).flip_y(), xyz(1, 0, 0));
+    }
+
+    #[test]
+    fn test_ancestor() {
+        let xyz = |z, x, y| TileCoord { z, x, y };
+        let offset = |x, y, scale| TileOffset { x, y, scale };
+        assert_eq!(
+            xyz(3, 5, 6).ancestor(3),
+            Some((xyz(3, 5, 6), offset(0, 0, 1)))
+        );
+        assert_eq!(
+            xyz(3, 5, 6).ancestor(2),
+            Some((xyz(2, 2, 3), offset(1, 0, 2)))
+        );
+        assert_eq!(
+            xyz(3, 5, 6).ancestor(0),
+            Some((xyz(0, 0, 0), offset(5, 6, 8)))
+        );
+        assert_eq!(
+            xyz(MAX_ZOOM, (1 << 30) - 1, 0).ancestor(0),
+            Some((xyz(0, 0, 0), offset((1 << 30) - 1, 0, 1 << 30)))
+        );
+        assert_eq!(xyz(2, 0, 0).ancestor(3), None);
+        assert_eq!(xyz(32, 0, 0).ancestor(0), None);
+    }
+
+    #[test]
+    fn test_parent_and_children() {
+        let xyz = |z, x, y| TileCoord { z, x, y };
+        assert_eq!(xyz(0, 0, 0).parent(), None);
+        assert_eq!(xyz(1, 1, 0).parent(), Some(xyz(0, 0, 0)));
+        assert_eq!(xyz(3, 5, 6).parent(), Some(xyz(2, 2, 3)));
+        assert_eq!(
+            xyz(1, 1, 0).children(),
+            [xyz(2, 2, 0), xyz(2, 3, 0), xyz(2, 2, 1), xyz(2, 3, 1)]
+        );
+        for tile in [xyz(0, 0, 0), xyz(3, 5, 6), xyz(MAX_ZOOM - 1, 7, 9)] {
+            for child in tile.children() {
+                assert!(child.is_valid());
+                assert_eq!(child.parent(), Some(tile));
+            }
+        }
+    }
+
+    #[test]
+    fn test_neighbors() {
+        let xyz = |z, x, y| TileCoord { z, x, y };
+        assert_eq!(xyz(0, 0, 0).neighbors(), vec![]);
+        assert_eq!(
+            xyz(2, 1, 1).neighbors(),
+            vec![
+                xyz(2, 0, 0),
+                xyz(2, 1, 0),
+                xyz(2, 2, 0),
+                xyz(2, 0, 1),
+                xyz(2, 2, 1),
+                xyz(2, 0, 2),
+                xyz(2, 1, 2),
+                xyz(2, 2, 2),
+            ]
+        );
+        // wraps around the antimeridian, but not the poles
+        assert_eq!(
+            xyz(2, 0, 0).neighbors(),
+            vec![
+                xyz(2, 3, 0),
+                xyz(2, 1, 0),
+                xyz(2, 3, 1),
+                xyz(2, 0, 1),
+                xyz(2, 1, 1)
+            ]
+        );
+        assert_eq!(
+            xyz(2, 3, 3).neighbors(),
+            vec![
+                xyz(2, 2, 2),
+                xyz(2, 3, 2),
+                xyz(2, 0, 2),
+                xyz(2, 2, 3),
+                xyz(2, 0, 3)
+            ]
+        );
+        // at zoom 1, the left and the right neighbor are the same tile
+        assert_eq!(
+            xyz(1, 0, 0).neighbors(),
+            vec![xyz(1, 1, 0), xyz(1, 1, 1), xyz(1, 0, 1)]
+        );
+        assert_eq!(xyz(32, u32::MAX, 0).neighbors().len(), 5);
+    }
+
+    #[test]
+    fn test_wrapped() {
+        let xyz = |z, x, y| TileCoord { z, x, y };
+        assert_eq!(xyz(0, 1, 0).wrapped(), xyz(0, 0, 0));
+        assert_eq!(xyz(1, 1, 1).wrapped(), xyz(1, 1, 1));
+        assert_eq!(xyz(1, 2, 1).wrapped(), xyz(1, 0, 1));
+        assert_eq!(xyz(3, 7, 2).wrapped(), xyz(3, 7, 2));
+        assert_eq!(xyz(3, 8, 2).wrapped(), xyz(3, 0, 2));
+        assert_eq!(xyz(3, 9, 2).wrapped(), xyz(3, 1, 2));
+        assert_eq!(xyz(10, 1024, 5).wrapped(), xyz(10, 0, 5));
+        assert_eq!(xyz(10, 2047, 5).wrapped(), xyz(10, 1023, 5));
+        assert_eq!(
+            xyz(MAX_ZOOM, 1 << MAX_ZOOM, 0).wrapped(),
+            xyz(MAX_ZOOM, 0, 0)
+        );
+        // y is not wrapped
+        assert!(!xyz(2, 5, 4).wrapped().is_valid());
+    }
+
+    #[test]
+    fn test_tiles_in_bbox() {
+        let xyz = |z, x, y| TileCoord { z, x, y };
+        let world = [-180.0, -85.0, 180.0, 85.0];
+        let tiles = |bounds, zoom| TileCoord::tiles_in_bbox(bounds, zoom).collect::<Vec<_>>();
+        assert_eq!(tiles(world, 0), vec![xyz(0, 0, 0)]);
+        assert_eq!(
+            tiles(world, 1),
+            vec![xyz(1, 0, 0), xyz(1, 1, 0), xyz(1, 0, 1), xyz(1, 1, 1)]
+        );
+        assert_eq!(TileCoord::tiles_in_bbox(world, 3).count(), 64);
+
+        let bounds = [1.0, 1.0, 179.0, 80.0];
+        assert_eq!(
+            tiles(bounds, 2),
+            vec![xyz(2, 2, 0), xyz(2, 3, 0), xyz(2, 2, 1), xyz(2, 3, 1)]
+        );
+        assert_eq!(tiles([0.1, 0.1, 0.2, 0.2], 2), vec![xyz(2, 2, 1)]);
+    }
+
+    #[test]
+    fn test_tile_coord_is_valid() {
+        let xyz = |z, x, y| TileCoord { z, x, y };
+        assert!(xyz(0, 0, 0).is_valid());
+        assert!(!xyz(0, 1, 0).is_valid());
+        assert!(!xyz(0, 0, 1).is_valid());
+        assert!(xyz(1, 1, 1).is_valid());
+        assert!(!xyz(1, 99999, 0).is_valid());
+        assert!(xyz(MAX_ZOOM, (1 << 30) - 1, (1 << 30) - 1).is_valid());
+        assert!(!xyz(MAX_ZOOM, 1 << 30, 0).is_valid());
+        assert!(xyz(31, (1 << 31) - 1, 0).is_valid());
+        assert!(!xyz(31, 1 << 31, 0).is_valid());
+        assert!(xyz(32, u32::MAX, u32::MAX).is_valid());
+        assert!(xyz(255, u32::MAX, u32::MAX).is_valid());
+    }
+
+    #[test]
+    fn test_tile_coord_serde() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct TileDebug {
+            #[serde(with = "tile_coord_array")]
+            tile: TileCoord,
+        }
+
+        let xyz = TileCoord { z: 1, x: 2, y: 3 };
+        let json = serde_json::to_string(&xyz).unwrap();
+        assert_eq!(json, r#"{"z":1,"x":2,"y":3}"#);
+        assert_eq!(serde_json::from_str::<TileCoord>(&json).unwrap(), xyz);
+
+        let value = TileDebug { tile: xyz };
+        let json = serde_json::to_string(&value).unwrap();
+        assert_eq!(json, r#"{"tile":[1,2,3]}"#);
+        assert_eq!(serde_json::from_str::<TileDebug>(&json).unwrap(), value);
+        assert!(serde_json::from_str::<TileDebug>(r#"{"tile":[256,0,0]}"#).is_err());
+        assert!(serde_json::from_str::<TileDebug>(r#"{"tile":[1,2]}"#).is_err());
+    }
+
+    #[test]
+    fn test_tile_coord_from_path() {
+        let xyz = |z, x, y| TileCoord { z, x, y };
+        assert_eq!(
+            TileCoord::from_path("1/2/3"),
+            Err(TileCoordError::OutOfRange(xyz(1, 2, 3)))
+        );
+        assert_eq!(TileCoord::from_path("2/1/3"), Ok(xyz(2, 1, 3)));
+        assert_eq!(TileCoord::from_path("2,1,3"), Ok(xyz(2, 1, 3)));
+        assert_eq!(TileCoord::from_path("0/0/0"), Ok(xyz(0, 0, 0)));
+        assert_eq!(
+            TileCoord::from_path("32/4294967295/0"),
+            Ok(xyz(32, u32::MAX, 0))
+        );
+        for v in [xyz(3, 7, 2), xyz(14, 8000, 5000)] {
+            assert_eq!(TileCoord::from_path(&format!("{v}")), Ok(v));
+            assert_eq!(TileCoord::from_path(&format!("{v:#}")), Ok(v));
+        }
+
+        assert_eq!(
+            TileCoord::from_path("0/1/0"),
+            Err(TileCoordError::OutOfRange(xyz(0, 1, 0)))
+        );
+        assert_eq!(
+            TileCoord::from_path("2/0/4"),
+            Err(TileCoordError::OutOfRange(xyz(2, 0, 4)))
+        );
+        assert_eq!(
+            TileCoord::from_path("2/0/4").unwrap_err().to_string(),
+            "Tile coordinates 2/0/4 are out of range, x and y must be less than 4"
+        );
+
+        for v in [
+            "", "1", "1/2", "1/2/3/4", "1/2,3", "a/0/0", "1/-1/0", "256/0/0", "1//0",
+        ] {
+            assert_eq!(
+                TileCoord::from_path(v),
+                Err(TileCoordError::InvalidFormat(v.to_string()))
+            );
+        }
+    }
+
     #[test]
     fn test_tile_colrow() {
         assert_eq!((0, 0), tile_index(-180.0, 85.0511, 0));
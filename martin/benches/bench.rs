@@ -62,11 +62,23 @@ async fn process_tile(sources: &TileSources) {
         .unwrap();
 }
 
+async fn process_single_tile(sources: &TileSources) {
+    sources
+        .get_single_source_tile("null", TileCoord { z: 0, x: 0, y: 0 }, None, Some(0))
+        .await
+        .unwrap()
+        .unwrap();
+}
+
 fn bench_null_source(c: &mut Criterion) {
     let sources = TileSources::new(vec![vec![Box::new(NullSource::new())]]);
     c.bench_function("get_table_source_tile", |b| {
         b.to_async(FuturesExecutor).iter(|| process_tile(&sources));
     });
+    c.bench_function("get_single_source_tile", |b| {
+        b.to_async(FuturesExecutor)
+            .iter(|| process_single_tile(&sources));
+    });
 }
 
 criterion_group! {
@@ -43,6 +43,10 @@ pub struct MetaArgs {
     /// By default, only print if sources are auto-detected.
     #[arg(long)]
     pub save_config: Option<PathBuf>,
+    /// Check the metadata of all tile sources for problems like a minzoom above the maxzoom,
+    /// report them, and exit without starting the server. Exits with an error if any source is invalid.
+    #[arg(long)]
+    pub validate: bool,
     /// Main cache size (in MB)
     #[arg(short = 'C', long)]
     pub cache_size: Option<u64>,
@@ -181,6 +185,16 @@ mod tests {
         assert_eq!(args, expected);
     }
 
+    #[test]
+    fn cli_validate() {
+        let args = parse(&["martin", "--validate"]).unwrap();
+        let meta = MetaArgs {
+            validate: true,
+            ..Default::default()
+        };
+        assert_eq!(args, (Config::default(), meta));
+    }
+
     #[cfg(feature = "postgres")]
     #[test]
     fn cli_with_config() {
@@ -2,7 +2,7 @@ use clap::Parser;
 use log::{error, info, log_enabled};
 use martin::args::{Args, OsEnv};
 use martin::srv::new_server;
-use martin::{read_config, Config, MartinResult};
+use martin::{read_config, Config, MartinError, MartinResult, TileSources};
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
@@ -11,6 +11,7 @@ async fn start(args: Args) -> MartinResult<()> {
 
     let env = OsEnv::default();
     let save_config = args.meta.save_config.clone();
+    let args_validate = args.meta.validate;
     let mut config = if let Some(ref cfg_filename) = args.meta.config {
         info!("Using {}", cfg_filename.display());
         read_config(cfg_filename, &env)?
@@ -29,6 +30,10 @@ async fn start(args: Args) -> MartinResult<()> {
         info!("Use --save-config to save or print Martin configuration.");
     }
 
+    if args_validate {
+        return validate(&sources.tiles);
+    }
+
     #[cfg(feature = "webui")]
     let web_ui_mode = config.srv.web_ui.unwrap_or_default();
 
@@ -48,6 +53,22 @@ async fn start(args: Args) -> MartinResult<()> {
     server.await
 }
 
+/// Log the problems of every invalid tile source, and fail if there are any
+fn validate(sources: &TileSources) -> MartinResult<()> {
+    let invalid = sources.validate_all();
+    for (id, problems) in &invalid {
+        for problem in problems {
+            error!("Source {id}: {problem}");
+        }
+    }
+    if invalid.is_empty() {
+        info!("All tile sources are valid");
+        Ok(())
+    } else {
+        Err(MartinError::InvalidSources(invalid.len()))
+    }
+}
+
 #[actix_web::main]
 async fn main() {
     let env = env_logger::Env::default().default_filter_or("martin=info");
@@ -7,7 +7,7 @@ use std::path::{Path, PathBuf};
 use std::pin::Pin;
 
 use futures::future::try_join_all;
-use log::info;
+use log::{debug, info};
 use serde::{Deserialize, Serialize};
 use subst::VariableMap;
 
@@ -126,7 +126,12 @@ impl Config {
                 MainCache::builder()
                     .weigher(|_key, value: &CacheValue| -> u32 {
                         match value {
-                            CacheValue::Tile(v) => v.len().try_into().unwrap_or(u32::MAX),
+                            CacheValue::Tile(v) => v.data.len().try_into().unwrap_or(u32::MAX),
+                            CacheValue::TileEtag(v) => v
+                                .as_ref()
+                                .map_or(0, String::len)
+                                .try_into()
+                                .unwrap_or(u32::MAX),
                             #[cfg(feature = "pmtiles")]
                             CacheValue::PmtDirectory(v) => {
                                 v.get_approx_byte_size().try_into().unwrap_or(u32::MAX)
@@ -179,7 +184,17 @@ impl Config {
             sources.push(Box::pin(val));
         }
 
-        Ok(TileSources::new(try_join_all(sources).await?))
+        let mut tiles = TileSources::new(try_join_all(sources).await?);
+        debug!("Loaded {tiles:?}");
+        tiles.check_miss_behaviors()?;
+        if let Some(id) = &self.srv.default_source {
+            tiles.set_default(id)?;
+        }
+        Ok(if let Some(max) = self.srv.max_merge_sources {
+            tiles.max_merge_sources(max)
+        } else {
+            tiles
+        })
     }
 
     pub fn save_to_file(&self, file_name: PathBuf) -> MartinResult<()> {
@@ -70,12 +70,14 @@ pub trait SourceConfigExtras: ConfigExtras {
         &self,
         id: String,
         path: PathBuf,
+        tags: Vec<String>,
     ) -> impl std::future::Future<Output = FileResult<TileInfoSource>> + Send;
 
     fn new_sources_url(
         &self,
         id: String,
         url: Url,
+        tags: Vec<String>,
     ) -> impl std::future::Future<Output = FileResult<TileInfoSource>> + Send;
 }
 
@@ -216,6 +218,15 @@ impl FileConfigSrc {
         }
     }
 
+    /// Labels of the source, see [`FileConfigSource::tags`]
+    #[must_use]
+    pub fn get_tags(&self) -> &[String] {
+        match self {
+            Self::Path(_) => &[],
+            Self::Obj(o) => &o.tags,
+        }
+    }
+
     pub fn abs_path(&self) -> FileResult<PathBuf> {
         let path = self.get_path();
         path.canonicalize().map_err(|e| IoError(e, path.clone()))
@@ -225,6 +236,9 @@ impl FileConfigSrc {
 #[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
 pub struct FileConfigSource {
     pub path: PathBuf,
+    /// Labels of the source, e.g. `basemap`, to filter the catalog by
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
 }
 
 pub async fn resolve_files<T: SourceConfigExtras>(
@@ -259,8 +273,13 @@ async fn resolve_int<T: SourceConfigExtras>(
                 let dup = !files.insert(source.get_path().clone());
                 let dup = if dup { "duplicate " } else { "" };
                 let id = idr.resolve(&id, url.to_string());
+                let tags = source.get_tags().to_vec();
                 configs.insert(id.clone(), source);
-                results.push(cfg.custom.new_sources_url(id.clone(), url.clone()).await?);
+                results.push(
+                    cfg.custom
+                        .new_sources_url(id.clone(), url.clone(), tags)
+                        .await?,
+                );
                 info!("Configured {dup}source {id} from {}", sanitize_url(&url));
             } else {
                 let can = source.abs_path()?;
@@ -274,7 +293,8 @@ async fn resolve_int<T: SourceConfigExtras>(
                 let id = idr.resolve(&id, can.to_string_lossy().to_string());
                 info!("Configured {dup}source {id} from {}", can.display());
                 configs.insert(id.clone(), source.clone());
-                results.push(cfg.custom.new_sources(id, source.into_path()).await?);
+                let tags = source.get_tags().to_vec();
+                results.push(cfg.custom.new_sources(id, source.into_path(), tags).await?);
             }
         }
     }
@@ -294,7 +314,11 @@ async fn resolve_int<T: SourceConfigExtras>(
 
             let id = idr.resolve(id, url.to_string());
             configs.insert(id.clone(), FileConfigSrc::Path(path));
-            results.push(cfg.custom.new_sources_url(id.clone(), url.clone()).await?);
+            results.push(
+                cfg.custom
+                    .new_sources_url(id.clone(), url.clone(), Vec::new())
+                    .await?,
+            );
             info!("Configured source {id} from URL {}", sanitize_url(&url));
         } else {
             let is_dir = path.is_dir();
@@ -323,7 +347,7 @@ async fn resolve_int<T: SourceConfigExtras>(
                 info!("Configured source {id} from {}", can.display());
                 files.insert(can);
                 configs.insert(id.clone(), FileConfigSrc::Path(path.clone()));
-                results.push(cfg.custom.new_sources(id, path).await?);
+                results.push(cfg.custom.new_sources(id, path, Vec::new()).await?);
             }
         }
     }
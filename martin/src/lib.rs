@@ -4,8 +4,25 @@
 mod config;
 pub use config::{read_config, Config, ServerState};
 
+mod mvt;
+pub use mvt::{concat_mvt, merge_mvt_layers};
+
 mod source;
-pub use source::{CatalogSourceEntry, Source, Tile, TileData, TileSources, UrlQuery};
+pub use source::{
+    diff_catalogs, empty_tile, encode_merged, normalize_query, AsSource, BlendSource, CachedSource,
+    CatalogDiff, CatalogSourceEntry, DebugGridSource, FallbackOn, FallbackSource, MemorySource,
+    MergedSources, MeteredSource, MissBehavior, OverzoomSource, RetryPolicy, RetrySource,
+    RewriteUrlSource, ShardedSource, SharedSource, Source, SourceError, Tile, TileCatalog,
+    TileClip, TileData, TileMetric, TileSources, TileSourcesBuilder, TileSourcesOf, TileTransform,
+    TileValidation, TimeoutSource, TranscodeOptions, TranscodeSource, TransformSource, UrlQuery,
+    ValidatingSource, ZoomCheck, ZoomShiftSource, DEFAULT_CRS, DEFAULT_SOURCE_ALIAS,
+    DEFAULT_TILE_SIZE, MERGE_DELIMITER,
+};
+
+#[cfg(any(test, feature = "test-utils"))]
+mod test_source;
+#[cfg(any(test, feature = "test-utils"))]
+pub use test_source::TestSource;
 
 mod utils;
 pub use utils::{
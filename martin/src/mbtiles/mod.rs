@@ -1,21 +1,24 @@
 use std::fmt::{Debug, Formatter};
 use std::io;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::SystemTime;
 
 use async_trait::async_trait;
-use log::trace;
+use futures::stream::BoxStream;
+use futures::StreamExt as _;
+use log::{trace, warn};
 use martin_tile_utils::{TileCoord, TileInfo};
-use mbtiles::MbtilesPool;
+use mbtiles::{MbtType, MbtilesPool};
 use serde::{Deserialize, Serialize};
 use tilejson::TileJSON;
 use url::Url;
 
 use crate::config::UnrecognizedValues;
-use crate::file_config::FileError::{AcquireConnError, InvalidMetadata, IoError};
+use crate::file_config::FileError::{AcquireConnError, InvalidFilePath, InvalidMetadata, IoError};
 use crate::file_config::{ConfigExtras, FileResult, SourceConfigExtras};
 use crate::source::{TileData, TileInfoSource, UrlQuery};
-use crate::{MartinResult, Source};
+use crate::{MartinResult, Source, Tile};
 
 #[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
 pub struct MbtConfig {
@@ -30,14 +33,27 @@ impl ConfigExtras for MbtConfig {
 }
 
 impl SourceConfigExtras for MbtConfig {
-    async fn new_sources(&self, id: String, path: PathBuf) -> FileResult<TileInfoSource> {
-        Ok(Box::new(MbtSource::new(id, path).await?))
+    async fn new_sources(
+        &self,
+        id: String,
+        path: PathBuf,
+        tags: Vec<String>,
+    ) -> FileResult<TileInfoSource> {
+        Ok(Box::new(MbtSource {
+            tags,
+            ..MbtSource::new(id, path).await?
+        }))
     }
 
     // TODO: Remove #[allow] after switching to Rust/Clippy v1.78+ in CI
     //       See https://github.com/rust-lang/rust-clippy/pull/12323
     #[allow(clippy::no_effect_underscore_binding)]
-    async fn new_sources_url(&self, _id: String, _url: Url) -> FileResult<TileInfoSource> {
+    async fn new_sources_url(
+        &self,
+        _id: String,
+        _url: Url,
+        _tags: Vec<String>,
+    ) -> FileResult<TileInfoSource> {
         unreachable!()
     }
 }
@@ -48,6 +64,10 @@ pub struct MbtSource {
     mbtiles: Arc<MbtilesPool>,
     tilejson: TileJSON,
     tile_info: TileInfo,
+    file_size: Option<u64>,
+    tile_count: Option<u64>,
+    mbt_type: Option<MbtType>,
+    tags: Vec<String>,
 }
 
 impl Debug for MbtSource {
@@ -71,13 +91,35 @@ impl MbtSource {
         let meta = mbt
             .get_metadata()
             .await
-            .map_err(|e| InvalidMetadata(e.to_string(), path))?;
+            .map_err(|e| InvalidMetadata(e.to_string(), path.clone()))?;
+
+        // The type is only needed to look up tile hashes, so a failure is not fatal
+        let mbt_type = mbt
+            .detect_type()
+            .await
+            .map_err(|e| warn!("Unable to detect MBTiles type of {}: {e}", path.display()))
+            .ok();
+
+        // The tile count is only reported in the catalog, so a failure is not fatal
+        let tile_count = match mbt_type {
+            Some(mbt_type) => mbt
+                .get_approx_tile_count(mbt_type)
+                .await
+                .map_err(|e| warn!("Unable to count the tiles of {}: {e}", path.display()))
+                .ok()
+                .flatten(),
+            None => None,
+        };
 
         Ok(Self {
             id,
             mbtiles: Arc::new(mbt),
             tilejson: meta.tilejson,
             tile_info: meta.tile_info,
+            file_size: path.metadata().ok().map(|m| m.len()),
+            tile_count,
+            mbt_type,
+            tags: Vec::new(),
         })
     }
 }
@@ -100,6 +142,36 @@ impl Source for MbtSource {
         Box::new(self.clone())
     }
 
+    fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    async fn health_check(&self) -> MartinResult<()> {
+        let path = Path::new(self.mbtiles.filepath());
+        if !path.is_file() {
+            Err(InvalidFilePath(path.to_path_buf()))?;
+        }
+        Ok(())
+    }
+
+    fn last_modified(&self) -> Option<SystemTime> {
+        Path::new(self.mbtiles.filepath())
+            .metadata()
+            .ok()?
+            .modified()
+            .ok()
+    }
+
+    /// Taken from the `SQLite` statistics when the source is opened, if the file was analyzed
+    fn approximate_tile_count(&self) -> Option<u64> {
+        self.tile_count
+    }
+
+    /// The size of the `MBTiles` file, which includes the metadata and indexes
+    fn approximate_total_bytes(&self) -> Option<u64> {
+        self.file_size
+    }
+
     async fn get_tile(
         &self,
         xyz: TileCoord,
@@ -123,6 +195,67 @@ impl Source for MbtSource {
             Ok(Vec::new())
         }
     }
+
+    async fn get_tile_opt(
+        &self,
+        xyz: TileCoord,
+        _url_query: Option<&UrlQuery>,
+    ) -> MartinResult<Option<Tile>> {
+        let tile = self
+            .mbtiles
+            .get_tile(xyz.z, xyz.x, xyz.y)
+            .await
+            .map_err(|_| AcquireConnError(self.id.clone()))?;
+        Ok(tile.map(|data| Tile::new(data, self.tile_info)))
+    }
+
+    async fn has_tile(&self, xyz: TileCoord, _url_query: Option<&UrlQuery>) -> MartinResult<bool> {
+        Ok(self
+            .mbtiles
+            .has_tile(xyz.z, xyz.x, xyz.y)
+            .await
+            .map_err(|_| AcquireConnError(self.id.clone()))?)
+    }
+
+    async fn get_tiles_batch(
+        &self,
+        coords: &[TileCoord],
+        _url_query: Option<&UrlQuery>,
+    ) -> MartinResult<Vec<Option<Tile>>> {
+        let tiles = self
+            .mbtiles
+            .get_tiles(coords)
+            .await
+            .map_err(|_| AcquireConnError(self.id.clone()))?;
+        Ok(tiles
+            .into_iter()
+            .map(|data| data.map(|data| Tile::new(data, self.tile_info)))
+            .collect())
+    }
+
+    fn tile_stream(&self) -> Option<BoxStream<'_, MartinResult<(TileCoord, Tile)>>> {
+        let stream = self.mbtiles.stream_tiles().map(|res| {
+            let (xyz, data) = res.map_err(|_| AcquireConnError(self.id.clone()))?;
+            Ok((xyz, Tile::new(data, self.tile_info)))
+        });
+        Some(stream.boxed())
+    }
+
+    /// Use the tile hash stored by `flat-with-hash` and `normalized` `MBTiles`
+    async fn get_tile_etag(
+        &self,
+        xyz: TileCoord,
+        _url_query: Option<&UrlQuery>,
+    ) -> MartinResult<Option<String>> {
+        let Some(mbt_type) = self.mbt_type else {
+            return Ok(None);
+        };
+        Ok(self
+            .mbtiles
+            .get_tile_hash(mbt_type, xyz.z, xyz.x, xyz.y)
+            .await
+            .map_err(|_| AcquireConnError(self.id.clone()))?)
+    }
 }
 
 #[cfg(test)]
@@ -130,10 +263,100 @@ mod tests {
     use std::collections::BTreeMap;
     use std::path::PathBuf;
 
+    use std::time::SystemTime;
+
     use indoc::indoc;
 
-    use crate::file_config::{FileConfigEnum, FileConfigSource, FileConfigSrc};
-    use crate::mbtiles::MbtConfig;
+    use martin_tile_utils::TileCoord;
+
+    use crate::file_config::{
+        FileConfigEnum, FileConfigSource, FileConfigSrc, SourceConfigExtras as _,
+    };
+    use crate::mbtiles::{MbtConfig, MbtSource};
+    use crate::Source as _;
+
+    #[actix_rt::test]
+    async fn tiles_batch() {
+        let path = PathBuf::from("../tests/fixtures/mbtiles/world_cities.mbtiles");
+        let src = MbtSource::new("m".to_string(), path).await.unwrap();
+        let coords = [
+            TileCoord { z: 1, x: 1, y: 0 },
+            TileCoord { z: 0, x: 0, y: 0 },
+            TileCoord { z: 30, x: 0, y: 0 },
+            TileCoord { z: 1, x: 1, y: 0 },
+        ];
+        let tiles = src.get_tiles_batch(&coords, None).await.unwrap();
+        assert_eq!(tiles.len(), coords.len());
+        assert!(tiles[2].is_none());
+        for (xyz, tile) in coords.iter().zip(tiles) {
+            let expected = src.get_tile(*xyz, None).await.unwrap();
+            assert_eq!(tile.map(|t| t.data).unwrap_or_default(), expected);
+        }
+    }
+
+    #[actix_rt::test]
+    async fn tile_opt() {
+        let path = PathBuf::from("../tests/fixtures/mbtiles/world_cities.mbtiles");
+        let src = MbtSource::new("m".to_string(), path).await.unwrap();
+        let tile = src.get_tile_opt(TileCoord { z: 0, x: 0, y: 0 }, None);
+        assert!(tile.await.unwrap().is_some());
+        let tile = src.get_tile_opt(TileCoord { z: 30, x: 0, y: 0 }, None);
+        assert!(tile.await.unwrap().is_none());
+    }
+
+    #[actix_rt::test]
+    async fn has_tile() {
+        let path = PathBuf::from("../tests/fixtures/mbtiles/world_cities.mbtiles");
+        let src = MbtSource::new("m".to_string(), path).await.unwrap();
+        assert!(src
+            .has_tile(TileCoord { z: 0, x: 0, y: 0 }, None)
+            .await
+            .unwrap());
+        assert!(!src
+            .has_tile(TileCoord { z: 30, x: 0, y: 0 }, None)
+            .await
+            .unwrap());
+    }
+
+    #[actix_rt::test]
+    async fn tile_stream() {
+        use futures::TryStreamExt as _;
+
+        let path = PathBuf::from("../tests/fixtures/mbtiles/world_cities.mbtiles");
+        let src = MbtSource::new("m".to_string(), path).await.unwrap();
+        let tiles: Vec<_> = src.tile_stream().unwrap().try_collect().await.unwrap();
+        assert!(!tiles.is_empty());
+        for (xyz, tile) in tiles {
+            assert_eq!(tile.data, src.get_tile(xyz, None).await.unwrap());
+        }
+    }
+
+    #[actix_rt::test]
+    async fn health_check() {
+        let path = PathBuf::from("../tests/fixtures/mbtiles/world_cities.mbtiles");
+        let src = MbtSource::new("m".to_string(), path).await.unwrap();
+        assert!(src.health_check().await.is_ok());
+    }
+
+    #[actix_rt::test]
+    async fn tags() {
+        let path = PathBuf::from("../tests/fixtures/mbtiles/world_cities.mbtiles");
+        let src = MbtConfig::default()
+            .new_sources("m".to_string(), path, vec!["basemap".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(src.tags(), ["basemap"]);
+        assert_eq!(src.get_catalog_entry().tags, ["basemap"]);
+    }
+
+    #[actix_rt::test]
+    async fn last_modified() {
+        let path = PathBuf::from("../tests/fixtures/mbtiles/world_cities.mbtiles");
+        let src = MbtSource::new("m".to_string(), path).await.unwrap();
+        let modified = src.last_modified().unwrap();
+        assert!(modified > SystemTime::UNIX_EPOCH);
+        assert!(modified <= SystemTime::now());
+    }
 
     #[test]
     fn parse() {
@@ -149,6 +372,9 @@ mod tests {
                 pm-src3: https://example.org/file3.ext
                 pm-src4:
                   path: https://example.org/file4.ext
+                pm-src5:
+                  path: /tmp/file5.ext
+                  tags: [basemap]
         "})
         .unwrap();
         let res = cfg.finalize("").unwrap();
@@ -176,6 +402,7 @@ mod tests {
                     "pm-src2".to_string(),
                     FileConfigSrc::Obj(FileConfigSource {
                         path: PathBuf::from("/tmp/file.ext"),
+                        ..Default::default()
                     })
                 ),
                 (
@@ -186,6 +413,14 @@ mod tests {
                     "pm-src4".to_string(),
                     FileConfigSrc::Obj(FileConfigSource {
                         path: PathBuf::from("https://example.org/file4.ext"),
+                        ..Default::default()
+                    })
+                ),
+                (
+                    "pm-src5".to_string(),
+                    FileConfigSrc::Obj(FileConfigSource {
+                        path: PathBuf::from("/tmp/file5.ext"),
+                        tags: vec!["basemap".to_string()],
                     })
                 ),
             ]))
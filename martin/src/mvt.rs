@@ -0,0 +1,526 @@
+use std::collections::HashMap;
+
+use martin_tile_utils::{Encoding, Format, TileInfo};
+
+use crate::source::SourceError::{CannotConcatenate, MvtMergeError};
+use crate::source::{SourceError, Tile};
+
+/// Concatenate MVT tiles of the same encoding, i.e. uncompressed or gzip, into a single tile.
+/// Empty tiles have no layers and are skipped, so the result has the tile info of the first
+/// non-empty tile. If all tiles are empty, the result is an empty tile with the info of the first one.
+/// Use [`encode_merged`](crate::encode_merged) instead to merge tiles with different encodings.
+#[must_use]
+pub fn concat_mvt(tiles: &[Tile]) -> Tile {
+    let mut non_empty = tiles.iter().filter(|tile| !tile.data.is_empty());
+    let Some(first) = non_empty.next() else {
+        let info = tiles.first().map_or(Format::Mvt.into(), |tile| tile.info);
+        return Tile::new(Vec::new(), info);
+    };
+    let mut data = first.data.clone();
+    for tile in non_empty {
+        data.extend_from_slice(&tile.data);
+    }
+    Tile::new(data, first.info)
+}
+
+/// Merge MVT tiles into a single uncompressed tile, combining the features of the layers
+/// with the same name into one layer. Unlike [`concat_mvt`], the result has no duplicate layers,
+/// which some renderers mishandle. Keys and values of the merged layers are deduplicated,
+/// and layers that appear only once are copied as is. Tiles may have mixed encodings,
+/// and empty tiles are skipped. Layers with the same name must have the same extent.
+pub fn merge_mvt_layers(tiles: &[Tile]) -> Result<Tile, SourceError> {
+    let mut decoded = Vec::with_capacity(tiles.len());
+    for tile in tiles {
+        if tile.info.format != Format::Mvt {
+            return Err(CannotConcatenate(tile.info.format));
+        }
+        decoded.push(tile.clone().decode()?.data);
+    }
+
+    // layers grouped by name, in the order the names first appear
+    let mut layers: Vec<(&[u8], Vec<&[u8]>)> = Vec::new();
+    for data in &decoded {
+        let mut reader = PbfReader::new(data);
+        while let Some(field) = reader.next_field()? {
+            if let (3, PbfValue::Bytes(layer)) = (field.number, field.value) {
+                let name = mvt_layer_name(layer)?;
+                match layers.iter_mut().find(|(n, _)| *n == name) {
+                    Some((_, group)) => group.push(layer),
+                    None => layers.push((name, vec![layer])),
+                }
+            }
+        }
+    }
+
+    let mut data = Vec::new();
+    for (name, group) in layers {
+        if let [layer] = group[..] {
+            write_pbf_bytes(&mut data, 3, layer);
+        } else {
+            write_pbf_bytes(&mut data, 3, &merge_mvt_layer_group(name, &group)?);
+        }
+    }
+    Ok(Tile::new(
+        data,
+        TileInfo::new(Format::Mvt, Encoding::Uncompressed),
+    ))
+}
+
+fn mvt_layer_name(layer: &[u8]) -> Result<&[u8], SourceError> {
+    let mut reader = PbfReader::new(layer);
+    while let Some(field) = reader.next_field()? {
+        if let (1, PbfValue::Bytes(name)) = (field.number, field.value) {
+            return Ok(name);
+        }
+    }
+    Err(MvtMergeError("layer has no name".to_string()))
+}
+
+/// Keys or values of a merged layer, each stored once
+#[derive(Default)]
+struct PbfTable<'a> {
+    items: Vec<&'a [u8]>,
+    index: HashMap<&'a [u8], usize>,
+}
+
+impl<'a> PbfTable<'a> {
+    fn insert(&mut self, item: &'a [u8]) -> usize {
+        *self.index.entry(item).or_insert_with(|| {
+            self.items.push(item);
+            self.items.len() - 1
+        })
+    }
+}
+
+fn merge_mvt_layer_group(name: &[u8], layers: &[&[u8]]) -> Result<Vec<u8>, SourceError> {
+    let mut version = 1;
+    let mut extent = None;
+    let mut keys = PbfTable::default();
+    let mut values = PbfTable::default();
+    let mut features = Vec::new();
+
+    for &layer in layers {
+        let mut layer_extent = 4096;
+        let mut layer_keys = Vec::new();
+        let mut layer_values = Vec::new();
+        let mut layer_features = Vec::new();
+        let mut reader = PbfReader::new(layer);
+        while let Some(field) = reader.next_field()? {
+            match (field.number, field.value) {
+                (2, PbfValue::Bytes(feature)) => layer_features.push(feature),
+                (3, PbfValue::Bytes(key)) => layer_keys.push(keys.insert(key)),
+                (4, PbfValue::Bytes(value)) => layer_values.push(values.insert(value)),
+                (5, PbfValue::Varint(value)) => layer_extent = value,
+                (15, PbfValue::Varint(value)) => version = version.max(value),
+                _ => {}
+            }
+        }
+        match extent {
+            Some(extent) if extent != layer_extent => Err(MvtMergeError(format!(
+                "layer {} has different extents {extent} and {layer_extent}",
+                String::from_utf8_lossy(name)
+            )))?,
+            _ => extent = Some(layer_extent),
+        }
+        for feature in layer_features {
+            features.push(remap_mvt_tags(feature, &layer_keys, &layer_values)?);
+        }
+    }
+
+    let mut data = Vec::new();
+    write_pbf_uint(&mut data, 15, version);
+    write_pbf_bytes(&mut data, 1, name);
+    for feature in &features {
+        write_pbf_bytes(&mut data, 2, feature);
+    }
+    for key in keys.items {
+        write_pbf_bytes(&mut data, 3, key);
+    }
+    for value in values.items {
+        write_pbf_bytes(&mut data, 4, value);
+    }
+    write_pbf_uint(&mut data, 5, extent.unwrap_or(4096));
+    Ok(data)
+}
+
+/// Re-encode an MVT feature with its tags pointing to the keys and values of the merged layer
+fn remap_mvt_tags(
+    feature: &[u8],
+    keys: &[usize],
+    values: &[usize],
+) -> Result<Vec<u8>, SourceError> {
+    let mut data = Vec::new();
+    let mut tags = Vec::new();
+    let mut reader = PbfReader::new(feature);
+    while let Some(field) = reader.next_field()? {
+        match (field.number, field.value) {
+            (2, PbfValue::Bytes(packed)) => {
+                let mut packed = PbfReader::new(packed);
+                while !packed.is_empty() {
+                    tags.push(packed.varint()?);
+                }
+            }
+            (2, PbfValue::Varint(tag)) => tags.push(tag),
+            _ => data.extend_from_slice(field.raw),
+        }
+    }
+    if tags.len() % 2 != 0 {
+        return Err(MvtMergeError(
+            "feature has an odd number of tags".to_string(),
+        ));
+    }
+
+    let remap = |index: u64, table: &[usize]| {
+        usize::try_from(index)
+            .ok()
+            .and_then(|index| table.get(index))
+            .map(|index| *index as u64)
+            .ok_or_else(|| MvtMergeError(format!("feature has an invalid tag {index}")))
+    };
+    let mut packed = Vec::new();
+    for pair in tags.chunks_exact(2) {
+        write_pbf_varint(&mut packed, remap(pair[0], keys)?);
+        write_pbf_varint(&mut packed, remap(pair[1], values)?);
+    }
+    if !packed.is_empty() {
+        write_pbf_bytes(&mut data, 2, &packed);
+    }
+    Ok(data)
+}
+
+/// A minimal reader of the protobuf wire format, enough to merge MVT layers
+/// without decoding their geometries, see <https://github.com/mapbox/vector-tile-spec>
+pub(crate) struct PbfReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+pub(crate) enum PbfValue<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+    Fixed,
+}
+
+pub(crate) struct PbfField<'a> {
+    pub(crate) number: u64,
+    pub(crate) value: PbfValue<'a>,
+    /// The encoded field, including its key, e.g. to copy it unchanged
+    pub(crate) raw: &'a [u8],
+}
+
+impl<'a> PbfReader<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+
+    pub(crate) fn varint(&mut self) -> Result<u64, SourceError> {
+        let mut value = 0_u64;
+        for shift in (0..64).step_by(7) {
+            let Some(&byte) = self.data.get(self.pos) else {
+                break;
+            };
+            self.pos += 1;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(MvtMergeError("invalid varint".to_string()))
+    }
+
+    fn take(&mut self, len: u64) -> Result<&'a [u8], SourceError> {
+        let end = usize::try_from(len)
+            .ok()
+            .and_then(|len| self.pos.checked_add(len))
+            .filter(|end| *end <= self.data.len())
+            .ok_or_else(|| MvtMergeError("truncated field".to_string()))?;
+        let bytes = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    pub(crate) fn next_field(&mut self) -> Result<Option<PbfField<'a>>, SourceError> {
+        if self.is_empty() {
+            return Ok(None);
+        }
+        let start = self.pos;
+        let key = self.varint()?;
+        let value = match key & 7 {
+            0 => PbfValue::Varint(self.varint()?),
+            1 => {
+                self.take(8)?;
+                PbfValue::Fixed
+            }
+            2 => {
+                let len = self.varint()?;
+                PbfValue::Bytes(self.take(len)?)
+            }
+            5 => {
+                self.take(4)?;
+                PbfValue::Fixed
+            }
+            wire_type => Err(MvtMergeError(format!("unsupported wire type {wire_type}")))?,
+        };
+        Ok(Some(PbfField {
+            number: key >> 3,
+            value,
+            raw: &self.data[start..self.pos],
+        }))
+    }
+}
+
+pub(crate) fn write_pbf_varint(data: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        data.push(value.to_le_bytes()[0] | 0x80);
+        value >>= 7;
+    }
+    data.push(value.to_le_bytes()[0]);
+}
+
+pub(crate) fn write_pbf_uint(data: &mut Vec<u8>, number: u64, value: u64) {
+    write_pbf_varint(data, number << 3);
+    write_pbf_varint(data, value);
+}
+
+pub(crate) fn write_pbf_bytes(data: &mut Vec<u8>, number: u64, bytes: &[u8]) {
+    write_pbf_varint(data, (number << 3) | 2);
+    write_pbf_varint(data, bytes.len() as u64);
+    data.extend_from_slice(bytes);
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    #[test]
+    fn concat_mvt_tiles() {
+        let info = TileInfo::new(Format::Mvt, Encoding::Gzip);
+        let tile = |data: &[u8]| Tile::new(data.to_vec(), info);
+
+        let merged = concat_mvt(&[tile(&[]), tile(&[1, 2]), tile(&[]), tile(&[3])]);
+        assert_eq!(merged.data, vec![1_u8, 2, 3]);
+        assert_eq!(merged.info, info);
+
+        let merged = concat_mvt(&[tile(&[]), tile(&[1, 2]), tile(&[])]);
+        assert_eq!(merged.data, vec![1_u8, 2]);
+
+        let merged = concat_mvt(&[tile(&[]), tile(&[])]);
+        assert!(merged.data.is_empty());
+        assert_eq!(merged.info, info);
+
+        let merged = concat_mvt(&[]);
+        assert!(merged.data.is_empty());
+        assert_eq!(merged.info, TileInfo::from(Format::Mvt));
+    }
+
+    /// Build an MVT layer with a point feature for each `(key, value)` pair
+    pub(crate) fn mvt_layer(name: &str, extent: u64, tags: &[(&str, &str)]) -> Vec<u8> {
+        let mut layer = Vec::new();
+        write_pbf_uint(&mut layer, 15, 2);
+        write_pbf_bytes(&mut layer, 1, name.as_bytes());
+        for idx in 0..tags.len() {
+            let mut packed = Vec::new();
+            write_pbf_varint(&mut packed, idx as u64);
+            write_pbf_varint(&mut packed, idx as u64);
+            let mut feature = Vec::new();
+            write_pbf_bytes(&mut feature, 2, &packed);
+            write_pbf_uint(&mut feature, 3, 1);
+            write_pbf_bytes(&mut feature, 4, &[9, 2, 2]);
+            write_pbf_bytes(&mut layer, 2, &feature);
+        }
+        for (key, _) in tags {
+            write_pbf_bytes(&mut layer, 3, key.as_bytes());
+        }
+        for (_, value) in tags {
+            let mut encoded = Vec::new();
+            write_pbf_bytes(&mut encoded, 1, value.as_bytes());
+            write_pbf_bytes(&mut layer, 4, &encoded);
+        }
+        write_pbf_uint(&mut layer, 5, extent);
+        layer
+    }
+
+    pub(crate) fn mvt_tile(layers: &[&[u8]]) -> Tile {
+        let mut data = Vec::new();
+        for layer in layers {
+            write_pbf_bytes(&mut data, 3, layer);
+        }
+        Tile::new(data, TileInfo::new(Format::Mvt, Encoding::Uncompressed))
+    }
+
+    /// A decoded MVT layer, with the `(key, value)` tags of all of its features
+    pub(crate) struct MvtLayer {
+        pub(crate) raw: Vec<u8>,
+        pub(crate) name: String,
+        pub(crate) key_count: usize,
+        pub(crate) tags: Vec<(String, String)>,
+    }
+
+    pub(crate) fn mvt_layers(tile: &Tile) -> Vec<MvtLayer> {
+        let text = |bytes: &[u8]| String::from_utf8(bytes.to_vec()).unwrap();
+        let mut layers = Vec::new();
+        let mut reader = PbfReader::new(&tile.data);
+        while let Some(field) = reader.next_field().unwrap() {
+            let PbfValue::Bytes(layer) = field.value else {
+                panic!("unexpected tile field {}", field.number);
+            };
+            let (mut name, mut keys, mut values, mut tags) = (None, vec![], vec![], vec![]);
+            let mut layer_reader = PbfReader::new(layer);
+            while let Some(field) = layer_reader.next_field().unwrap() {
+                match (field.number, field.value) {
+                    (1, PbfValue::Bytes(bytes)) => name = Some(text(bytes)),
+                    (2, PbfValue::Bytes(feature)) => {
+                        let mut feature = PbfReader::new(feature);
+                        while let Some(field) = feature.next_field().unwrap() {
+                            if let (2, PbfValue::Bytes(packed)) = (field.number, field.value) {
+                                let mut packed = PbfReader::new(packed);
+                                while !packed.is_empty() {
+                                    tags.push(packed.varint().unwrap());
+                                }
+                            }
+                        }
+                    }
+                    (3, PbfValue::Bytes(key)) => keys.push(text(key)),
+                    (4, PbfValue::Bytes(value)) => {
+                        let field = PbfReader::new(value).next_field().unwrap().unwrap();
+                        values.push(match field.value {
+                            PbfValue::Bytes(value) => text(value),
+                            PbfValue::Varint(value) => value.to_string(),
+                            PbfValue::Fixed => panic!("unexpected value type"),
+                        });
+                    }
+                    _ => {}
+                }
+            }
+            let tags = tags
+                .chunks_exact(2)
+                .map(|pair| {
+                    (
+                        keys[usize::try_from(pair[0]).unwrap()].clone(),
+                        values[usize::try_from(pair[1]).unwrap()].clone(),
+                    )
+                })
+                .collect();
+            layers.push(MvtLayer {
+                raw: layer.to_vec(),
+                name: name.unwrap(),
+                key_count: keys.len(),
+                tags,
+            });
+        }
+        layers
+    }
+
+    #[test]
+    fn merge_mvt_layers_by_name() {
+        let water_a = mvt_layer("water", 4096, &[("class", "lake")]);
+        let roads = mvt_layer("roads", 4096, &[("class", "primary")]);
+        let water_b = mvt_layer("water", 4096, &[("class", "river"), ("name", "Nile")]);
+        let buildings = mvt_layer("buildings", 4096, &[("height", "10")]);
+        let first = mvt_tile(&[&water_a, &roads]);
+        let second = mvt_tile(&[&water_b, &buildings]);
+
+        let merged = merge_mvt_layers(&[first.clone(), second.clone()]).unwrap();
+        assert_eq!(
+            merged.info,
+            TileInfo::new(Format::Mvt, Encoding::Uncompressed)
+        );
+        let layers = mvt_layers(&merged);
+        let names: Vec<_> = layers.iter().map(|layer| layer.name.as_str()).collect();
+        assert_eq!(names, vec!["water", "roads", "buildings"]);
+
+        // features of the same-named layers are combined, and their keys deduplicated
+        assert_eq!(layers[0].key_count, 2);
+        let pair = |k: &str, v: &str| (k.to_string(), v.to_string());
+        assert_eq!(
+            layers[0].tags,
+            vec![
+                pair("class", "lake"),
+                pair("class", "river"),
+                pair("name", "Nile")
+            ]
+        );
+
+        // distinct layers are copied as is
+        assert_eq!(layers[1].raw, roads);
+        assert_eq!(layers[2].raw, buildings);
+
+        // tiles may have different encodings, and empty tiles are skipped
+        let empty = Tile::new(vec![], TileInfo::new(Format::Mvt, Encoding::Uncompressed));
+        let gzipped = second.encode(Encoding::Gzip).unwrap();
+        let again = merge_mvt_layers(&[first.clone(), empty.clone(), gzipped]).unwrap();
+        assert_eq!(again.data, merged.data);
+        assert!(merge_mvt_layers(&[empty]).unwrap().data.is_empty());
+
+        let other_extent = mvt_tile(&[&mvt_layer("water", 512, &[("class", "sea")])]);
+        assert!(matches!(
+            merge_mvt_layers(&[first.clone(), other_extent]),
+            Err(MvtMergeError(_))
+        ));
+        let png = Tile::new(vec![], TileInfo::new(Format::Png, Encoding::Internal));
+        assert!(matches!(
+            merge_mvt_layers(&[first, png]),
+            Err(CannotConcatenate(Format::Png))
+        ));
+        let truncated = Tile::new(vec![0x1a, 0x05, 0x0a], Format::Mvt.into());
+        assert!(matches!(
+            merge_mvt_layers(&[truncated]),
+            Err(MvtMergeError(_))
+        ));
+    }
+
+    #[test]
+    fn pbf_varint() {
+        for value in [0, 1, 127, 128, 300, u64::from(u32::MAX), u64::MAX] {
+            let mut data = Vec::new();
+            write_pbf_varint(&mut data, value);
+            let mut reader = PbfReader::new(&data);
+            assert_eq!(reader.varint().unwrap(), value);
+            assert!(reader.is_empty());
+        }
+        let mut data = Vec::new();
+        write_pbf_varint(&mut data, 300);
+        assert_eq!(data, vec![0xac, 0x02]);
+
+        // the last byte still has the continuation bit
+        assert!(PbfReader::new(&[0xac]).varint().is_err());
+        assert!(PbfReader::new(&[]).varint().is_err());
+        // more than 10 bytes do not fit into 64 bits
+        assert!(PbfReader::new(&[0xff; 11]).varint().is_err());
+    }
+
+    #[test]
+    fn pbf_fields() {
+        let mut data = Vec::new();
+        write_pbf_uint(&mut data, 15, 2);
+        write_pbf_bytes(&mut data, 1, b"water");
+        let mut reader = PbfReader::new(&data);
+
+        let field = reader.next_field().unwrap().unwrap();
+        assert_eq!(field.number, 15);
+        assert!(matches!(field.value, PbfValue::Varint(2)));
+        assert_eq!(field.raw, &[0x78, 0x02]);
+        let field = reader.next_field().unwrap().unwrap();
+        assert_eq!(field.number, 1);
+        assert!(matches!(field.value, PbfValue::Bytes(b"water")));
+        assert!(reader.next_field().unwrap().is_none());
+
+        // the length is larger than the remaining data
+        let truncated = [0x0a, 0x05, b'w'];
+        assert!(PbfReader::new(&truncated).next_field().is_err());
+        // the key is cut off, or the value is missing
+        assert!(PbfReader::new(&[0x80]).next_field().is_err());
+        assert!(PbfReader::new(&[0x78]).next_field().is_err());
+        // fixed-size values must be complete
+        assert!(PbfReader::new(&[0x09, 0, 0, 0]).next_field().is_err());
+        assert!(PbfReader::new(&[0x0d, 0, 0, 0]).next_field().is_err());
+        let field = PbfReader::new(&[0x0d, 0, 0, 0, 0]).next_field().unwrap();
+        assert!(matches!(field.unwrap().value, PbfValue::Fixed));
+        // groups are not supported
+        assert!(PbfReader::new(&[0x0b]).next_field().is_err());
+    }
+}
@@ -300,7 +300,8 @@ impl PgBuilder {
         sql_info: PgSqlInfo,
     ) {
         let tilejson = pg_info.to_tilejson(id.clone());
-        let source = PgSource::new(id, sql_info, tilejson, self.pool.clone());
+        let source = PgSource::new(id, sql_info, tilejson, self.pool.clone())
+            .with_tags(pg_info.get_tags().to_vec());
         sources.push(Box::new(source));
     }
 }
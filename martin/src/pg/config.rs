@@ -21,6 +21,7 @@ use crate::MartinResult;
 pub trait PgInfo {
     fn format_id(&self) -> String;
     fn to_tilejson(&self, source_id: String) -> TileJSON;
+    fn get_tags(&self) -> &[String];
 }
 
 #[serde_with::skip_serializing_none]
@@ -241,6 +242,7 @@ mod tests {
                   geometry_type: GEOMETRY
                   properties:
                     gid: int4
+                  tags: [basemap]
 
               functions:
                 function_zxy_query:
@@ -249,6 +251,7 @@ mod tests {
                   minzoom: 0
                   maxzoom: 30
                   bounds: [-180.0, -90.0, 180.0, 90.0]
+                  tags: [overlay, experimental]
         "},
             &Config {
                 postgres: One(PgConfig {
@@ -274,18 +277,22 @@ mod tests {
                                 "gid".to_string(),
                                 "int4".to_string(),
                             )])),
+                            tags: Some(vec!["basemap".to_string()]),
                             ..Default::default()
                         },
                     )])),
                     functions: Some(BTreeMap::from([(
                         "function_zxy_query".to_string(),
-                        FunctionInfo::new_extended(
-                            "public".to_string(),
-                            "function_zxy_query".to_string(),
-                            0,
-                            30,
-                            Bounds::MAX,
-                        ),
+                        FunctionInfo {
+                            tags: Some(vec!["overlay".to_string(), "experimental".to_string()]),
+                            ..FunctionInfo::new_extended(
+                                "public".to_string(),
+                                "function_zxy_query".to_string(),
+                                0,
+                                30,
+                                Bounds::MAX,
+                            )
+                        },
                     )])),
                     ..Default::default()
                 }),
@@ -28,6 +28,9 @@ pub struct FunctionInfo {
     /// Values may be integers or floating point numbers.
     pub bounds: Option<Bounds>,
 
+    /// Labels of the source, e.g. `basemap`, to filter the catalog by
+    pub tags: Option<Vec<String>>,
+
     /// TileJSON provided by the SQL function comment. Not serialized.
     #[serde(skip)]
     pub tilejson: Option<serde_json::Value>,
@@ -82,6 +85,10 @@ impl PgInfo for FunctionInfo {
         tilejson.bounds = self.bounds;
         patch_json(tilejson, self.tilejson.as_ref())
     }
+
+    fn get_tags(&self) -> &[String] {
+        self.tags.as_deref().unwrap_or_default()
+    }
 }
 
 impl FunctionInfo {
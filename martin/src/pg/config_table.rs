@@ -66,6 +66,9 @@ pub struct TableInfo {
     /// List of columns, that should be encoded as tile properties
     pub properties: Option<BTreeMap<String, String>>,
 
+    /// Labels of the source, e.g. `basemap`, to filter the catalog by
+    pub tags: Option<Vec<String>>,
+
     /// Mapping of properties to the actual table columns
     #[serde(skip)]
     pub prop_mapping: HashMap<String, String>,
@@ -112,6 +115,10 @@ impl PgInfo for TableInfo {
         tilejson.vector_layers = Some(vec![layer]);
         patch_json(tilejson, self.tilejson.as_ref())
     }
+
+    fn get_tags(&self) -> &[String] {
+        self.tags.as_deref().unwrap_or_default()
+    }
 }
 
 impl TableInfo {
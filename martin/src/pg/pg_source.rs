@@ -1,3 +1,5 @@
+use std::time::SystemTime;
+
 use async_trait::async_trait;
 use deadpool_postgres::tokio_postgres::types::{ToSql, Type};
 use log::debug;
@@ -8,7 +10,7 @@ use tilejson::TileJSON;
 
 use crate::pg::pool::PgPool;
 use crate::pg::utils::query_to_json;
-use crate::pg::PgError::{GetTileError, GetTileWithQueryError, PrepareQueryError};
+use crate::pg::PgError::{GetTileError, GetTileWithQueryError, PostgresError, PrepareQueryError};
 use crate::source::{Source, TileData, TileInfoSource, UrlQuery};
 use crate::MartinResult;
 
@@ -18,6 +20,7 @@ pub struct PgSource {
     info: PgSqlInfo,
     pool: PgPool,
     tilejson: TileJSON,
+    tags: Vec<String>,
 }
 
 impl PgSource {
@@ -28,8 +31,16 @@ impl PgSource {
             info,
             pool,
             tilejson,
+            tags: Vec::new(),
         }
     }
+
+    /// Set the labels of the source, see [`Source::tags`]
+    #[must_use]
+    pub fn with_tags(mut self, tags: Vec<String>) -> Self {
+        self.tags = tags;
+        self
+    }
 }
 
 #[async_trait]
@@ -54,6 +65,25 @@ impl Source for PgSource {
         self.info.use_url_query
     }
 
+    fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    /// Unknown, because `PostgreSQL` does not track when the rows of a table were last changed,
+    /// and functions may return different tiles on every call. Responding with a timestamp
+    /// like the startup time would let clients keep outdated tiles.
+    fn last_modified(&self) -> Option<SystemTime> {
+        None
+    }
+
+    async fn health_check(&self) -> MartinResult<()> {
+        let conn = self.pool.get().await?;
+        conn.simple_query("SELECT 1")
+            .await
+            .map_err(|e| PostgresError(e, "checking source health"))?;
+        Ok(())
+    }
+
     async fn get_tile(
         &self,
         xyz: TileCoord,
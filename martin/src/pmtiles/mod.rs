@@ -5,11 +5,12 @@ use std::path::{Path, PathBuf};
 use std::sync::atomic::AtomicUsize;
 use std::sync::atomic::Ordering::Relaxed;
 use std::sync::Arc;
+use std::time::SystemTime;
 
 use async_trait::async_trait;
 use log::{trace, warn};
 use martin_tile_utils::{Encoding, Format, TileCoord, TileInfo};
-use pmtiles::async_reader::AsyncPmTilesReader;
+use pmtiles::async_reader::{AsyncBackend as _, AsyncPmTilesReader};
 use pmtiles::cache::{DirCacheResult, DirectoryCache};
 use pmtiles::reqwest::Client;
 use pmtiles::{Compression, Directory, HttpBackend, MmapBackend, TileType};
@@ -18,7 +19,9 @@ use tilejson::TileJSON;
 use url::Url;
 
 use crate::config::UnrecognizedValues;
-use crate::file_config::FileError::{InvalidMetadata, InvalidUrlMetadata, IoError};
+use crate::file_config::FileError::{
+    InvalidFilePath, InvalidMetadata, InvalidUrlMetadata, IoError,
+};
 use crate::file_config::{ConfigExtras, FileError, FileResult, SourceConfigExtras};
 use crate::source::{TileInfoSource, UrlQuery};
 use crate::utils::cache::get_cached_value;
@@ -134,22 +137,34 @@ impl SourceConfigExtras for PmtConfig {
         true
     }
 
-    async fn new_sources(&self, id: String, path: PathBuf) -> FileResult<TileInfoSource> {
-        Ok(Box::new(
-            PmtFileSource::new(self.new_cached_source(), id, path).await?,
-        ))
+    async fn new_sources(
+        &self,
+        id: String,
+        path: PathBuf,
+        tags: Vec<String>,
+    ) -> FileResult<TileInfoSource> {
+        Ok(Box::new(PmtFileSource {
+            tags,
+            ..PmtFileSource::new(self.new_cached_source(), id, path).await?
+        }))
     }
 
-    async fn new_sources_url(&self, id: String, url: Url) -> FileResult<TileInfoSource> {
-        Ok(Box::new(
-            PmtHttpSource::new(
+    async fn new_sources_url(
+        &self,
+        id: String,
+        url: Url,
+        tags: Vec<String>,
+    ) -> FileResult<TileInfoSource> {
+        Ok(Box::new(PmtHttpSource {
+            tags,
+            ..PmtHttpSource::new(
                 self.client.clone().unwrap(),
                 self.new_cached_source(),
                 id,
                 url,
             )
-            .await?,
-        ))
+            .await?
+        }))
     }
 }
 
@@ -162,6 +177,11 @@ macro_rules! impl_pmtiles_source {
             pmtiles: Arc<AsyncPmTilesReader<$backend, PmtCache>>,
             tilejson: TileJSON,
             tile_info: TileInfo,
+            /// Size of the whole `PMTiles` archive, if known
+            size: Option<u64>,
+            /// Number of addressed tiles from the header, if known
+            tile_count: Option<u64>,
+            tags: Vec<String>,
         }
 
         impl Debug for $name {
@@ -181,6 +201,8 @@ macro_rules! impl_pmtiles_source {
                 id: String,
                 path: $path,
                 reader: AsyncPmTilesReader<$backend, PmtCache>,
+                size: Option<u64>,
+                tile_count: Option<u64>,
             ) -> FileResult<Self> {
                 let hdr = &reader.get_header();
 
@@ -232,6 +254,9 @@ macro_rules! impl_pmtiles_source {
                     pmtiles: Arc::new(reader),
                     tilejson,
                     tile_info: format,
+                    size,
+                    tile_count,
+                    tags: Vec::new(),
                 })
             }
         }
@@ -254,6 +279,26 @@ macro_rules! impl_pmtiles_source {
                 Box::new(self.clone())
             }
 
+            fn approximate_tile_count(&self) -> Option<u64> {
+                self.tile_count
+            }
+
+            fn approximate_total_bytes(&self) -> Option<u64> {
+                self.size
+            }
+
+            fn tags(&self) -> &[String] {
+                &self.tags
+            }
+
+            async fn health_check(&self) -> MartinResult<()> {
+                self.check_backend().await
+            }
+
+            fn last_modified(&self) -> Option<SystemTime> {
+                self.modified_time()
+            }
+
             async fn get_tile(
                 &self,
                 xyz: TileCoord,
@@ -281,6 +326,14 @@ macro_rules! impl_pmtiles_source {
     };
 }
 
+/// Read the number of addressed tiles from the archive header, which the reader does not expose.
+/// It is stored as a little-endian `u64` at byte 72 of the header, and `0` if unknown.
+async fn read_tile_count(backend: &MmapBackend) -> Option<u64> {
+    let bytes = backend.read_exact(72, 8).await.ok()?;
+    let count = u64::from_le_bytes(bytes.as_ref().try_into().ok()?);
+    (count > 0).then_some(count)
+}
+
 impl_pmtiles_source!(
     PmtHttpSource,
     HttpBackend,
@@ -294,7 +347,20 @@ impl PmtHttpSource {
         let reader = AsyncPmTilesReader::new_with_cached_url(cache, client, url.clone()).await;
         let reader = reader.map_err(|e| FileError::PmtError(e, url.to_string()))?;
 
-        Self::new_int(id, url, reader).await
+        // Neither the size nor the tile count is fetched, to avoid extra requests on startup
+        Self::new_int(id, url, reader, None, None).await
+    }
+
+    /// Make sure the remote archive can still be read
+    async fn check_backend(&self) -> MartinResult<()> {
+        self.pmtiles.get_metadata().await?;
+        Ok(())
+    }
+
+    /// The modification time of remote archives is not known
+    #[allow(clippy::unused_self)]
+    fn modified_time(&self) -> Option<SystemTime> {
+        None
     }
 }
 
@@ -313,11 +379,59 @@ impl PmtFileSource {
             .map_err(|e| io::Error::other(format!("{e:?}: Cannot open file {}", path.display())))
             .map_err(|e| IoError(e, path.clone()))?;
 
+        let tile_count = read_tile_count(&backend).await;
         let reader = AsyncPmTilesReader::try_from_cached_source(backend, cache).await;
         let reader = reader
             .map_err(|e| io::Error::other(format!("{e:?}: Cannot open file {}", path.display())))
             .map_err(|e| IoError(e, path.clone()))?;
 
-        Self::new_int(id, path, reader).await
+        let size = path.metadata().ok().map(|m| m.len());
+        Self::new_int(id, path, reader, size, tile_count).await
+    }
+
+    /// Make sure the file has not been removed since it was opened, and can still be read
+    async fn check_backend(&self) -> MartinResult<()> {
+        if !self.path.is_file() {
+            Err(InvalidFilePath(self.path.clone()))?;
+        }
+        self.pmtiles.get_metadata().await?;
+        Ok(())
+    }
+
+    fn modified_time(&self) -> Option<SystemTime> {
+        self.path.metadata().ok()?.modified().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use martin_tile_utils::TileCoord;
+
+    use crate::pmtiles::{PmtCache, PmtFileSource};
+    use crate::utils::NO_MAIN_CACHE;
+    use crate::{MartinError, Source as _};
+
+    #[actix_rt::test]
+    async fn tile_range() {
+        let path =
+            PathBuf::from("../tests/fixtures/pmtiles/stamen_toner__raster_CC-BY+ODbL_z3.pmtiles");
+        let cache = PmtCache::new(0, NO_MAIN_CACHE);
+        let src = PmtFileSource::new(cache, "p".to_string(), path)
+            .await
+            .unwrap();
+        let xyz = TileCoord { z: 0, x: 0, y: 0 };
+        let data = src.get_tile(xyz, None).await.unwrap();
+        let len = data.len();
+
+        let tile = src.get_tile_range(xyz, None, 8..16).await.unwrap();
+        assert_eq!(tile.data, data[8..16]);
+        assert_eq!(tile.info, src.get_tile_info());
+        let tile = src.get_tile_range(xyz, None, 16..len + 100).await.unwrap();
+        assert_eq!(tile.data, data[16..]);
+
+        let err = src.get_tile_range(xyz, None, len + 1..len + 2).await;
+        assert!(matches!(err, Err(MartinError::SourceError(_))));
     }
 }
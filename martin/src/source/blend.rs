@@ -0,0 +1,253 @@
+use std::fmt::{Debug, Formatter};
+use std::time::SystemTime;
+
+use futures::future::try_join;
+use martin_tile_utils::{Format, TileCoord, TileInfo};
+
+use crate::source::delegate::delegate_source;
+use crate::source::SourceError::{BlendError, BlendSizeMismatch, CannotBlend};
+use crate::source::{
+    decode_rgba, encode_rgba, Source, SourceError, TileData, TileInfoSource, UrlQuery,
+};
+use crate::MartinResult;
+
+/// Alpha-composites the tiles of two raster sources, e.g. a hillshade over JPEG imagery.
+/// Tiles are requested from both sources, and the `top` tile is drawn over the `bottom` one.
+/// The result is always a PNG tile, so if only one of them has a tile, it is only re-encoded
+/// unless it already is a PNG. Metadata like the `TileJSON` is the bottom's.
+#[derive(Clone)]
+pub struct BlendSource {
+    top: TileInfoSource,
+    bottom: TileInfoSource,
+}
+
+impl BlendSource {
+    /// Fails with [`SourceError::CannotBlend`] unless both sources have PNG or JPEG tiles,
+    /// and with [`SourceError::BlendSizeMismatch`] if their tile sizes differ
+    pub fn new(top: TileInfoSource, bottom: TileInfoSource) -> Result<Self, SourceError> {
+        for src in [&top, &bottom] {
+            let format = src.get_tile_info().format;
+            if !matches!(format, Format::Png | Format::Jpeg) {
+                return Err(CannotBlend(src.get_id().to_string(), format));
+            }
+        }
+        if top.tile_size() != bottom.tile_size() {
+            return Err(BlendSizeMismatch {
+                top_id: top.get_id().to_string(),
+                top: top.tile_size(),
+                bottom_id: bottom.get_id().to_string(),
+                bottom: bottom.tile_size(),
+            });
+        }
+        Ok(Self { top, bottom })
+    }
+
+    async fn get_covered_tile(
+        src: &dyn Source,
+        xyz: TileCoord,
+        url_query: Option<&UrlQuery>,
+    ) -> MartinResult<TileData> {
+        if src.covers(xyz) {
+            let url_query = url_query.filter(|_| src.support_url_query());
+            src.get_tile(xyz, url_query).await
+        } else {
+            Ok(TileData::default())
+        }
+    }
+}
+
+/// Draw the `top` RGBA pixels over the `bottom` ones using the "over" operator
+fn blend_over(top: &[u8], bottom: &mut [u8]) {
+    for (top, bottom) in top.chunks_exact(4).zip(bottom.chunks_exact_mut(4)) {
+        let top_alpha = u32::from(top[3]);
+        let bottom_alpha = u32::from(bottom[3]) * (255 - top_alpha);
+        // alpha of the result, scaled by 255
+        let alpha = top_alpha * 255 + bottom_alpha;
+        if alpha == 0 {
+            continue;
+        }
+        let (colors, bottom_alpha_px) = bottom.split_at_mut(3);
+        for (color, &top_color) in colors.iter_mut().zip(&top[..3]) {
+            let value = u32::from(top_color) * top_alpha * 255 + u32::from(*color) * bottom_alpha;
+            *color = u8::try_from((value + alpha / 2) / alpha).unwrap_or(u8::MAX);
+        }
+        bottom_alpha_px[0] = u8::try_from((alpha + 127) / 255).unwrap_or(u8::MAX);
+    }
+}
+
+impl Debug for BlendSource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "BlendSource {{ top: {:?}, bottom: {:?} }}",
+            self.top, self.bottom
+        )
+    }
+}
+
+delegate_source! {
+    impl Source for BlendSource => self.bottom;
+    except [
+        get_tile_info, support_url_query, url_query_key, requires_auth, get_tile,
+        get_tile_with_info, health_check, last_modified, is_valid_zoom, covers, has_tile,
+        get_tile_etag, tile_stream, approximate_tile_count, approximate_total_bytes,
+        available_encodings,
+        get_tile_opt, get_tile_decoded, supports_range, get_tile_range, get_tiles_batch, prewarm,
+        supported_formats, can_encode, get_tile_as, transcode_tile,
+    ];
+
+    fn get_tile_info(&self) -> TileInfo {
+        TileInfo::from(Format::Png)
+    }
+
+    fn support_url_query(&self) -> bool {
+        self.top.support_url_query() || self.bottom.support_url_query()
+    }
+
+    fn requires_auth(&self) -> bool {
+        self.top.requires_auth() || self.bottom.requires_auth()
+    }
+
+    async fn get_tile(
+        &self,
+        xyz: TileCoord,
+        url_query: Option<&UrlQuery>,
+    ) -> MartinResult<TileData> {
+        let (top, bottom) = try_join(
+            Self::get_covered_tile(self.top.as_ref(), xyz, url_query),
+            Self::get_covered_tile(self.bottom.as_ref(), xyz, url_query),
+        )
+        .await?;
+        let is_png = |data: &[u8]| TileInfo::detect(data).map(|info| info.format) == Some(Format::Png);
+        let blend_error = |e: String| BlendError(self.get_id().to_string(), xyz, e);
+        let (width, height, bottom) = match (top.is_empty(), bottom.is_empty()) {
+            (true, true) => return Ok(TileData::default()),
+            (true, false) if is_png(&bottom) => return Ok(bottom),
+            (false, true) if is_png(&top) => return Ok(top),
+            (true, false) => decode_rgba(&bottom).map_err(blend_error)?,
+            (false, true) => decode_rgba(&top).map_err(blend_error)?,
+            (false, false) => {
+                let (width, height, top) = decode_rgba(&top).map_err(blend_error)?;
+                let (bottom_width, bottom_height, mut bottom) =
+                    decode_rgba(&bottom).map_err(blend_error)?;
+                if (width, height) != (bottom_width, bottom_height) {
+                    Err(blend_error(format!(
+                        "tile sizes differ, {width}x{height} and {bottom_width}x{bottom_height}"
+                    )))?;
+                }
+                blend_over(&top, &mut bottom);
+                (width, height, bottom)
+            }
+        };
+        Ok(
+            encode_rgba(width, height, &bottom, png::Compression::Default)
+                .map_err(|e| blend_error(e.to_string()))?,
+        )
+    }
+
+    async fn health_check(&self) -> MartinResult<()> {
+        self.top.health_check().await?;
+        self.bottom.health_check().await
+    }
+
+    /// The later of both modification times, or `None` unless both are known
+    fn last_modified(&self) -> Option<SystemTime> {
+        Some(self.top.last_modified()?.max(self.bottom.last_modified()?))
+    }
+
+    fn is_valid_zoom(&self, zoom: u8) -> bool {
+        self.top.is_valid_zoom(zoom) || self.bottom.is_valid_zoom(zoom)
+    }
+
+    fn covers(&self, xyz: TileCoord) -> bool {
+        self.top.covers(xyz) || self.bottom.covers(xyz)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tilejson::tilejson;
+
+    use super::*;
+    use crate::source::tests::{solid_png, test_source, RetinaSource};
+    use crate::TestSource;
+
+    #[actix_rt::test]
+    async fn blend_source() {
+        let xyz = TileCoord { z: 0, x: 0, y: 0 };
+        let red = Box::new(solid_png("red", [255, 0, 0, 128]));
+        let blue = Box::new(solid_png("blue", [0, 0, 255, 255]));
+        let src = BlendSource::new(red, blue).unwrap();
+        assert_eq!(src.get_id(), "blue");
+        assert_eq!(src.get_tile_info(), TileInfo::from(Format::Png));
+        let (width, height, rgba) = decode_rgba(&src.get_tile(xyz, None).await.unwrap()).unwrap();
+        assert_eq!((width, height), (2, 2));
+        assert_eq!(rgba, [128, 0, 127, 255].repeat(4));
+
+        // transparent pixels keep the bottom color
+        let clear = Box::new(solid_png("clear", [0, 0, 0, 0]));
+        let green = Box::new(solid_png("green", [0, 255, 0, 64]));
+        let src = BlendSource::new(clear, green).unwrap();
+        let (.., rgba) = decode_rgba(&src.get_tile(xyz, None).await.unwrap()).unwrap();
+        assert_eq!(rgba, [0, 255, 0, 64].repeat(4));
+
+        // a missing tile in one source returns the other one unchanged
+        let empty = Box::new(TestSource {
+            data: Vec::default(),
+            ..solid_png("empty", [0; 4])
+        });
+        let blue = solid_png("blue", [0, 0, 255, 255]);
+        let data = blue.data.clone();
+        let src = BlendSource::new(empty, Box::new(blue)).unwrap();
+        assert_eq!(src.get_tile(xyz, None).await.unwrap(), data);
+    }
+
+    #[actix_rt::test]
+    async fn blend_source_jpeg() {
+        let xyz = TileCoord { z: 0, x: 0, y: 0 };
+        let jpeg = include_bytes!("../../../martin-tile-utils/fixtures/world.jpg").to_vec();
+        let (width, height, world) = decode_rgba(&jpeg).unwrap();
+        let source = |id, data: Vec<u8>| -> TileInfoSource {
+            let info = TileInfo::detect(&data).unwrap_or(Format::Png.into());
+            Box::new(TestSource {
+                id,
+                tj: tilejson! { tiles: vec![] },
+                info,
+                data,
+            })
+        };
+
+        // the JPEG tile is drawn under a transparent PNG tile, and served as PNG
+        let clear = encode_rgba(width, height, &vec![0; world.len()], png::Compression::Fast);
+        let src = BlendSource::new(
+            source("clear", clear.unwrap()),
+            source("world", jpeg.clone()),
+        );
+        let tile = src.unwrap().get_tile(xyz, None).await.unwrap();
+        assert_eq!(TileInfo::detect(&tile).unwrap().format, Format::Png);
+        assert_eq!(decode_rgba(&tile).unwrap(), (width, height, world.clone()));
+
+        // a single JPEG tile is re-encoded as PNG
+        let src = BlendSource::new(source("world", jpeg), source("empty", Vec::default()));
+        let tile = src.unwrap().get_tile(xyz, None).await.unwrap();
+        assert_eq!(TileInfo::detect(&tile).unwrap().format, Format::Png);
+        assert_eq!(decode_rgba(&tile).unwrap(), (width, height, world));
+    }
+
+    #[test]
+    fn blend_source_validation() {
+        let png = || Box::new(solid_png("png", [0; 4]));
+        let err = BlendSource::new(png(), test_source("mvt")).err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            "Source mvt (mvt) cannot be blended, only PNG and JPEG tiles are supported"
+        );
+
+        let retina = Box::new(RetinaSource(solid_png("retina", [0; 4])));
+        let err = BlendSource::new(retina, png()).err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            "Cannot blend source retina (512px) over source png (256px), because their tile sizes differ"
+        );
+    }
+}
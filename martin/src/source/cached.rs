@@ -0,0 +1,412 @@
+use std::fmt::{Debug, Formatter};
+
+use log::debug;
+use martin_tile_utils::{Format, TileCoord};
+
+use crate::source::delegate::delegate_source;
+use crate::source::{Source, Tile, TileData, TileInfoSource, UrlQuery};
+use crate::MartinResult;
+
+/// Wraps a source to keep its most recently used tiles in memory, e.g. for frequently requested
+/// low zoom tiles. Tiles are cached per coordinate, and per normalized URL query if the source
+/// [supports it](Source::support_url_query), see [`Source::url_query_key`].
+/// Clones of this source, including [`Source::clone_source`], share the same cache.
+/// After each request, the [prefetch hints](Source::prefetch_hints) that are not cached yet
+/// are fetched in the background.
+#[derive(Clone)]
+pub struct CachedSource {
+    source: TileInfoSource,
+    cache: moka::future::Cache<(TileCoord, String), Tile>,
+}
+
+impl CachedSource {
+    /// Create a cache for up to `capacity` tiles, evicting the least recently used ones first.
+    #[must_use]
+    pub fn new(source: TileInfoSource, capacity: u64) -> Self {
+        let cache = moka::future::Cache::builder()
+            .name(source.get_id())
+            .eviction_policy(moka::policy::EvictionPolicy::lru())
+            .max_capacity(capacity)
+            .build();
+        Self { source, cache }
+    }
+
+    /// Key of the tile in the cache. The URL query is ignored if the source does not support it.
+    fn cache_key(&self, xyz: TileCoord, url_query: Option<&UrlQuery>) -> (TileCoord, String) {
+        let query_key = url_query
+            .filter(|_| self.support_url_query())
+            .map(|q| self.url_query_key(q))
+            .unwrap_or_default();
+        (xyz, query_key)
+    }
+
+    /// Fetch and cache the uncached prefetch hints of the tile in a background task.
+    /// Nothing is prefetched outside of a Tokio runtime, where no task can be spawned.
+    fn prefetch(&self, xyz: TileCoord, url_query: Option<&UrlQuery>) {
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        let hints: Vec<_> = self
+            .source
+            .prefetch_hints(xyz)
+            .into_iter()
+            .filter(|hint| {
+                hint.is_valid()
+                    && self.source.is_valid_zoom(hint.z)
+                    && self.source.covers(*hint)
+                    && !self.cache.contains_key(&self.cache_key(*hint, url_query))
+            })
+            .collect();
+        if hints.is_empty() {
+            return;
+        }
+        let this = self.clone();
+        let url_query = url_query.cloned();
+        runtime.spawn(async move {
+            for hint in hints {
+                let tile = match this
+                    .source
+                    .get_tile_with_info(hint, url_query.as_ref())
+                    .await
+                {
+                    Ok(tile) => tile,
+                    Err(e) => {
+                        debug!(
+                            "Unable to prefetch tile {hint} of source {}: {e}",
+                            this.get_id()
+                        );
+                        continue;
+                    }
+                };
+                let key = this.cache_key(hint, url_query.as_ref());
+                this.cache.insert(key, tile).await;
+            }
+        });
+    }
+}
+
+impl Debug for CachedSource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "CachedSource {{ source: {:?}, entries: {} }}",
+            self.source,
+            self.cache.entry_count()
+        )
+    }
+}
+
+delegate_source! {
+    impl Source for CachedSource => self.source;
+    except [
+        get_tile, get_tile_with_info, get_tile_as,
+        get_tile_opt, get_tile_decoded, supports_range, get_tile_range, get_tiles_batch, prewarm,
+        transcode_tile,
+    ];
+
+    async fn get_tile(
+        &self,
+        xyz: TileCoord,
+        url_query: Option<&UrlQuery>,
+    ) -> MartinResult<TileData> {
+        Ok(self.get_tile_with_info(xyz, url_query).await?.data)
+    }
+
+    async fn get_tile_with_info(
+        &self,
+        xyz: TileCoord,
+        url_query: Option<&UrlQuery>,
+    ) -> MartinResult<Tile> {
+        let key = self.cache_key(xyz, url_query);
+        let tile = if let Some(tile) = self.cache.get(&key).await {
+            tile
+        } else {
+            let tile = self.source.get_tile_with_info(xyz, url_query).await?;
+            self.cache.insert(key, tile.clone()).await;
+            tile
+        };
+        self.prefetch(xyz, url_query);
+        Ok(tile)
+    }
+
+    /// Only tiles in the native format are cached, others are requested from the source
+    async fn get_tile_as(
+        &self,
+        xyz: TileCoord,
+        url_query: Option<&UrlQuery>,
+        format: Format,
+    ) -> MartinResult<Tile> {
+        if format == self.get_tile_info().format {
+            self.get_tile_with_info(xyz, url_query).await
+        } else {
+            self.source.get_tile_as(xyz, url_query, format).await
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use async_trait::async_trait;
+    use martin_tile_utils::{Encoding, TileInfo};
+    use tilejson::{tilejson, TileJSON};
+
+    use super::*;
+    use crate::source::tests::CountingSource;
+    use crate::{MeteredSource, MissBehavior, TestSource, TileMetric};
+
+    /// Hints that the four children of each requested tile will be needed next
+    #[derive(Debug, Clone)]
+    struct ChildrenSource(CountingSource);
+
+    #[async_trait]
+    impl Source for ChildrenSource {
+        fn get_id(&self) -> &str {
+            self.0.get_id()
+        }
+
+        fn get_tilejson(&self) -> &TileJSON {
+            self.0.get_tilejson()
+        }
+
+        fn get_tile_info(&self) -> TileInfo {
+            self.0.get_tile_info()
+        }
+
+        fn clone_source(&self) -> TileInfoSource {
+            Box::new(self.clone())
+        }
+
+        async fn get_tile(
+            &self,
+            xyz: TileCoord,
+            url_query: Option<&UrlQuery>,
+        ) -> MartinResult<TileData> {
+            self.0.get_tile(xyz, url_query).await
+        }
+
+        fn prefetch_hints(&self, xyz: TileCoord) -> Vec<TileCoord> {
+            xyz.children().to_vec()
+        }
+    }
+
+    #[actix_rt::test]
+    async fn prefetch_hints() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let backend = ChildrenSource(CountingSource(
+            TestSource {
+                id: "children",
+                tj: tilejson! { tiles: vec![], minzoom: 0, maxzoom: 2 },
+                info: TileInfo::new(Format::Mvt, Encoding::Uncompressed),
+                data: vec![1_u8],
+            },
+            calls.clone(),
+        ));
+        assert_eq!(
+            backend.prefetch_hints(TileCoord { z: 1, x: 1, y: 0 }),
+            vec![
+                TileCoord { z: 2, x: 2, y: 0 },
+                TileCoord { z: 2, x: 3, y: 0 },
+                TileCoord { z: 2, x: 2, y: 1 },
+                TileCoord { z: 2, x: 3, y: 1 },
+            ]
+        );
+        assert!(backend
+            .0
+             .0
+            .prefetch_hints(TileCoord { z: 1, x: 1, y: 0 })
+            .is_empty());
+
+        // requesting a tile fetches its children in the background
+        let src = CachedSource::new(Box::new(backend), 100);
+        src.get_tile(TileCoord { z: 1, x: 1, y: 0 }, None)
+            .await
+            .unwrap();
+        let children = TileCoord { z: 1, x: 1, y: 0 }.children();
+        let prefetched = async {
+            while !children
+                .iter()
+                .all(|child| src.cache.contains_key(&src.cache_key(*child, None)))
+            {
+                tokio::time::sleep(Duration::from_millis(1)).await;
+            }
+        };
+        tokio::time::timeout(Duration::from_secs(5), prefetched)
+            .await
+            .expect("children were not prefetched");
+        src.get_tile(TileCoord { z: 2, x: 3, y: 1 }, None)
+            .await
+            .unwrap();
+        assert_eq!(calls.load(Ordering::Relaxed), 5);
+
+        // children above the maxzoom are not prefetched
+        src.get_tile(TileCoord { z: 2, x: 3, y: 1 }, None)
+            .await
+            .unwrap();
+        tokio::task::yield_now().await;
+        assert_eq!(calls.load(Ordering::Relaxed), 5);
+
+        // outside of a Tokio runtime, tiles are served without prefetching
+        let other = CachedSource::new(src.source.clone_source(), 100);
+        std::thread::spawn(move || {
+            futures::executor::block_on(other.get_tile(TileCoord { z: 1, x: 0, y: 0 }, None))
+                .unwrap();
+        })
+        .join()
+        .unwrap();
+        assert_eq!(calls.load(Ordering::Relaxed), 6);
+    }
+
+    #[actix_rt::test]
+    async fn cached_source() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let backend = CountingSource(
+            TestSource {
+                id: "counted",
+                tj: tilejson! { tiles: vec![] },
+                info: TileInfo::new(Format::Mvt, Encoding::Uncompressed),
+                data: vec![1_u8, 2],
+            },
+            calls.clone(),
+        );
+        let src = CachedSource::new(Box::new(backend), 10);
+        assert_eq!(src.get_id(), "counted");
+        assert!(src.support_url_query());
+
+        let xyz = TileCoord { z: 1, x: 0, y: 0 };
+        assert_eq!(src.get_tile(xyz, None).await.unwrap(), vec![1_u8, 2]);
+        assert_eq!(src.get_tile(xyz, None).await.unwrap(), vec![1_u8, 2]);
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+
+        // clones share the cache
+        let cloned = src.clone_source();
+        cloned.get_tile_with_info(xyz, None).await.unwrap();
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+
+        // the key includes the coordinate and the normalized query
+        src.get_tile(TileCoord { z: 1, x: 1, y: 0 }, None)
+            .await
+            .unwrap();
+        assert_eq!(calls.load(Ordering::Relaxed), 2);
+        let query = |pairs: &[(&str, &str)]| {
+            pairs
+                .iter()
+                .map(|(k, v)| ((*k).to_string(), (*v).to_string()))
+                .collect::<UrlQuery>()
+        };
+        let q1 = query(&[("a", "1"), ("b", "2")]);
+        let q2 = query(&[("b", "2"), ("a", "1")]);
+        src.get_tile(xyz, Some(&q1)).await.unwrap();
+        src.get_tile(xyz, Some(&q2)).await.unwrap();
+        assert_eq!(calls.load(Ordering::Relaxed), 3);
+    }
+
+    #[actix_rt::test]
+    async fn cached_source_ignored_query() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let metric: TileMetric = Arc::new(move |_, _, _| {
+            counter.fetch_add(1, Ordering::Relaxed);
+        });
+        let backend = MeteredSource::new(
+            Box::new(TestSource {
+                id: "unqueried",
+                tj: tilejson! { tiles: vec![] },
+                info: TileInfo::new(Format::Mvt, Encoding::Uncompressed),
+                data: vec![1_u8],
+            }),
+            metric,
+        );
+        let src = CachedSource::new(Box::new(backend), 10);
+        assert!(!src.support_url_query());
+
+        let xyz = TileCoord { z: 0, x: 0, y: 0 };
+        let query = UrlQuery::from([("a".to_string(), "1".to_string())]);
+        src.get_tile(xyz, None).await.unwrap();
+        src.get_tile(xyz, Some(&query)).await.unwrap();
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+    }
+
+    /// Has only the tile at zoom 0, and reports its own caching metadata
+    #[derive(Debug, Clone)]
+    struct MetadataSource(TestSource);
+
+    #[async_trait]
+    impl Source for MetadataSource {
+        fn get_id(&self) -> &str {
+            self.0.get_id()
+        }
+
+        fn get_tilejson(&self) -> &TileJSON {
+            self.0.get_tilejson()
+        }
+
+        fn get_tile_info(&self) -> TileInfo {
+            self.0.get_tile_info()
+        }
+
+        fn clone_source(&self) -> TileInfoSource {
+            Box::new(self.clone())
+        }
+
+        fn miss_behavior(&self) -> MissBehavior {
+            MissBehavior::Empty
+        }
+
+        fn cache_control_max_age(&self) -> Option<u32> {
+            Some(3600)
+        }
+
+        async fn get_tile(
+            &self,
+            xyz: TileCoord,
+            url_query: Option<&UrlQuery>,
+        ) -> MartinResult<TileData> {
+            self.0.get_tile(xyz, url_query).await
+        }
+
+        async fn has_tile(
+            &self,
+            xyz: TileCoord,
+            _url_query: Option<&UrlQuery>,
+        ) -> MartinResult<bool> {
+            Ok(xyz.z == 0)
+        }
+
+        async fn get_tile_etag(
+            &self,
+            xyz: TileCoord,
+            _url_query: Option<&UrlQuery>,
+        ) -> MartinResult<Option<String>> {
+            Ok(Some(format!("etag-{xyz:#}")))
+        }
+    }
+
+    #[actix_rt::test]
+    async fn cached_source_forwards() {
+        let src = CachedSource::new(
+            Box::new(MetadataSource(TestSource {
+                id: "meta",
+                tj: tilejson! { tiles: vec![] },
+                info: TileInfo::new(Format::Mvt, Encoding::Uncompressed),
+                data: vec![1_u8],
+            })),
+            10,
+        );
+        let xyz = TileCoord { z: 1, x: 1, y: 0 };
+        assert_eq!(src.miss_behavior(), MissBehavior::Empty);
+        assert_eq!(src.cache_control_max_age(), Some(3600));
+        assert!(!src.has_tile(xyz, None).await.unwrap());
+        assert!(src
+            .has_tile(TileCoord { z: 0, x: 0, y: 0 }, None)
+            .await
+            .unwrap());
+        assert_eq!(
+            src.get_tile_etag(xyz, None).await.unwrap().as_deref(),
+            Some("etag-1/1/0")
+        );
+    }
+}
@@ -0,0 +1,187 @@
+use std::fmt::{Debug, Formatter};
+
+use futures::stream::BoxStream;
+use futures::StreamExt as _;
+use martin_tile_utils::{Encoding, Format, TileCoord};
+
+use crate::mvt::{write_pbf_bytes, write_pbf_uint, write_pbf_varint};
+use crate::source::delegate::delegate_source;
+use crate::source::SourceError::UnsupportedFormat;
+use crate::source::{SourceError, Tile, TileData, TileInfoSource, UrlQuery};
+use crate::MartinResult;
+
+/// Adds a `debug` layer to the vector tiles of a source, with the outline of each tile
+/// and a point in its center labeled with the tile coordinate, e.g. `3/4/2`.
+/// Useful to check which tiles a map client requests. All metadata is the source's.
+#[derive(Clone)]
+pub struct DebugGridSource {
+    source: TileInfoSource,
+}
+
+impl DebugGridSource {
+    /// Fails with [`SourceError::UnsupportedFormat`] unless the source has MVT tiles
+    pub fn new(source: TileInfoSource) -> Result<Self, SourceError> {
+        if source.get_tile_info().format == Format::Mvt {
+            Ok(Self { source })
+        } else {
+            Err(UnsupportedFormat(source.get_id().to_string(), Format::Mvt))
+        }
+    }
+}
+
+impl Debug for DebugGridSource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "DebugGridSource {{ source: {:?} }}", self.source)
+    }
+}
+
+delegate_source! {
+    impl Source for DebugGridSource => self.source;
+    except [
+        get_tile, get_tile_with_info, has_tile, tile_stream,
+        get_tile_opt, get_tile_decoded, supports_range, get_tile_range, get_tiles_batch, prewarm,
+        supported_formats, can_encode, get_tile_as, transcode_tile,
+    ];
+
+    async fn get_tile(
+        &self,
+        xyz: TileCoord,
+        url_query: Option<&UrlQuery>,
+    ) -> MartinResult<TileData> {
+        Ok(self.get_tile_with_info(xyz, url_query).await?.data)
+    }
+
+    async fn get_tile_with_info(
+        &self,
+        xyz: TileCoord,
+        url_query: Option<&UrlQuery>,
+    ) -> MartinResult<Tile> {
+        let tile = self.source.get_tile_with_info(xyz, url_query).await?;
+        Ok(with_debug_layer(xyz, tile)?)
+    }
+
+    fn tile_stream(&self) -> Option<BoxStream<'_, MartinResult<(TileCoord, Tile)>>> {
+        let stream = self.source.tile_stream()?;
+        Some(
+            stream
+                .map(|res| res.and_then(|(xyz, tile)| Ok((xyz, with_debug_layer(xyz, tile)?))))
+                .boxed(),
+        )
+    }
+}
+
+/// Append the `debug` layer to the tile, keeping its encoding
+fn with_debug_layer(xyz: TileCoord, tile: Tile) -> Result<Tile, SourceError> {
+    let encoding = tile.info.encoding;
+    let mut tile = tile.decode()?;
+    // empty tiles are not decoded, but the debug layer is always uncompressed
+    tile.info = tile.info.encoding(Encoding::Uncompressed);
+    write_pbf_bytes(&mut tile.data, 3, &debug_layer(xyz));
+    tile.encode(encoding)
+}
+
+/// The `debug` layer of a [`DebugGridSource`] tile. Its point feature has the `label`
+/// `z/x/y`, and the separate `z`, `x`, and `y` numbers.
+fn debug_layer(xyz: TileCoord) -> Vec<u8> {
+    // MoveTo(2048, 2048), with zigzag encoded parameters
+    let center = [9, 4096, 4096];
+    // MoveTo(0, 0), then LineTo +4096,0 / 0,+4096 / -4096,0 / 0,-4096
+    let outline = [9, 0, 0, 34, 8192, 0, 0, 8192, 8191, 0, 0, 8191];
+
+    let mut layer = Vec::new();
+    write_pbf_uint(&mut layer, 15, 2);
+    write_pbf_bytes(&mut layer, 1, b"debug");
+    write_pbf_bytes(
+        &mut layer,
+        2,
+        &mvt_feature(1, &[0, 0, 1, 1, 2, 2, 3, 3], &center),
+    );
+    write_pbf_bytes(&mut layer, 2, &mvt_feature(2, &[], &outline));
+    for key in ["label", "z", "x", "y"] {
+        write_pbf_bytes(&mut layer, 3, key.as_bytes());
+    }
+    let mut label = Vec::new();
+    write_pbf_bytes(&mut label, 1, format!("{xyz:#}").as_bytes());
+    write_pbf_bytes(&mut layer, 4, &label);
+    for number in [u64::from(xyz.z), u64::from(xyz.x), u64::from(xyz.y)] {
+        let mut value = Vec::new();
+        write_pbf_uint(&mut value, 5, number);
+        write_pbf_bytes(&mut layer, 4, &value);
+    }
+    write_pbf_uint(&mut layer, 5, 4096);
+    layer
+}
+
+fn mvt_feature(geom_type: u64, tags: &[u64], geometry: &[u64]) -> Vec<u8> {
+    let pack = |values: &[u64]| {
+        let mut packed = Vec::new();
+        for value in values {
+            write_pbf_varint(&mut packed, *value);
+        }
+        packed
+    };
+    let mut feature = Vec::new();
+    if !tags.is_empty() {
+        write_pbf_bytes(&mut feature, 2, &pack(tags));
+    }
+    write_pbf_uint(&mut feature, 3, geom_type);
+    write_pbf_bytes(&mut feature, 4, &pack(geometry));
+    feature
+}
+
+#[cfg(test)]
+mod tests {
+    use martin_tile_utils::TileInfo;
+    use tilejson::tilejson;
+
+    use super::*;
+    use crate::mvt::tests::{mvt_layer, mvt_layers, mvt_tile};
+    use crate::source::tests::{solid_png, test_source};
+    use crate::source::Source;
+    use crate::TestSource;
+
+    #[actix_rt::test]
+    async fn debug_grid_source() {
+        let xyz = TileCoord { z: 3, x: 4, y: 2 };
+        let water = mvt_layer("water", 4096, &[("class", "lake")]);
+        let gzipped = mvt_tile(&[&water]).encode(Encoding::Gzip).unwrap();
+        let src = DebugGridSource::new(Box::new(TestSource {
+            id: "water",
+            tj: tilejson! { tiles: vec![] },
+            info: gzipped.info,
+            data: gzipped.data,
+        }))
+        .unwrap();
+        assert_eq!(src.get_id(), "water");
+
+        let tile = src.get_tile_with_info(xyz, None).await.unwrap();
+        assert_eq!(tile.info, TileInfo::new(Format::Mvt, Encoding::Gzip));
+        let layers = mvt_layers(&tile.decode().unwrap());
+        let names: Vec<_> = layers.iter().map(|layer| layer.name.as_str()).collect();
+        assert_eq!(names, ["water", "debug"]);
+        assert_eq!(layers[0].raw, water);
+        let pair = |k: &str, v: &str| (k.to_string(), v.to_string());
+        assert_eq!(
+            layers[1].tags,
+            [
+                pair("label", "3/4/2"),
+                pair("z", "3"),
+                pair("x", "4"),
+                pair("y", "2")
+            ]
+        );
+
+        // empty tiles only have the debug layer
+        let src = DebugGridSource::new(test_source("empty")).unwrap();
+        let tile = src.get_tile_with_info(xyz, None).await.unwrap();
+        let layers = mvt_layers(&tile);
+        assert_eq!(layers.len(), 1);
+        assert_eq!(layers[0].name, "debug");
+
+        let png = Box::new(solid_png("png", [0; 4]));
+        assert!(matches!(
+            DebugGridSource::new(png),
+            Err(UnsupportedFormat(id, Format::Mvt)) if id == "png"
+        ));
+    }
+}
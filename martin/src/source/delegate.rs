@@ -0,0 +1,182 @@
+/// Implements [`Source`](crate::Source) for a wrapper source by forwarding every method
+/// to the wrapped source, except the listed ones, which are either implemented in the body,
+/// or left to the trait's default implementation. Listing a method without implementing it
+/// is how a wrapper opts out of forwarding, e.g. to let [`Source::get_tile_opt`](crate::Source::get_tile_opt)
+/// go through the wrapper's own `get_tile_with_info`.
+///
+/// ```ignore
+/// delegate_source! {
+///     impl Source for TimeoutSource => self.source;
+///     except [get_tile, get_tile_with_info];
+///
+///     async fn get_tile(&self, xyz: TileCoord, url_query: Option<&UrlQuery>) -> MartinResult<TileData> {
+///         ...
+///     }
+///     ...
+/// }
+/// ```
+///
+/// [`Source::clone_source`](crate::Source::clone_source) always clones the wrapper itself.
+/// Methods derived from others, like the response headers or the catalog entry, are not forwarded,
+/// so that they reflect the wrapper's own metadata.
+macro_rules! delegate_source {
+    (impl Source for $ty: ty => self.$inner: ident $([$idx: literal])?;
+     except [$($own: ident),* $(,)?];
+     $($body: tt)*) => {
+        $crate::source::delegate::delegate_source!(@skip ($) $($own)*);
+
+        delegate_source_step!(
+            [
+                {fn get_id() -> &str}
+                {fn get_tilejson() -> &::tilejson::TileJSON}
+                {fn get_tile_info() -> ::martin_tile_utils::TileInfo}
+                {fn support_url_query() -> bool}
+                {fn required_query_keys() -> &[&str]}
+                {fn prefers_uncompressed() -> bool}
+                {fn uses_tms() -> bool}
+                {fn crs() -> &str}
+                {fn requires_auth() -> bool}
+                {fn wrap_x() -> bool}
+                {fn miss_behavior() -> $crate::source::MissBehavior}
+                {fn tags() -> &[String]}
+                {fn tile_size() -> u32}
+                {async fn get_tile(
+                    xyz: ::martin_tile_utils::TileCoord,
+                    url_query: Option<&$crate::source::UrlQuery>
+                ) -> $crate::MartinResult<$crate::source::TileData>}
+                {fn url_query_key(query: &$crate::source::UrlQuery) -> String}
+                {fn cache_control_max_age() -> Option<u32>}
+                {fn last_modified() -> Option<::std::time::SystemTime>}
+                {async fn health_check() -> $crate::MartinResult<()>}
+                {async fn get_tile_with_info(
+                    xyz: ::martin_tile_utils::TileCoord,
+                    url_query: Option<&$crate::source::UrlQuery>
+                ) -> $crate::MartinResult<$crate::source::Tile>}
+                {async fn get_tile_opt(
+                    xyz: ::martin_tile_utils::TileCoord,
+                    url_query: Option<&$crate::source::UrlQuery>
+                ) -> $crate::MartinResult<Option<$crate::source::Tile>>}
+                {async fn get_tile_decoded(
+                    xyz: ::martin_tile_utils::TileCoord,
+                    url_query: Option<&$crate::source::UrlQuery>
+                ) -> $crate::MartinResult<($crate::source::Tile, ::martin_tile_utils::TileInfo)>}
+                {fn supports_range() -> bool}
+                {async fn get_tile_range(
+                    xyz: ::martin_tile_utils::TileCoord,
+                    url_query: Option<&$crate::source::UrlQuery>,
+                    range: ::std::ops::Range<usize>
+                ) -> $crate::MartinResult<$crate::source::Tile>}
+                {fn tile_stream() -> Option<::futures::stream::BoxStream<
+                    '_,
+                    $crate::MartinResult<(::martin_tile_utils::TileCoord, $crate::source::Tile)>,
+                >>}
+                {async fn has_tile(
+                    xyz: ::martin_tile_utils::TileCoord,
+                    url_query: Option<&$crate::source::UrlQuery>
+                ) -> $crate::MartinResult<bool>}
+                {async fn get_tiles_batch(
+                    coords: &[::martin_tile_utils::TileCoord],
+                    url_query: Option<&$crate::source::UrlQuery>
+                ) -> $crate::MartinResult<Vec<Option<$crate::source::Tile>>>}
+                {async fn prewarm(
+                    bounds: [f64; 4],
+                    zooms: ::std::ops::Range<u8>,
+                    url_query: Option<&$crate::source::UrlQuery>
+                ) -> $crate::MartinResult<usize>}
+                {async fn get_tile_etag(
+                    xyz: ::martin_tile_utils::TileCoord,
+                    url_query: Option<&$crate::source::UrlQuery>
+                ) -> $crate::MartinResult<Option<String>>}
+                {fn supported_formats() -> Vec<::martin_tile_utils::Format>}
+                {async fn get_tile_as(
+                    xyz: ::martin_tile_utils::TileCoord,
+                    url_query: Option<&$crate::source::UrlQuery>,
+                    format: ::martin_tile_utils::Format
+                ) -> $crate::MartinResult<$crate::source::Tile>}
+                {async fn transcode_tile(
+                    xyz: ::martin_tile_utils::TileCoord,
+                    url_query: Option<&$crate::source::UrlQuery>,
+                    format: ::martin_tile_utils::Format,
+                    options: $crate::source::TranscodeOptions
+                ) -> $crate::MartinResult<$crate::source::Tile>}
+                {fn can_encode(format: ::martin_tile_utils::Format) -> bool}
+                {fn available_encodings() -> Vec<::martin_tile_utils::Encoding>}
+                {fn approximate_tile_count() -> Option<u64>}
+                {fn approximate_total_bytes() -> Option<u64>}
+                {fn max_overzoom() -> Option<u8>}
+                {fn available_zooms() -> Option<&[u8]>}
+                {fn covers(xyz: ::martin_tile_utils::TileCoord) -> bool}
+                {fn prefetch_hints(
+                    xyz: ::martin_tile_utils::TileCoord
+                ) -> Vec<::martin_tile_utils::TileCoord>}
+                {fn is_valid_zoom(zoom: u8) -> bool}
+                {fn zoom_check(zoom: u8) -> $crate::source::ZoomCheck}
+            ]
+            [$ty]
+            [$inner $([$idx])?]
+            [$($body)*]
+        );
+    };
+
+    // Defines the macro walking through the methods, skipping the wrapper's own ones.
+    // The `$` is passed in, because nested macro definitions cannot use it directly.
+    (@skip ($d: tt) $($own: ident)*) => {
+        macro_rules! delegate_source_step {
+            ([] [$d ty: ty] [$d($d inner: tt)*] [$d($d body: tt)*]) => {
+                #[::async_trait::async_trait]
+                impl $crate::source::Source for $d ty {
+                    fn clone_source(&self) -> $crate::source::TileInfoSource {
+                        Box::new(self.clone())
+                    }
+
+                    $d($d body)*
+                }
+            };
+            $(
+                ([{fn $own $d($d sig: tt)*} $d($d rest: tt)*] $d($d state: tt)*) => {
+                    delegate_source_step!([$d($d rest)*] $d($d state)*);
+                };
+                ([{async fn $own $d($d sig: tt)*} $d($d rest: tt)*] $d($d state: tt)*) => {
+                    delegate_source_step!([$d($d rest)*] $d($d state)*);
+                };
+            )*
+            ([$d method: tt $d($d rest: tt)*] $d($d state: tt)*) => {
+                $crate::source::delegate::delegate_source!(
+                    @forward $d method [$d($d rest)*] $d($d state)*
+                );
+            };
+        }
+    };
+
+    (@forward {fn $name: ident ($($arg: ident: $arg_ty: ty),*) -> $ret: ty}
+     [$($rest: tt)*] [$ty: ty] [$($inner: tt)*] [$($body: tt)*]) => {
+        delegate_source_step!(
+            [$($rest)*]
+            [$ty]
+            [$($inner)*]
+            [
+                $($body)*
+                fn $name(&self $(, $arg: $arg_ty)*) -> $ret {
+                    self.$($inner)*.$name($($arg),*)
+                }
+            ]
+        );
+    };
+
+    (@forward {async fn $name: ident ($($arg: ident: $arg_ty: ty),*) -> $ret: ty}
+     [$($rest: tt)*] [$ty: ty] [$($inner: tt)*] [$($body: tt)*]) => {
+        delegate_source_step!(
+            [$($rest)*]
+            [$ty]
+            [$($inner)*]
+            [
+                $($body)*
+                async fn $name(&self $(, $arg: $arg_ty)*) -> $ret {
+                    self.$($inner)*.$name($($arg),*).await
+                }
+            ]
+        );
+    };
+}
+
+pub(crate) use delegate_source;
@@ -0,0 +1,299 @@
+use std::fmt::{Debug, Formatter};
+use std::time::SystemTime;
+
+use log::debug;
+use martin_tile_utils::TileCoord;
+
+use crate::source::delegate::delegate_source;
+use crate::source::SourceError::FallbackFormatMismatch;
+use crate::source::{SourceError, Tile, TileData, TileInfoSource, UrlQuery};
+use crate::MartinResult;
+
+/// When [`FallbackSource`] requests a tile from its fallback source
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FallbackOn {
+    /// The primary source has no tile, i.e. returns an empty tile
+    #[default]
+    Missing,
+    /// The primary source has no tile, or fails to return it
+    MissingOrError,
+}
+
+/// Combines two sources of the same format, e.g. a base layer and its overrides.
+/// Tiles are requested from the primary source first, and from the fallback source
+/// if the primary one has no tile, see [`FallbackOn`]. Metadata like the `TileJSON` is the primary's.
+#[derive(Clone)]
+pub struct FallbackSource {
+    primary: TileInfoSource,
+    fallback: TileInfoSource,
+    fallback_on: FallbackOn,
+}
+
+impl FallbackSource {
+    /// Fails with [`SourceError::FallbackFormatMismatch`] if the sources have different formats
+    pub fn new(
+        primary: TileInfoSource,
+        fallback: TileInfoSource,
+        fallback_on: FallbackOn,
+    ) -> Result<Self, SourceError> {
+        let (primary_fmt, fallback_fmt) = (
+            primary.get_tile_info().format,
+            fallback.get_tile_info().format,
+        );
+        if primary_fmt != fallback_fmt {
+            return Err(FallbackFormatMismatch {
+                primary_id: primary.get_id().to_string(),
+                primary: primary_fmt,
+                fallback_id: fallback.get_id().to_string(),
+                fallback: fallback_fmt,
+            });
+        }
+        Ok(Self {
+            primary,
+            fallback,
+            fallback_on,
+        })
+    }
+}
+
+impl Debug for FallbackSource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "FallbackSource {{ primary: {:?}, fallback: {:?}, fallback_on: {:?} }}",
+            self.primary, self.fallback, self.fallback_on
+        )
+    }
+}
+
+delegate_source! {
+    impl Source for FallbackSource => self.primary;
+    except [
+        support_url_query, url_query_key, requires_auth, get_tile, get_tile_with_info,
+        health_check, last_modified, covers, has_tile, get_tile_etag, tile_stream,
+        approximate_tile_count, approximate_total_bytes,
+        get_tile_opt, get_tile_decoded, supports_range, get_tile_range, get_tiles_batch, prewarm,
+        supported_formats, can_encode, get_tile_as, transcode_tile,
+    ];
+
+    /// The URL query is only passed to the sources that support it
+    fn support_url_query(&self) -> bool {
+        self.primary.support_url_query() || self.fallback.support_url_query()
+    }
+
+    fn requires_auth(&self) -> bool {
+        self.primary.requires_auth() || self.fallback.requires_auth()
+    }
+
+    async fn get_tile(
+        &self,
+        xyz: TileCoord,
+        url_query: Option<&UrlQuery>,
+    ) -> MartinResult<TileData> {
+        Ok(self.get_tile_with_info(xyz, url_query).await?.data)
+    }
+
+    async fn get_tile_with_info(
+        &self,
+        xyz: TileCoord,
+        url_query: Option<&UrlQuery>,
+    ) -> MartinResult<Tile> {
+        if self.primary.covers(xyz) {
+            let query = url_query.filter(|_| self.primary.support_url_query());
+            match self.primary.get_tile_with_info(xyz, query).await {
+                Ok(tile) if !tile.data.is_empty() => return Ok(tile),
+                Ok(_) => {}
+                Err(e) if self.fallback_on == FallbackOn::Missing => return Err(e),
+                Err(e) => debug!(
+                    "Using the fallback source {} for tile {xyz} of source {}: {e}",
+                    self.fallback.get_id(),
+                    self.get_id()
+                ),
+            }
+        }
+        let query = url_query.filter(|_| self.fallback.support_url_query());
+        self.fallback.get_tile_with_info(xyz, query).await
+    }
+
+    async fn health_check(&self) -> MartinResult<()> {
+        self.primary.health_check().await?;
+        self.fallback.health_check().await
+    }
+
+    /// The later of both modification times, or `None` unless both are known
+    fn last_modified(&self) -> Option<SystemTime> {
+        Some(self.primary.last_modified()?.max(self.fallback.last_modified()?))
+    }
+
+    fn covers(&self, xyz: TileCoord) -> bool {
+        self.primary.covers(xyz) || self.fallback.covers(xyz)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use async_trait::async_trait;
+    use martin_tile_utils::{Encoding, Format, TileInfo};
+    use tilejson::{tilejson, TileJSON};
+
+    use super::*;
+    use crate::source::tests::FlakySource;
+    use crate::source::Source;
+    use crate::TestSource;
+
+    /// Returns the given tile for requests with a URL query, if it supports queries at all
+    #[derive(Debug, Clone)]
+    struct QuerySource {
+        inner: TestSource,
+        query_tile: Option<TileData>,
+        modified: Option<SystemTime>,
+    }
+
+    #[async_trait]
+    impl Source for QuerySource {
+        fn get_id(&self) -> &str {
+            self.inner.get_id()
+        }
+
+        fn get_tilejson(&self) -> &TileJSON {
+            self.inner.get_tilejson()
+        }
+
+        fn get_tile_info(&self) -> TileInfo {
+            self.inner.get_tile_info()
+        }
+
+        fn clone_source(&self) -> TileInfoSource {
+            Box::new(self.clone())
+        }
+
+        fn support_url_query(&self) -> bool {
+            self.query_tile.is_some()
+        }
+
+        fn last_modified(&self) -> Option<SystemTime> {
+            self.modified
+        }
+
+        async fn get_tile(
+            &self,
+            xyz: TileCoord,
+            url_query: Option<&UrlQuery>,
+        ) -> MartinResult<TileData> {
+            match (url_query, &self.query_tile) {
+                (Some(_), Some(tile)) => Ok(tile.clone()),
+                (Some(_), None) => panic!("{} got an unsupported query", self.get_id()),
+                (None, _) => self.inner.get_tile(xyz, url_query).await,
+            }
+        }
+    }
+
+    #[actix_rt::test]
+    async fn fallback_source() {
+        let source = |id, data: &[u8]| -> TileInfoSource {
+            Box::new(TestSource {
+                id,
+                tj: tilejson! { tiles: vec![] },
+                info: TileInfo::new(Format::Mvt, Encoding::Uncompressed),
+                data: data.to_vec(),
+            })
+        };
+        let flaky = || -> TileInfoSource {
+            Box::new(FlakySource {
+                inner: TestSource {
+                    id: "flaky",
+                    tj: tilejson! { tiles: vec![] },
+                    info: TileInfo::new(Format::Mvt, Encoding::Uncompressed),
+                    data: vec![1_u8],
+                },
+                calls: Arc::new(AtomicUsize::new(0)),
+                failures: 1,
+                terminal: false,
+            })
+        };
+        let xyz = TileCoord { z: 0, x: 0, y: 0 };
+
+        let src = FallbackSource::new(
+            source("primary", &[1, 2]),
+            source("base", &[3]),
+            FallbackOn::Missing,
+        )
+        .unwrap();
+        assert_eq!(src.get_id(), "primary");
+        assert_eq!(src.get_tile(xyz, None).await.unwrap(), vec![1_u8, 2]);
+
+        let src = FallbackSource::new(
+            source("primary", &[]),
+            source("base", &[3]),
+            FallbackOn::Missing,
+        )
+        .unwrap();
+        let tile = src.clone_source().get_tile_opt(xyz, None).await.unwrap();
+        assert_eq!(tile.unwrap().data, vec![3_u8]);
+
+        let src = FallbackSource::new(flaky(), source("base", &[3]), FallbackOn::Missing).unwrap();
+        assert!(src.get_tile(xyz, None).await.is_err());
+        let src =
+            FallbackSource::new(flaky(), source("base", &[3]), FallbackOn::MissingOrError).unwrap();
+        assert_eq!(src.get_tile(xyz, None).await.unwrap(), vec![3_u8]);
+
+        let png = Box::new(TestSource {
+            id: "png",
+            tj: tilejson! { tiles: vec![] },
+            info: TileInfo::from(Format::Png),
+            data: Vec::default(),
+        });
+        let err = FallbackSource::new(source("primary", &[]), png, FallbackOn::Missing).err();
+        assert_eq!(
+            err.unwrap().to_string(),
+            "Source png (png) cannot be the fallback of source primary (mvt), because their formats differ"
+        );
+    }
+    #[actix_rt::test]
+    async fn fallback_source_metadata() {
+        let source = |id, data: &[u8], query_tile: Option<&[u8]>, modified| -> TileInfoSource {
+            Box::new(QuerySource {
+                inner: TestSource {
+                    id,
+                    tj: tilejson! { tiles: vec![] },
+                    info: TileInfo::new(Format::Mvt, Encoding::Uncompressed),
+                    data: data.to_vec(),
+                },
+                query_tile: query_tile.map(<[u8]>::to_vec),
+                modified,
+            })
+        };
+        let xyz = TileCoord { z: 0, x: 0, y: 0 };
+        let query = UrlQuery::from([("key".to_string(), "value".to_string())]);
+        let (early, late) = (
+            SystemTime::UNIX_EPOCH,
+            SystemTime::UNIX_EPOCH + Duration::from_secs(60),
+        );
+
+        // only the fallback source supports the query
+        let src = FallbackSource::new(
+            source("primary", &[], None, Some(late)),
+            source("base", &[3], Some(&[4]), Some(early)),
+            FallbackOn::Missing,
+        )
+        .unwrap();
+        assert!(src.support_url_query());
+        assert_eq!(src.get_tile(xyz, Some(&query)).await.unwrap(), vec![4_u8]);
+        assert_eq!(src.get_tile(xyz, None).await.unwrap(), vec![3_u8]);
+        assert_eq!(src.last_modified(), Some(late));
+
+        // only the primary source supports the query
+        let src = FallbackSource::new(
+            source("primary", &[], Some(&[]), None),
+            source("base", &[3], None, Some(early)),
+            FallbackOn::Missing,
+        )
+        .unwrap();
+        assert_eq!(src.get_tile(xyz, Some(&query)).await.unwrap(), vec![3_u8]);
+        assert_eq!(src.last_modified(), None);
+    }
+}
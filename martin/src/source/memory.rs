@@ -0,0 +1,154 @@
+use std::collections::HashMap;
+use std::fmt::{Debug, Formatter};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use martin_tile_utils::{TileCoord, TileInfo};
+use tilejson::TileJSON;
+
+use crate::source::{MissBehavior, Source, Tile, TileData, TileInfoSource, UrlQuery};
+use crate::MartinResult;
+
+/// A source serving hand-crafted tiles from memory, e.g. for fixtures or demos without
+/// a database or a file. Clones share the same tiles. Tiles that are not in the map are empty,
+/// and answered according to the source's [`MissBehavior`].
+#[derive(Clone)]
+pub struct MemorySource {
+    id: String,
+    tilejson: TileJSON,
+    info: TileInfo,
+    tiles: Arc<HashMap<TileCoord, Tile>>,
+    miss_behavior: MissBehavior,
+}
+
+impl MemorySource {
+    /// The `info` is reported for the whole source, but each tile keeps its own tile info.
+    #[must_use]
+    pub fn new(
+        id: impl Into<String>,
+        tilejson: TileJSON,
+        info: TileInfo,
+        tiles: HashMap<TileCoord, Tile>,
+    ) -> Self {
+        Self {
+            id: id.into(),
+            tilejson,
+            info,
+            tiles: Arc::new(tiles),
+            miss_behavior: MissBehavior::default(),
+        }
+    }
+
+    /// Set how to respond to requests for tiles that are not in the map
+    #[must_use]
+    pub fn with_miss_behavior(mut self, miss_behavior: MissBehavior) -> Self {
+        self.miss_behavior = miss_behavior;
+        self
+    }
+}
+
+impl Debug for MemorySource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "MemorySource {{ id: {}, info: {}, tiles: {} }}",
+            self.id,
+            self.info,
+            self.tiles.len()
+        )
+    }
+}
+
+#[async_trait]
+impl Source for MemorySource {
+    fn get_id(&self) -> &str {
+        &self.id
+    }
+
+    fn get_tilejson(&self) -> &TileJSON {
+        &self.tilejson
+    }
+
+    fn get_tile_info(&self) -> TileInfo {
+        self.info
+    }
+
+    fn clone_source(&self) -> TileInfoSource {
+        Box::new(self.clone())
+    }
+
+    fn miss_behavior(&self) -> MissBehavior {
+        self.miss_behavior
+    }
+
+    async fn get_tile(
+        &self,
+        xyz: TileCoord,
+        url_query: Option<&UrlQuery>,
+    ) -> MartinResult<TileData> {
+        Ok(self.get_tile_with_info(xyz, url_query).await?.data)
+    }
+
+    async fn get_tile_with_info(
+        &self,
+        xyz: TileCoord,
+        _url_query: Option<&UrlQuery>,
+    ) -> MartinResult<Tile> {
+        Ok(self
+            .tiles
+            .get(&xyz)
+            .cloned()
+            .unwrap_or_else(|| Tile::new(TileData::default(), self.info)))
+    }
+
+    async fn has_tile(&self, xyz: TileCoord, _url_query: Option<&UrlQuery>) -> MartinResult<bool> {
+        Ok(self
+            .tiles
+            .get(&xyz)
+            .is_some_and(|tile| !tile.data.is_empty()))
+    }
+
+    fn approximate_tile_count(&self) -> Option<u64> {
+        u64::try_from(self.tiles.len()).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use martin_tile_utils::{Encoding, Format};
+    use tilejson::tilejson;
+
+    use super::*;
+
+    #[actix_rt::test]
+    async fn memory_source() {
+        let xyz = TileCoord { z: 1, x: 1, y: 0 };
+        let info = TileInfo::new(Format::Mvt, Encoding::Gzip);
+        let tiles = HashMap::from([(xyz, Tile::new(vec![1_u8, 2], info))]);
+        let src = MemorySource::new(
+            "memory",
+            tilejson! { tiles: vec![], minzoom: 0, maxzoom: 2 },
+            info,
+            tiles,
+        );
+        assert_eq!(src.get_id(), "memory");
+        assert_eq!(src.get_tile_info(), info);
+        assert_eq!(src.get_tilejson().maxzoom, Some(2));
+        assert_eq!(src.approximate_tile_count(), Some(1));
+        assert_eq!(src.miss_behavior(), MissBehavior::NotFound);
+
+        // clones share the tiles
+        let cloned = src.clone_source();
+        let tile = cloned.get_tile_with_info(xyz, None).await.unwrap();
+        assert_eq!((tile.data, tile.info), (vec![1_u8, 2], info));
+        assert!(cloned.has_tile(xyz, None).await.unwrap());
+
+        let missing = TileCoord { z: 1, x: 0, y: 0 };
+        assert!(src.get_tile(missing, None).await.unwrap().is_empty());
+        assert!(src.get_tile_opt(missing, None).await.unwrap().is_none());
+        assert!(!src.has_tile(missing, None).await.unwrap());
+
+        let src = src.with_miss_behavior(MissBehavior::Empty);
+        assert_eq!(src.miss_behavior(), MissBehavior::Empty);
+    }
+}
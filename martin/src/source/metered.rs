@@ -0,0 +1,126 @@
+use std::fmt::{Debug, Formatter};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use martin_tile_utils::{Format, TileCoord};
+
+use crate::source::delegate::delegate_source;
+use crate::source::{Source, Tile, TileData, TileInfoSource, UrlQuery};
+use crate::MartinResult;
+
+/// A function called by [`MeteredSource`] after each tile request with the source ID,
+/// how long the request took, and whether it failed
+pub type TileMetric = Arc<dyn Fn(&str, Duration, bool) + Send + Sync>;
+
+/// Wraps a source to measure how long its tile requests take, e.g. to record them
+/// in a metrics library without Martin depending on it.
+#[derive(Clone)]
+pub struct MeteredSource {
+    source: TileInfoSource,
+    metric: TileMetric,
+}
+
+impl MeteredSource {
+    #[must_use]
+    pub fn new(source: TileInfoSource, metric: TileMetric) -> Self {
+        Self { source, metric }
+    }
+
+    async fn measure<T>(
+        &self,
+        future: impl Future<Output = MartinResult<T>> + Send,
+    ) -> MartinResult<T> {
+        let start = Instant::now();
+        let result = future.await;
+        (self.metric)(self.get_id(), start.elapsed(), result.is_err());
+        result
+    }
+}
+
+impl Debug for MeteredSource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "MeteredSource {{ source: {:?} }}", self.source)
+    }
+}
+
+delegate_source! {
+    impl Source for MeteredSource => self.source;
+    except [
+        get_tile, get_tile_with_info, get_tile_as,
+        get_tile_opt, get_tile_decoded, supports_range, get_tile_range, get_tiles_batch, prewarm,
+        transcode_tile,
+    ];
+
+    async fn get_tile(
+        &self,
+        xyz: TileCoord,
+        url_query: Option<&UrlQuery>,
+    ) -> MartinResult<TileData> {
+        self.measure(self.source.get_tile(xyz, url_query)).await
+    }
+
+    async fn get_tile_with_info(
+        &self,
+        xyz: TileCoord,
+        url_query: Option<&UrlQuery>,
+    ) -> MartinResult<Tile> {
+        self.measure(self.source.get_tile_with_info(xyz, url_query)).await
+    }
+
+    async fn get_tile_as(
+        &self,
+        xyz: TileCoord,
+        url_query: Option<&UrlQuery>,
+        format: Format,
+    ) -> MartinResult<Tile> {
+        self.measure(self.source.get_tile_as(xyz, url_query, format)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use martin_tile_utils::{Encoding, TileInfo};
+    use tilejson::tilejson;
+
+    use super::*;
+    use crate::source::tests::SlowSource;
+    use crate::TestSource;
+
+    #[actix_rt::test]
+    async fn metered_source() {
+        let calls = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let recorded = calls.clone();
+        let src = MeteredSource::new(
+            Box::new(SlowSource(
+                TestSource {
+                    id: "metered",
+                    tj: tilejson! { tiles: vec![] },
+                    info: TileInfo::new(Format::Mvt, Encoding::Uncompressed),
+                    data: vec![1_u8],
+                },
+                Duration::from_millis(5),
+            )),
+            Arc::new(move |id: &str, elapsed: Duration, failed: bool| {
+                recorded
+                    .lock()
+                    .unwrap()
+                    .push((id.to_string(), elapsed, failed));
+            }),
+        );
+        let xyz = TileCoord { z: 0, x: 0, y: 0 };
+        assert_eq!(src.get_tile(xyz, None).await.unwrap(), vec![1_u8]);
+        src.clone_source()
+            .get_tile_with_info(xyz, None)
+            .await
+            .unwrap();
+
+        let calls = calls.lock().unwrap();
+        assert_eq!(calls.len(), 2);
+        for (id, elapsed, failed) in calls.iter() {
+            assert_eq!(id, "metered");
+            assert!(*elapsed >= Duration::from_millis(5));
+            assert!(!failed);
+        }
+    }
+}
@@ -0,0 +1,4044 @@
+pub(crate) mod delegate;
+
+mod blend;
+mod cached;
+mod debug_grid;
+mod fallback;
+mod memory;
+mod metered;
+mod overzoom;
+mod retry;
+mod rewrite_url;
+mod sharded;
+mod timeout;
+mod transcode;
+mod transform;
+mod validating;
+mod zoom_shift;
+
+pub use blend::BlendSource;
+pub use cached::CachedSource;
+pub use debug_grid::DebugGridSource;
+pub use fallback::{FallbackOn, FallbackSource};
+pub use memory::MemorySource;
+pub use metered::{MeteredSource, TileMetric};
+pub use overzoom::{OverzoomSource, TileClip};
+pub use retry::{RetryPolicy, RetrySource};
+pub use rewrite_url::RewriteUrlSource;
+pub use sharded::ShardedSource;
+pub use timeout::TimeoutSource;
+pub use transcode::TranscodeSource;
+pub use transform::{TileTransform, TransformSource};
+pub use validating::{TileValidation, ValidatingSource};
+pub use zoom_shift::ZoomShiftSource;
+
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{Debug, Formatter};
+use std::ops::{Range, RangeInclusive};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, SystemTime};
+
+use actix_web::error::{ErrorBadRequest, ErrorNotFound};
+use actix_web::http::StatusCode;
+use actix_web::ResponseError;
+use async_trait::async_trait;
+use futures::future::{join_all, try_join_all};
+use futures::stream::BoxStream;
+use itertools::Itertools as _;
+use log::{debug, warn};
+use martin_tile_utils::{
+    decode_brotli, decode_gzip, encode_brotli, encode_gzip, Encoding, Format, TileCoord, TileInfo,
+    MAX_ZOOM,
+};
+use serde::{Deserialize, Serialize};
+use tilejson::TileJSON;
+
+use crate::source::SourceError::{
+    AliasConflict, BlendError, BlendSizeMismatch, CannotBlend, CannotConcatenate, CrsMismatch,
+    DanglingAlias, DecodingError, DefaultSourceNotFound, DuplicateSourceIds, EncodingError,
+    FallbackFormatMismatch, InvalidQueryValue, InvalidRange, InvalidTile, MergeFormatMismatch,
+    MissingQueryKey, MvtMergeError, NoShards, ShardMismatch, Timeout, TooManySources,
+    TranscodeError, TransparentNotRaster, Unauthorized, UnsupportedEncoding, UnsupportedFormat,
+    UnsupportedQuality, ZoomShiftOutOfRange,
+};
+use crate::srv::{map_tile_error, merge_tilejson};
+use crate::{MartinError, MartinResult};
+
+pub type TileData = Vec<u8>;
+pub type UrlQuery = HashMap<String, String>;
+
+pub type TileInfoSource = Box<dyn Source>;
+
+pub type TileInfoSources = Vec<TileInfoSource>;
+
+/// Build a canonical, percent-encoded form of the URL query with the parameters sorted,
+/// so that the same parameters in a different order produce the same key.
+#[must_use]
+pub fn normalize_query(query: &UrlQuery) -> String {
+    url::form_urlencoded::Serializer::new(String::new())
+        .extend_pairs(query.iter().sorted())
+        .finish()
+}
+
+/// Separates source IDs when requesting a composite source, e.g. `/src1,src2/{z}/{x}/{y}`
+pub const MERGE_DELIMITER: char = ',';
+
+/// Source ID that resolves to the source set with [`TileSourcesOf::set_default`], e.g. `/_/{z}/{x}/{y}`.
+/// A source configured with this exact ID takes precedence.
+pub const DEFAULT_SOURCE_ALIAS: &str = "_";
+
+/// Maximum number of sources that can be merged into one composite source, unless configured
+pub const MAX_MERGE_SOURCES_DEFAULT: usize = 16;
+
+/// Coordinate reference system of the tiles, unless a source reports otherwise with [`Source::crs`]
+pub const DEFAULT_CRS: &str = "EPSG:3857";
+
+/// Size of the tiles in pixels, unless a source reports otherwise with [`Source::tile_size`]
+pub const DEFAULT_TILE_SIZE: u32 = 256;
+
+#[derive(thiserror::Error, Debug)]
+pub enum SourceError {
+    /// `left` is the tile info of the sources merged so far, starting with `left_id`
+    #[error("Cannot merge source {left_id} ({left}) with source {right_id} ({right})")]
+    MergeFormatMismatch {
+        left_id: String,
+        left: TileInfo,
+        right_id: String,
+        right: TileInfo,
+    },
+
+    #[error("Cannot merge source {left_id} ({left}) with source {right_id} ({right}), because their coordinate reference systems differ")]
+    CrsMismatch {
+        left_id: String,
+        left: String,
+        right_id: String,
+        right: String,
+    },
+
+    #[error("Cannot merge {count} sources, at most {max} sources can be merged")]
+    TooManySources { count: usize, max: usize },
+
+    #[error("Source {0} cannot produce {1} tiles")]
+    UnsupportedFormat(String, Format),
+
+    #[error("Tile is stored as {0}, but the client does not accept this encoding")]
+    UnsupportedEncoding(TileInfo),
+
+    #[error("Unable to decode {1} tile: {0}")]
+    DecodingError(#[source] std::io::Error, TileInfo),
+
+    #[error("Unable to encode tile as {1:?}: {0}")]
+    EncodingError(#[source] std::io::Error, Encoding),
+
+    #[error("Cannot concatenate {0} tiles, only MVT tiles can be merged into one")]
+    CannotConcatenate(Format),
+
+    #[error("Unable to merge MVT layers: {0}")]
+    MvtMergeError(String),
+
+    #[error("Source {0} requires the URL query parameter {1}")]
+    MissingQueryKey(String, String),
+
+    #[error("URL query parameter {0}={1} must be a number from {2} to {3}")]
+    InvalidQueryValue(String, String, u8, u8),
+
+    #[error("Source {0} cannot encode {1} tiles with a given quality")]
+    UnsupportedQuality(String, Format),
+
+    #[error("Byte range {0:?} is not valid for a tile of {1} bytes")]
+    InvalidRange(Range<usize>, usize),
+
+    #[error("Access to source {0} is not authorized")]
+    Unauthorized(String),
+
+    #[error("Default source {0} does not exist")]
+    DefaultSourceNotFound(String),
+
+    #[error("Multiple sources have the same ID: {}", .0.join(", "))]
+    DuplicateSourceIds(Vec<String>),
+
+    #[error("Source {fallback_id} ({fallback}) cannot be the fallback of source {primary_id} ({primary}), because their formats differ")]
+    FallbackFormatMismatch {
+        primary_id: String,
+        primary: Format,
+        fallback_id: String,
+        fallback: Format,
+    },
+
+    #[error("Alias {0} cannot be used, because it is already a source ID")]
+    AliasConflict(String),
+
+    #[error("Alias {0} refers to source {1}, which does not exist")]
+    DanglingAlias(String, String),
+
+    #[error("Source {0} returned an invalid {2} tile {1}: {3}")]
+    InvalidTile(String, TileCoord, Format, &'static str),
+
+    #[error("Source {0} did not return tile {1} within {2:?}")]
+    Timeout(String, TileCoord, Duration),
+
+    #[error("Source {0} has no tile {1} when shifted by {2} zoom levels")]
+    ZoomShiftOutOfRange(String, TileCoord, i8),
+
+    #[error("Source {0} ({1}) cannot be blended, only PNG and JPEG tiles are supported")]
+    CannotBlend(String, Format),
+
+    #[error("Cannot blend source {top_id} ({top}px) over source {bottom_id} ({bottom}px), because their tile sizes differ")]
+    BlendSizeMismatch {
+        top_id: String,
+        top: u32,
+        bottom_id: String,
+        bottom: u32,
+    },
+
+    #[error("Unable to blend tile {1} of source {0}: {2}")]
+    BlendError(String, TileCoord, String),
+
+    #[error("Unable to transcode tile {1} of source {0}: {2}")]
+    TranscodeError(String, TileCoord, String),
+
+    #[error(
+        "Source {0} ({1}) cannot respond with transparent tiles, because it is not a raster source"
+    )]
+    TransparentNotRaster(String, Format),
+
+    #[error("A sharded source needs at least one shard")]
+    NoShards,
+
+    #[error("Shard {shard_id} has a different {property} than the first shard {first_id}")]
+    ShardMismatch {
+        first_id: String,
+        shard_id: String,
+        property: &'static str,
+    },
+}
+
+impl ResponseError for SourceError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            MergeFormatMismatch { .. }
+            | CrsMismatch { .. }
+            | TooManySources { .. }
+            | UnsupportedEncoding(..)
+            | CannotConcatenate(..)
+            | MissingQueryKey(..)
+            | InvalidQueryValue(..)
+            | UnsupportedQuality(..) => StatusCode::BAD_REQUEST,
+            UnsupportedFormat(..) => StatusCode::NOT_ACCEPTABLE,
+            DecodingError(..)
+            | EncodingError(..)
+            | DuplicateSourceIds(..)
+            | AliasConflict(..)
+            | FallbackFormatMismatch { .. }
+            | CannotBlend(..)
+            | BlendSizeMismatch { .. }
+            | BlendError(..)
+            | TranscodeError(..)
+            | TransparentNotRaster(..)
+            | NoShards
+            | ShardMismatch { .. }
+            | MvtMergeError(..)
+            | InvalidTile(..) => StatusCode::INTERNAL_SERVER_ERROR,
+            InvalidRange(..) => StatusCode::RANGE_NOT_SATISFIABLE,
+            Unauthorized(..) => StatusCode::FORBIDDEN,
+            DefaultSourceNotFound(..) | DanglingAlias(..) | ZoomShiftOutOfRange(..) => {
+                StatusCode::NOT_FOUND
+            }
+            Timeout(..) => StatusCode::GATEWAY_TIMEOUT,
+        }
+    }
+}
+
+/// Access to a tile source as a trait object, for both concrete and boxed sources.
+pub trait AsSource: Send + Sync {
+    fn as_source(&self) -> &dyn Source;
+}
+
+impl<S: Source> AsSource for S {
+    fn as_source(&self) -> &dyn Source {
+        self
+    }
+}
+
+impl AsSource for TileInfoSource {
+    fn as_source(&self) -> &dyn Source {
+        self.as_ref()
+    }
+}
+
+impl AsSource for dyn Source {
+    fn as_source(&self) -> &dyn Source {
+        self
+    }
+}
+
+/// A source that can be added to a [`TileSourcesOf`], which shares its sources between clones
+pub trait SharedSource: AsSource + Sized {
+    /// How the source is shared, i.e. `dyn Source` for boxed sources, and the source itself otherwise
+    type Shared: AsSource + ?Sized;
+
+    fn into_shared(self) -> Arc<Self::Shared>;
+}
+
+impl<S: Source> SharedSource for S {
+    type Shared = S;
+
+    fn into_shared(self) -> Arc<S> {
+        Arc::new(self)
+    }
+}
+
+impl SharedSource for TileInfoSource {
+    type Shared = dyn Source;
+
+    fn into_shared(self) -> Arc<dyn Source> {
+        Arc::from(self)
+    }
+}
+
+/// A set of tile sources of any kind, stored as `Arc<dyn Source>`
+pub type TileSources = TileSourcesOf<TileInfoSource>;
+
+/// A set of tile sources of the same type. Deployments with a single kind of source
+/// can use it to avoid boxing, see [`TileSourcesOf::get`].
+/// Sources are shared between clones, so cloning is cheap even with many sources,
+/// see [`TileSourcesOf::snapshot`]. Sources inserted or removed afterwards
+/// only affect the set they were changed in.
+pub struct TileSourcesOf<S: SharedSource> {
+    sources: HashMap<String, Arc<S::Shared>>,
+    /// Lowercased source ID -> source ID, only used for case-insensitive lookups
+    lowercase_ids: Option<HashMap<String, String>>,
+    /// Overrides [`MAX_MERGE_SOURCES_DEFAULT`]
+    max_merge_sources: Option<usize>,
+    /// Lazily computed catalog, reset whenever sources are added or removed
+    catalog: OnceLock<TileCatalog>,
+    /// Source ID that [`DEFAULT_SOURCE_ALIAS`] resolves to
+    default_id: Option<String>,
+    /// Alias -> source ID, see [`TileSourcesOf::add_alias`]
+    aliases: HashMap<String, String>,
+}
+
+impl<S: SharedSource> Default for TileSourcesOf<S> {
+    fn default() -> Self {
+        Self {
+            sources: HashMap::new(),
+            lowercase_ids: None,
+            max_merge_sources: None,
+            catalog: OnceLock::new(),
+            default_id: None,
+            aliases: HashMap::new(),
+        }
+    }
+}
+
+impl<S: SharedSource> Clone for TileSourcesOf<S> {
+    fn clone(&self) -> Self {
+        Self {
+            sources: self.sources.clone(),
+            lowercase_ids: self.lowercase_ids.clone(),
+            max_merge_sources: self.max_merge_sources,
+            catalog: self.catalog.clone(),
+            default_id: self.default_id.clone(),
+            aliases: self.aliases.clone(),
+        }
+    }
+}
+
+#[allow(clippy::missing_fields_in_debug)]
+impl<S: SharedSource> Debug for TileSourcesOf<S> {
+    /// Lists the source IDs in order, with their format and encoding, e.g. `TileSources { src: mvt/gzip }`
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut dbg = f.debug_struct("TileSources");
+        for (id, src) in self.sources.iter().sorted_by(|a, b| a.0.cmp(b.0)) {
+            let info = src.as_source().get_tile_info();
+            let enc = info.encoding.content_encoding().unwrap_or("none");
+            dbg.field(id, &format_args!("{}/{enc}", info.format));
+        }
+        dbg.finish()
+    }
+}
+
+impl<S: SharedSource> FromIterator<S> for TileSourcesOf<S> {
+    /// Same as [`TileSourcesOf::new`], but without collecting the sources into vectors first,
+    /// e.g. when chaining the sources of several providers.
+    fn from_iter<I: IntoIterator<Item = S>>(sources: I) -> Self {
+        let sources = Self {
+            sources: sources
+                .into_iter()
+                .map(|src| (src.as_source().get_id().to_string(), src.into_shared()))
+                .collect(),
+            ..Self::default()
+        };
+        for id in sources.validate_ids() {
+            warn!("Source ID `{id}` contains a `{MERGE_DELIMITER}`, and can only be accessed as part of a composite source");
+        }
+        sources
+    }
+}
+
+/// Collects sources to create a [`TileSourcesOf`], and fails if several sources have the same ID,
+/// unlike [`TileSourcesOf::new`] which silently keeps the last one.
+pub struct TileSourcesBuilder<S = TileInfoSource> {
+    sources: Vec<S>,
+}
+
+impl<S> Default for TileSourcesBuilder<S> {
+    fn default() -> Self {
+        Self {
+            sources: Vec::new(),
+        }
+    }
+}
+
+impl<S: SharedSource> TileSourcesBuilder<S> {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn add_source(mut self, source: S) -> Self {
+        self.sources.push(source);
+        self
+    }
+
+    #[must_use]
+    pub fn add_sources(mut self, sources: impl IntoIterator<Item = S>) -> Self {
+        self.sources.extend(sources);
+        self
+    }
+
+    /// Create the source set, or fail with [`SourceError::DuplicateSourceIds`]
+    /// listing every ID that is used by more than one source, sorted.
+    pub fn build(self) -> Result<TileSourcesOf<S>, SourceError> {
+        let duplicates = self
+            .sources
+            .iter()
+            .map(|src| src.as_source().get_id())
+            .duplicates()
+            .sorted()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        if !duplicates.is_empty() {
+            return Err(DuplicateSourceIds(duplicates));
+        }
+        let sources = TileSourcesOf::new(vec![self.sources]);
+        sources.check_miss_behaviors()?;
+        Ok(sources)
+    }
+}
+
+/// Result of checking a zoom level against a source
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ZoomCheck {
+    Ok,
+    /// The zoom is below the source's minzoom, which is included
+    BelowMin(u8),
+    /// The zoom is above the source's maxzoom, which is included
+    AboveMax(u8),
+    /// The zoom is within bounds, but not in the source's [`Source::available_zooms`],
+    /// or rejected by its [`Source::is_valid_zoom`]
+    NotAvailable,
+}
+
+/// How to respond to a tile request when a source has no tile, see [`Source::miss_behavior`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MissBehavior {
+    /// Respond with `404 Not Found`, the same as the default response
+    /// if none of the requested sources support the zoom, see [`crate::srv::SrvConfig`]
+    #[default]
+    NotFound,
+    /// Respond with a valid empty tile of the source's format, see [`empty_tile`]
+    Empty,
+    /// Respond with a transparent PNG image. Only valid for raster sources.
+    Transparent,
+}
+
+/// Output quality of transcoded raster tiles, see [`Source::transcode_tile`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TranscodeOptions {
+    /// Quality of lossy formats like JPEG, from 1 to 100. Only applied by sources
+    /// that [encode](Source::transcode_tile) lossy formats themselves.
+    pub quality: Option<u8>,
+    /// PNG compression level, from 0 (fastest) to 9 (smallest). The PNG encoder only has
+    /// three levels, so 0–3 are fast, 4–6 the default, and 7–9 the best compression.
+    pub compression: Option<u8>,
+}
+
+impl TranscodeOptions {
+    /// Parse the `quality` and `compression` URL query parameters, e.g. `?quality=80`.
+    /// Missing parameters are `None`, and invalid values fail with [`SourceError::InvalidQueryValue`].
+    pub fn from_query(query: &UrlQuery) -> Result<Self, SourceError> {
+        Ok(Self {
+            quality: parse_query_value(query, "quality", 1..=100)?,
+            compression: parse_query_value(query, "compression", 0..=9)?,
+        })
+    }
+}
+
+fn parse_query_value(
+    query: &UrlQuery,
+    key: &str,
+    range: RangeInclusive<u8>,
+) -> Result<Option<u8>, SourceError> {
+    let Some(value) = query.get(key) else {
+        return Ok(None);
+    };
+    match value.parse() {
+        Ok(parsed) if range.contains(&parsed) => Ok(Some(parsed)),
+        _ => Err(InvalidQueryValue(
+            key.to_string(),
+            value.clone(),
+            *range.start(),
+            *range.end(),
+        )),
+    }
+}
+
+fn is_raster(format: Format) -> bool {
+    matches!(
+        format,
+        Format::Png | Format::Jpeg | Format::Webp | Format::Gif
+    )
+}
+
+pub type TileCatalog = BTreeMap<String, CatalogSourceEntry>;
+
+/// Differences between two catalogs, see [`diff_catalogs`]. Each list is sorted by source ID.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CatalogDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    /// Sources in both catalogs, but with different entries
+    pub changed: Vec<String>,
+}
+
+impl CatalogDiff {
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Compare two catalogs, e.g. before and after reloading the sources
+#[must_use]
+pub fn diff_catalogs(old: &TileCatalog, new: &TileCatalog) -> CatalogDiff {
+    let mut diff = CatalogDiff::default();
+    for (id, entry) in new {
+        match old.get(id) {
+            None => diff.added.push(id.clone()),
+            Some(old_entry) if old_entry != entry => diff.changed.push(id.clone()),
+            Some(_) => {}
+        }
+    }
+    diff.removed = old
+        .keys()
+        .filter(|id| !new.contains_key(*id))
+        .cloned()
+        .collect();
+    diff
+}
+
+/// Inconsistencies of a `TileJSON`, see [`TileSourcesOf::validate_all`]
+fn tilejson_problems(tj: &TileJSON) -> Vec<String> {
+    let mut problems = Vec::new();
+    if let (Some(min), Some(max)) = (tj.minzoom, tj.maxzoom) {
+        if min > max {
+            problems.push(format!("minzoom {min} is above maxzoom {max}"));
+        }
+    }
+    if let Some(max) = tj.maxzoom.filter(|max| *max > MAX_ZOOM) {
+        problems.push(format!(
+            "maxzoom {max} is above the highest zoom {MAX_ZOOM}"
+        ));
+    }
+    if let Some(b) = tj.bounds {
+        if ![b.left, b.right]
+            .iter()
+            .all(|lon| (-180.0..=180.0).contains(lon))
+        {
+            problems.push(format!(
+                "bounds {b} have a longitude outside of [-180, 180]"
+            ));
+        }
+        if ![b.bottom, b.top]
+            .iter()
+            .all(|lat| (-90.0..=90.0).contains(lat))
+        {
+            problems.push(format!("bounds {b} have a latitude outside of [-90, 90]"));
+        }
+        if b.bottom > b.top {
+            problems.push(format!("bounds {b} have the south above the north"));
+        }
+    }
+    problems
+}
+
+/// Sources resolved for a possibly merged request like `a,b`, see [`TileSources::get_sources_detailed`]
+#[derive(Debug)]
+pub struct MergedSources<'a> {
+    /// Sources to merge, without the ones skipped because of the requested zoom
+    pub sources: Vec<&'a dyn Source>,
+    /// Whether any of the sources uses the URL query
+    pub use_url_query: bool,
+    /// Tile info of the merged tile
+    pub info: TileInfo,
+    /// IDs of the sources skipped because they do not support the requested zoom
+    pub skipped: Vec<String>,
+    /// Requested IDs of the included sources, in the same order as [`MergedSources::sources`].
+    /// If only one source is included, its tile is served as is, without merging.
+    pub included: Vec<String>,
+}
+
+impl<S: SharedSource> TileSourcesOf<S> {
+    /// Create a source set. If several sources have the same ID, only the last one is kept.
+    /// Use [`TileSourcesBuilder`] to treat duplicate IDs as an error instead.
+    /// Sources can also be collected from a flat iterator, see [`TileSourcesOf::from_iter`].
+    #[must_use]
+    pub fn new(sources: Vec<Vec<S>>) -> Self {
+        sources.into_iter().flatten().collect()
+    }
+
+    /// Check that only raster sources respond to missing tiles with [`MissBehavior::Transparent`],
+    /// or fail with [`SourceError::TransparentNotRaster`] for the first invalid one by ID.
+    pub fn check_miss_behaviors(&self) -> Result<(), SourceError> {
+        for id in self.source_ids().sorted() {
+            let src = self.sources[id].as_source();
+            let format = src.get_tile_info().format;
+            if src.miss_behavior() == MissBehavior::Transparent && !is_raster(format) {
+                return Err(TransparentNotRaster(id.to_string(), format));
+            }
+        }
+        Ok(())
+    }
+
+    /// Get a sorted list of source IDs that contain the [`MERGE_DELIMITER`],
+    /// and therefore cannot be requested individually.
+    #[must_use]
+    pub fn validate_ids(&self) -> Vec<&str> {
+        self.source_ids()
+            .filter(|id| id.contains(MERGE_DELIMITER))
+            .sorted()
+            .collect()
+    }
+
+    /// Enable or disable case-insensitive source ID lookups in [`TileSources::get_source`].
+    /// An exact match always wins. If several IDs are identical when lowercased,
+    /// only the first one in alphabetical order can be found case-insensitively.
+    #[must_use]
+    pub fn case_insensitive(mut self, enabled: bool) -> Self {
+        self.lowercase_ids = enabled.then(|| self.index_lowercase_ids());
+        self
+    }
+
+    /// Limit how many sources can be requested at once as a composite source.
+    /// Defaults to [`MAX_MERGE_SOURCES_DEFAULT`].
+    #[must_use]
+    pub fn max_merge_sources(mut self, max: usize) -> Self {
+        self.max_merge_sources = Some(max);
+        self
+    }
+
+    /// Make [`DEFAULT_SOURCE_ALIAS`] resolve to the given source, so that clients
+    /// can request tiles without knowing its ID. The source must already exist.
+    /// If it is removed later, requesting the alias fails with [`SourceError::DefaultSourceNotFound`].
+    pub fn set_default(&mut self, id: &str) -> Result<(), SourceError> {
+        if self.sources.contains_key(id) {
+            self.default_id = Some(id.to_string());
+            Ok(())
+        } else {
+            Err(DefaultSourceNotFound(id.to_string()))
+        }
+    }
+
+    /// The source ID that [`DEFAULT_SOURCE_ALIAS`] resolves to, if any
+    #[must_use]
+    pub fn default_id(&self) -> Option<&str> {
+        self.default_id.as_deref()
+    }
+
+    /// Make another ID resolve to an existing source, e.g. its old ID after a rename,
+    /// without storing the source twice. Aliases are only used if no source has the requested ID,
+    /// so an alias cannot be the ID of an existing source. Adding an alias again replaces its target.
+    /// If the target does not exist when the alias is requested,
+    /// the request fails with [`SourceError::DanglingAlias`].
+    pub fn add_alias(&mut self, alias: &str, target: &str) -> Result<(), SourceError> {
+        if self.sources.contains_key(alias) || alias == DEFAULT_SOURCE_ALIAS {
+            return Err(AliasConflict(alias.to_string()));
+        }
+        self.aliases.insert(alias.to_string(), target.to_string());
+        Ok(())
+    }
+
+    fn index_lowercase_ids(&self) -> HashMap<String, String> {
+        let mut index = HashMap::new();
+        for id in self.sources.keys().sorted() {
+            match index.entry(id.to_lowercase()) {
+                Entry::Vacant(e) => {
+                    e.insert(id.clone());
+                }
+                Entry::Occupied(e) => {
+                    warn!(
+                        "Source {id} has the same case-insensitive ID as {}, and can only be accessed by its exact ID",
+                        e.get()
+                    );
+                }
+            }
+        }
+        index
+    }
+
+    /// Add a source after construction. If a source with the same ID already exists,
+    /// it is replaced by the new one, and the old source is returned.
+    /// It is returned shared, e.g. as `Arc<dyn Source>`, because clones of this set made before keep using it.
+    pub fn insert(&mut self, source: S) -> Option<Arc<S::Shared>> {
+        let old = self.sources.insert(
+            source.as_source().get_id().to_string(),
+            source.into_shared(),
+        );
+        self.catalog.take();
+        if self.lowercase_ids.is_some() {
+            self.lowercase_ids = Some(self.index_lowercase_ids());
+        }
+        old
+    }
+
+    /// Remove a source by its exact ID, returning it if it existed.
+    /// Like in [`TileSourcesOf::insert`], it is returned shared.
+    pub fn remove(&mut self, id: &str) -> Option<Arc<S::Shared>> {
+        let old = self.sources.remove(id);
+        if old.is_some() {
+            self.catalog.take();
+            if self.lowercase_ids.is_some() {
+                self.lowercase_ids = Some(self.index_lowercase_ids());
+            }
+        }
+        old
+    }
+
+    /// A read-only copy of this source set, e.g. to keep serving requests while the sources
+    /// are reloaded. Same as [`Clone::clone`], which shares the sources instead of cloning them.
+    #[must_use]
+    pub fn snapshot(&self) -> Self {
+        self.clone()
+    }
+
+    /// Iterate over all source IDs without building the catalog.
+    pub fn source_ids(&self) -> impl Iterator<Item = &str> {
+        self.sources.keys().map(String::as_str)
+    }
+
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.sources.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.sources.is_empty()
+    }
+
+    /// Get the catalog of all sources. It is computed once, and cached until the sources change.
+    #[must_use]
+    pub fn get_catalog(&self) -> TileCatalog {
+        self.catalog
+            .get_or_init(|| self.catalog_iter().collect())
+            .clone()
+    }
+
+    /// Iterate over the catalog entries sorted by ID, computing each entry only when it is reached,
+    /// e.g. to stream a large catalog. Unlike [`TileSourcesOf::get_catalog`], entries are not cached.
+    pub fn catalog_iter(&self) -> impl Iterator<Item = (String, CatalogSourceEntry)> + '_ {
+        self.sources
+            .iter()
+            .sorted_by(|a, b| a.0.cmp(b.0))
+            .map(|(id, src)| (id.clone(), src.as_source().get_catalog_entry()))
+    }
+
+    /// Same as [`TileSourcesOf::get_catalog`], but computes the entries concurrently,
+    /// each on Tokio's blocking thread pool. Use it when the catalog entries of many sources
+    /// are expensive to compute, e.g. if they query the backend. The result is cached the same way.
+    /// Must be called from within a Tokio runtime.
+    pub async fn get_catalog_async(&self) -> TileCatalog
+    where
+        S::Shared: 'static,
+    {
+        if let Some(catalog) = self.catalog.get() {
+            return catalog.clone();
+        }
+        let entries = self.sources.iter().map(|(id, src)| {
+            let src = Arc::clone(src);
+            let entry = tokio::task::spawn_blocking(move || src.as_source().get_catalog_entry());
+            async move {
+                (
+                    id.clone(),
+                    entry.await.expect("catalog entry task panicked"),
+                )
+            }
+        });
+        let catalog = join_all(entries).await.into_iter().collect();
+        self.catalog.get_or_init(|| catalog).clone()
+    }
+
+    /// Same as [`TileSourcesOf::get_catalog`], but only with the entries matching the predicate.
+    #[must_use]
+    pub fn get_catalog_filtered(
+        &self,
+        predicate: impl Fn(&CatalogSourceEntry) -> bool,
+    ) -> TileCatalog {
+        let mut catalog = self.get_catalog();
+        catalog.retain(|_, entry| predicate(entry));
+        catalog
+    }
+
+    /// Get the catalog entries of the sources with the given content type, e.g. `image/png`
+    #[must_use]
+    pub fn get_catalog_by_content_type(&self, content_type: &str) -> TileCatalog {
+        self.get_catalog_filtered(|entry| entry.content_type == content_type)
+    }
+
+    /// Get the catalog entries of the sources with the given [tag](Source::tags), e.g. `basemap`
+    #[must_use]
+    pub fn get_catalog_with_tag(&self, tag: &str) -> TileCatalog {
+        self.get_catalog_filtered(|entry| entry.tags.iter().any(|t| t == tag))
+    }
+
+    /// Source IDs grouped by the content type of their tiles, e.g. `image/png`, sorted by ID.
+    #[must_use]
+    pub fn catalog_by_format(&self) -> BTreeMap<String, Vec<String>> {
+        let mut groups = BTreeMap::<String, Vec<String>>::new();
+        // the catalog is sorted by ID
+        for (id, entry) in self.get_catalog() {
+            groups.entry(entry.content_type).or_default().push(id);
+        }
+        groups
+    }
+
+    /// Union of the bounds of all sources as `[west, south, east, north]`, e.g. to show everything
+    /// on a map. Sources without bounds are ignored, and `None` is returned if no source has bounds.
+    /// Bounds crossing the antimeridian (west > east) are treated as covering all longitudes.
+    #[must_use]
+    pub fn overall_bounds(&self) -> Option<[f64; 4]> {
+        self.sources
+            .values()
+            .filter_map(|src| src.as_source().get_tilejson().bounds)
+            .map(|mut b| {
+                if b.left > b.right {
+                    (b.left, b.right) = (-180.0, 180.0);
+                }
+                b
+            })
+            .reduce(|a, b| a + b)
+            .map(|b| [b.left, b.bottom, b.right, b.top])
+    }
+
+    /// Check that the `TileJSON` of every source is consistent, e.g. to report problems on startup
+    /// that would otherwise cause unexpected behavior later, like a `minzoom` above the `maxzoom`
+    /// or bounds outside of the world. Returns the problems of each invalid source, sorted by ID.
+    #[must_use]
+    pub fn validate_all(&self) -> Vec<(String, Vec<String>)> {
+        self.sources
+            .iter()
+            .sorted_by(|a, b| a.0.cmp(b.0))
+            .filter_map(|(id, src)| {
+                let problems = tilejson_problems(src.as_source().get_tilejson());
+                (!problems.is_empty()).then(|| (id.clone(), problems))
+            })
+            .collect()
+    }
+
+    /// Check the health of every source, e.g. whether its database or file is still reachable.
+    pub async fn health_check_all(&self) -> BTreeMap<String, MartinResult<()>> {
+        let ids = self.sources.keys().cloned();
+        let checks = self
+            .sources
+            .values()
+            .map(|src| src.as_source().health_check());
+        let results = join_all(checks).await;
+        ids.zip(results).collect()
+    }
+
+    /// Check if a source exists, using the same lookup rules as [`TileSources::get_source`].
+    #[must_use]
+    pub fn contains(&self, id: &str) -> bool {
+        self.find(id).is_some()
+    }
+
+    /// Whether all sources of a possibly merged source like `a,b` [wrap](Source::wrap_x)
+    /// the `x` coordinate. Unknown sources do not wrap.
+    #[must_use]
+    pub fn wraps_x(&self, source_ids: &str) -> bool {
+        source_ids
+            .split(MERGE_DELIMITER)
+            .all(|id| self.get_source(id).is_ok_and(Source::wrap_x))
+    }
+
+    /// Get a source by its ID, an alias, or [`DEFAULT_SOURCE_ALIAS`].
+    /// Access is not checked, see [`TileSourcesOf::get_source_authorized`].
+    pub fn get_source(&self, id: &str) -> actix_web::Result<&dyn Source> {
+        if let Some(src) = self.find(id) {
+            return Ok(src.as_source());
+        }
+        if let Some(target) = self.aliases.get(id) {
+            return Err(DanglingAlias(id.to_string(), target.clone()).into());
+        }
+        match &self.default_id {
+            Some(default_id) if id == DEFAULT_SOURCE_ALIAS => {
+                Err(DefaultSourceNotFound(default_id.clone()).into())
+            }
+            _ => Err(ErrorNotFound(format!("Source {id} does not exist"))),
+        }
+    }
+
+    /// Same as [`TileSourcesOf::get_source`], but sources that [require authorization](Source::requires_auth)
+    /// are only returned if `authorize` returns `true` for their ID, e.g. to check the tenant of the request.
+    /// Without `authorize`, such sources are never returned. Fails with [`SourceError::Unauthorized`],
+    /// which is distinct from a missing source.
+    pub fn get_source_authorized(
+        &self,
+        id: &str,
+        authorize: Option<&dyn Fn(&str) -> bool>,
+    ) -> actix_web::Result<&dyn Source> {
+        let src = self.get_source(id)?;
+        if src.requires_auth() && !authorize.is_some_and(|authorize| authorize(src.get_id())) {
+            Err(Unauthorized(src.get_id().to_string()))?;
+        }
+        Ok(src)
+    }
+
+    /// Same as [`TileSourcesOf::get_source`], but returns an owned clone of the source,
+    /// e.g. to move it into a spawned task.
+    pub fn get_source_owned(&self, id: &str) -> actix_web::Result<TileInfoSource> {
+        Ok(self.get_source(id)?.clone_source())
+    }
+
+    /// Get a source as its concrete type, using the same lookup rules as [`TileSourcesOf::get_source`].
+    #[must_use]
+    pub fn get(&self, id: &str) -> Option<&S::Shared> {
+        self.find(id)
+    }
+
+    /// Get all sources whose ID starts with the given prefix, sorted by ID.
+    /// Unlike [`TileSources::get_source`], this only matches IDs exactly as they were configured.
+    #[must_use]
+    pub fn get_sources_by_prefix(&self, prefix: &str) -> Vec<&dyn Source> {
+        self.sources
+            .iter()
+            .filter(|(id, _)| id.starts_with(prefix))
+            .sorted_by(|a, b| a.0.cmp(b.0))
+            .map(|(_, src)| src.as_source())
+            .collect()
+    }
+
+    fn find(&self, id: &str) -> Option<&S::Shared> {
+        let src = self
+            .sources
+            .get(id)
+            .or_else(|| self.sources.get(self.aliases.get(id)?))
+            .or_else(|| {
+                let id = self.lowercase_ids.as_ref()?.get(&id.to_lowercase())?;
+                self.sources.get(id)
+            })
+            .or_else(|| {
+                if id == DEFAULT_SOURCE_ALIAS {
+                    self.sources.get(self.default_id.as_ref()?)
+                } else {
+                    None
+                }
+            })?;
+        Some(src.as_ref())
+    }
+
+    /// Get a list of sources, and the tile info for the merged sources.
+    /// Ensure that all sources have the same format. If the encodings differ,
+    /// the merged tile info is uncompressed, and each tile must be decoded before merging.
+    /// If zoom is specified, filter out sources that do not support it.
+    /// If none of them support it, the list is empty, which is not an error:
+    /// the returned tile info can still be used to respond, e.g. with [`empty_tile`].
+    /// The length of the returned list is the number of tiles fetched to respond to the request,
+    /// see [`TileSources::estimate_fetch_count`]. If only one source is left after filtering,
+    /// e.g. `a,b,c` at a zoom only `b` supports, the tile info is the one of that source,
+    /// so that its tiles do not need to be decoded and re-encoded as if they were merged.
+    pub fn get_sources(
+        &self,
+        source_ids: &str,
+        zoom: Option<u8>,
+    ) -> actix_web::Result<(Vec<&dyn Source>, bool, TileInfo)> {
+        let ids = source_ids.split(MERGE_DELIMITER).collect::<Vec<_>>();
+        self.get_sources_from_ids(&ids, zoom)
+    }
+
+    /// Same as [`TileSources::get_sources`], but the zoom is only validated if `enforce_zoom` is set.
+    /// Otherwise, sources are included even at zooms outside of their range, e.g. to debug a source
+    /// whose data goes beyond its declared zooms. Note that the backend of such a source may fail
+    /// or respond with 404 Not Found for tiles it does not have.
+    pub fn get_sources_with_zoom_check(
+        &self,
+        source_ids: &str,
+        zoom: Option<u8>,
+        enforce_zoom: bool,
+    ) -> actix_web::Result<(Vec<&dyn Source>, bool, TileInfo)> {
+        self.get_sources(source_ids, zoom.filter(|_| enforce_zoom))
+    }
+
+    /// Same as [`TileSources::get_sources`], but checks the access to every requested source
+    /// like [`TileSourcesOf::get_source_authorized`] before resolving them.
+    pub fn get_sources_authorized(
+        &self,
+        source_ids: &str,
+        zoom: Option<u8>,
+        authorize: Option<&dyn Fn(&str) -> bool>,
+    ) -> actix_web::Result<(Vec<&dyn Source>, bool, TileInfo)> {
+        self.check_authorized(source_ids, authorize)?;
+        self.get_sources(source_ids, zoom)
+    }
+
+    /// Check the access to every source of a possibly merged source like `a,b`,
+    /// see [`TileSourcesOf::get_source_authorized`].
+    pub fn check_authorized(
+        &self,
+        source_ids: &str,
+        authorize: Option<&dyn Fn(&str) -> bool>,
+    ) -> actix_web::Result<()> {
+        for id in source_ids.split(MERGE_DELIMITER) {
+            self.get_source_authorized(id, authorize)?;
+        }
+        Ok(())
+    }
+
+    /// Get the tile info of a possibly merged source like `a,b`, e.g. to respond to `HEAD` requests.
+    /// Like [`TileSources::get_sources`], this fails for unknown or incompatible sources,
+    /// but no sources are filtered by zoom, and no tiles are fetched.
+    pub fn resolve_merged_info(&self, source_ids: &str) -> actix_web::Result<TileInfo> {
+        Ok(self.get_sources(source_ids, None)?.2)
+    }
+
+    /// Same as [`TileSources::get_sources`], but for source IDs that are already split.
+    pub fn get_sources_from_ids(
+        &self,
+        ids: &[&str],
+        zoom: Option<u8>,
+    ) -> actix_web::Result<(Vec<&dyn Source>, bool, TileInfo)> {
+        let merged = self.resolve_sources(ids, zoom)?;
+        Ok((merged.sources, merged.use_url_query, merged.info))
+    }
+
+    /// Same as [`TileSources::get_sources`], but returns named fields, including the IDs
+    /// of the sources that were skipped because they do not support the requested zoom,
+    /// and the IDs of the included sources, e.g. to report which sources were merged.
+    pub fn get_sources_detailed(
+        &self,
+        source_ids: &str,
+        zoom: Option<u8>,
+    ) -> actix_web::Result<MergedSources<'_>> {
+        let ids = source_ids.split(MERGE_DELIMITER).collect::<Vec<_>>();
+        self.resolve_sources(&ids, zoom)
+    }
+
+    /// Keep only the sources that have a tile at the given coordinate, see [`Source::has_tile`],
+    /// e.g. to filter the result of [`TileSources::get_sources`] before merging.
+    /// The URL query is only passed to sources that support it.
+    pub async fn sources_with_tile<'a>(
+        sources: &[&'a dyn Source],
+        xyz: TileCoord,
+        url_query: Option<&UrlQuery>,
+    ) -> MartinResult<Vec<&'a dyn Source>> {
+        let checks = sources.iter().map(|src| {
+            let query = url_query.filter(|_| src.support_url_query());
+            src.has_tile(Self::source_coord(*src, xyz), query)
+        });
+        let found = try_join_all(checks).await?;
+        Ok(sources
+            .iter()
+            .zip(found)
+            .filter_map(|(src, found)| found.then_some(*src))
+            .collect())
+    }
+
+    /// Number of tiles that will be fetched from the sources to respond to a single tile request,
+    /// e.g. to charge merged requests proportionally when rate limiting.
+    /// Sources that do not support the zoom are not counted. Fails like [`TileSources::get_sources`].
+    pub fn estimate_fetch_count(
+        &self,
+        source_ids: &str,
+        zoom: Option<u8>,
+    ) -> actix_web::Result<usize> {
+        Ok(self.get_sources(source_ids, zoom)?.0.len())
+    }
+
+    /// Get a tile of a single source without the merging logic of [`TileSources::get_sources`],
+    /// for the common case of a request without a [`MERGE_DELIMITER`].
+    /// Returns `None` if the source does not support the zoom or does not cover the tile,
+    /// otherwise the tile together with the tile info declared by the source.
+    /// Like in merged requests, the URL query is only passed to sources that support it.
+    pub async fn get_single_source_tile(
+        &self,
+        id: &str,
+        xyz: TileCoord,
+        query: Option<&UrlQuery>,
+        zoom: Option<u8>,
+    ) -> actix_web::Result<Option<(Tile, TileInfo)>> {
+        if id.contains(MERGE_DELIMITER) {
+            return Err(ErrorBadRequest(format!(
+                "{id} is a composite source, use get_sources instead"
+            )));
+        }
+        let src = self.get_source(id)?;
+        if zoom.is_some_and(|zoom| !Self::check_zoom(src, id, zoom))
+            || !src.covers(Self::source_coord(src, xyz))
+        {
+            return Ok(None);
+        }
+        let query = query.filter(|_| src.support_url_query());
+        Self::check_query(&[src], query)?;
+        let tile = Self::get_source_tile(src, xyz, query)
+            .await
+            .map_err(map_tile_error)?;
+        Ok(Some((tile, src.get_tile_info())))
+    }
+
+    /// Translate a requested tile coordinate into the addressing scheme of the source,
+    /// i.e. flip its Y axis if the source [uses TMS](Source::uses_tms).
+    #[must_use]
+    pub fn source_coord(src: &dyn Source, xyz: TileCoord) -> TileCoord {
+        if src.uses_tms() {
+            xyz.flip_y()
+        } else {
+            xyz
+        }
+    }
+
+    /// Get a tile of one source, annotating a failure with the source ID and the tile coordinate,
+    /// so that errors of all backends can be correlated in the logs, see [`MartinError::TileError`].
+    /// The coordinate is translated with [`TileSources::source_coord`], but reported as requested.
+    pub async fn get_source_tile(
+        src: &dyn Source,
+        xyz: TileCoord,
+        url_query: Option<&UrlQuery>,
+    ) -> MartinResult<Tile> {
+        src.get_tile_with_info(Self::source_coord(src, xyz), url_query)
+            .await
+            .map_err(|e| MartinError::TileError(src.get_id().to_string(), xyz, Box::new(e)))
+    }
+
+    /// Get the `TileJSON` of a composite source like `a,b`, combining the bounds, zoom range,
+    /// vector layers, and attributions of its sources, see [`merge_tilejson`].
+    /// Fails for incompatible sources just like [`TileSources::get_sources`].
+    /// The `tiles` list is left empty, because the URL depends on how the source is served.
+    pub fn get_composite_tilejson(&self, source_ids: &str) -> actix_web::Result<TileJSON> {
+        let (sources, ..) = self.get_sources(source_ids, None)?;
+        let mut tilejson = merge_tilejson(&sources, String::new());
+        tilejson.tiles.clear();
+        Ok(tilejson)
+    }
+
+    fn resolve_sources(
+        &self,
+        ids: &[&str],
+        zoom: Option<u8>,
+    ) -> actix_web::Result<MergedSources<'_>> {
+        let max = self.max_merge_sources.unwrap_or(MAX_MERGE_SOURCES_DEFAULT);
+        let count = ids.len();
+        if count > max {
+            Err(TooManySources { count, max })?;
+        }
+
+        let mut sources = Vec::new();
+        let mut included = Vec::new();
+        let mut skipped = Vec::new();
+        let mut info: Option<TileInfo> = None;
+        // formats that all sources can produce
+        let mut formats: Vec<Format> = Vec::new();
+        let mut use_url_query = false;
+        let mut crs = None;
+
+        for &id in ids {
+            let src = self.get_source(id)?;
+            let src_inf = src.get_tile_info();
+            use_url_query |= src.support_url_query();
+
+            let first_crs = *crs.get_or_insert(src.crs());
+            if first_crs != src.crs() {
+                Err(CrsMismatch {
+                    left_id: ids[0].to_string(),
+                    left: first_crs.to_string(),
+                    right_id: id.to_string(),
+                    right: src.crs().to_string(),
+                })?;
+            }
+
+            let src_formats = src.supported_formats();
+            if info.is_none() {
+                formats = src_formats;
+            } else {
+                formats.retain(|f| src_formats.contains(f));
+            }
+
+            // make sure all sources have the same format, or can all produce a common one.
+            // Encoding differences are reconciled by decoding the tiles before merging.
+            match info {
+                Some(inf) if inf == src_inf => {}
+                Some(inf) if inf.format == src_inf.format => {
+                    info = Some(inf.encoding(Self::merge_encoding(inf.encoding, src_inf.encoding)));
+                }
+                Some(inf) if formats.contains(&inf.format) => {}
+                Some(_) if !formats.is_empty() => info = Some(formats[0].into()),
+                Some(inf) => Err(MergeFormatMismatch {
+                    left_id: ids[0].to_string(),
+                    left: inf,
+                    right_id: id.to_string(),
+                    right: src_inf,
+                })?,
+                None => info = Some(src_inf),
+            }
+
+            // TODO: Use chained-if-let once available
+            if match zoom {
+                Some(zoom) if Self::check_zoom(src, id, zoom) => true,
+                None => true,
+                _ => false,
+            } {
+                sources.push(src);
+                included.push(id.to_string());
+            } else {
+                skipped.push(id.to_string());
+            }
+        }
+
+        // format is guaranteed to be Some() here
+        let mut info = info.unwrap();
+        if let [src] = sources[..] {
+            if src.get_tile_info().format == info.format {
+                info = src.get_tile_info();
+            }
+        }
+        Ok(MergedSources {
+            sources,
+            use_url_query,
+            info,
+            skipped,
+            included,
+        })
+    }
+
+    /// Pick the encoding that tiles of two sources with the same format can be merged in.
+    /// Differently encoded tiles are merged uncompressed, and re-encoded for the response.
+    #[must_use]
+    pub fn merge_encoding(left: Encoding, right: Encoding) -> Encoding {
+        if left == right {
+            left
+        } else {
+            Encoding::Uncompressed
+        }
+    }
+
+    /// Check that the URL query has all the [required keys](Source::required_query_keys) of the sources,
+    /// or fail with [`SourceError::MissingQueryKey`] for the first missing one.
+    pub fn check_query(
+        sources: &[&dyn Source],
+        query: Option<&UrlQuery>,
+    ) -> Result<(), SourceError> {
+        for src in sources {
+            for key in src.required_query_keys() {
+                if !query.is_some_and(|q| q.contains_key(*key)) {
+                    return Err(MissingQueryKey(
+                        src.get_id().to_string(),
+                        (*key).to_string(),
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn check_zoom(src: &dyn Source, id: &str, zoom: u8) -> bool {
+        Self::check_zoom_detailed(src, id, zoom) == ZoomCheck::Ok
+    }
+
+    /// Same as [`TileSources::check_zoom`], but returns the reason why the zoom is not valid.
+    /// Sources overriding [`Source::is_valid_zoom`] can reject zooms within their bounds.
+    pub fn check_zoom_detailed(src: &dyn Source, id: &str, zoom: u8) -> ZoomCheck {
+        let mut check = src.zoom_check(zoom);
+        if check == ZoomCheck::Ok && !src.is_valid_zoom(zoom) {
+            check = ZoomCheck::NotAvailable;
+        }
+        match check {
+            ZoomCheck::Ok => {}
+            ZoomCheck::BelowMin(min) => {
+                debug!("Zoom {zoom} is below the minzoom {min} of source {id}");
+            }
+            ZoomCheck::AboveMax(max) => {
+                debug!("Zoom {zoom} is above the maxzoom {max} of source {id}");
+            }
+            ZoomCheck::NotAvailable => {
+                debug!("Zoom {zoom} is not available in source {id}");
+            }
+        }
+        check
+    }
+}
+
+#[async_trait]
+pub trait Source: Send + Sync + Debug {
+    fn get_id(&self) -> &str;
+
+    fn get_tilejson(&self) -> &TileJSON;
+
+    fn get_tile_info(&self) -> TileInfo;
+
+    fn clone_source(&self) -> TileInfoSource;
+
+    fn support_url_query(&self) -> bool {
+        false
+    }
+
+    /// URL query parameters that must be present to request tiles of this source,
+    /// e.g. a time filter, to avoid silently serving unfiltered data.
+    /// Sources with required keys must also [support the URL query](Source::support_url_query).
+    fn required_query_keys(&self) -> &[&str] {
+        &[]
+    }
+
+    /// Whether tiles of this source should always be served uncompressed, regardless of
+    /// how they are stored and of the client's `Accept-Encoding`, e.g. for clients that cannot
+    /// decompress tiles. Such tiles are decoded with [`Source::get_tile_decoded`].
+    fn prefers_uncompressed(&self) -> bool {
+        false
+    }
+
+    /// Whether the tiles are addressed using the TMS scheme (Y axis inverted).
+    /// If so, the requested coordinates are flipped with [`TileCoord::flip_y`]
+    /// before they are passed to the source, see [`TileSources::source_coord`].
+    fn uses_tms(&self) -> bool {
+        false
+    }
+
+    /// Coordinate reference system of the tiles as an EPSG code, e.g. `EPSG:4326`.
+    /// Sources with different coordinate reference systems cannot be merged.
+    fn crs(&self) -> &str {
+        DEFAULT_CRS
+    }
+
+    /// Whether access to this source must be authorized per request,
+    /// see [`TileSourcesOf::get_source_authorized`].
+    fn requires_auth(&self) -> bool {
+        false
+    }
+
+    /// Whether requests with an `x` coordinate beyond the antimeridian, i.e. `x >= 2^z`,
+    /// are wrapped around the globe with [`TileCoord::wrapped`] instead of being rejected.
+    fn wrap_x(&self) -> bool {
+        false
+    }
+
+    /// How to respond to requests for tiles this source does not have, e.g. to let map
+    /// clients render a transparent raster tile instead of an error.
+    /// Composite requests respond like their first source in the requested order.
+    fn miss_behavior(&self) -> MissBehavior {
+        MissBehavior::default()
+    }
+
+    /// Labels to group sources in the catalog, e.g. `basemap` or `overlay`, usually set
+    /// in the source configuration. See [`TileSourcesOf::get_catalog_with_tag`].
+    fn tags(&self) -> &[String] {
+        &[]
+    }
+
+    /// Width and height of the tiles in pixels, e.g. 512 for high resolution raster tiles.
+    /// Clients need it to scale the tiles correctly, e.g. as the `tileSize` of a map source.
+    fn tile_size(&self) -> u32 {
+        DEFAULT_TILE_SIZE
+    }
+
+    async fn get_tile(
+        &self,
+        xyz: TileCoord,
+        url_query: Option<&UrlQuery>,
+    ) -> MartinResult<TileData>;
+
+    /// HTTP headers describing the tiles of this source, i.e. `Content-Type` and `Content-Encoding`.
+    /// Responses for merged or re-encoded tiles should use [`Tile::response_headers`] instead.
+    fn response_headers(&self) -> Vec<(&'static str, String)> {
+        response_headers(self.get_tile_info())
+    }
+
+    /// Key identifying the URL query when caching tiles, see [`normalize_query`].
+    /// Sources that ignore the URL query return an empty key.
+    fn url_query_key(&self, query: &UrlQuery) -> String {
+        if self.support_url_query() {
+            normalize_query(query)
+        } else {
+            String::new()
+        }
+    }
+
+    /// Suggested file name of a tile, e.g. `src_1_2_3.png` to download it,
+    /// with the extension from [`Format::extension`].
+    fn tile_filename(&self, xyz: TileCoord) -> String {
+        let TileCoord { z, x, y } = xyz;
+        let ext = self.get_tile_info().format.extension();
+        format!("{}_{z}_{x}_{y}.{ext}", self.get_id())
+    }
+
+    /// Canonical key of a tile for external caches, e.g. `src/1/2/3?a=1&b=2`,
+    /// with the query part from [`Source::url_query_key`] omitted if it is empty.
+    fn tile_cache_key(&self, xyz: TileCoord, url_query: Option<&UrlQuery>) -> String {
+        let id = self.get_id();
+        match url_query.map(|q| self.url_query_key(q)) {
+            Some(key) if !key.is_empty() => format!("{id}/{xyz:#}?{key}"),
+            _ => format!("{id}/{xyz:#}"),
+        }
+    }
+
+    /// How long clients and proxies may cache the tiles of this source, in seconds,
+    /// e.g. a long time for static data. If `None`, no `Cache-Control` header is sent.
+    fn cache_control_max_age(&self) -> Option<u32> {
+        None
+    }
+
+    /// When the data of this source last changed, e.g. the modification time of its file.
+    /// Used for the `Last-Modified` header and conditional requests. `None` if unknown,
+    /// e.g. for database sources whose data may change at any time.
+    fn last_modified(&self) -> Option<SystemTime> {
+        None
+    }
+
+    /// Check if the backend of this source, e.g. a database or a file, is still reachable.
+    async fn health_check(&self) -> MartinResult<()> {
+        Ok(())
+    }
+
+    /// Get the tile data together with its actual tile info.
+    /// Sources that may return tiles encoded differently than reported by
+    /// [`Source::get_tile_info`] should override this to report the per-tile info.
+    async fn get_tile_with_info(
+        &self,
+        xyz: TileCoord,
+        url_query: Option<&UrlQuery>,
+    ) -> MartinResult<Tile> {
+        let data = self.get_tile(xyz, url_query).await?;
+        Ok(Tile::new(data, self.get_tile_info()))
+    }
+
+    /// Get the tile, or `None` if the source has no tile at these coordinates.
+    /// This lets callers tell a missing tile apart from a failure.
+    /// By default, an empty tile returned by [`Source::get_tile_with_info`] is treated as missing.
+    async fn get_tile_opt(
+        &self,
+        xyz: TileCoord,
+        url_query: Option<&UrlQuery>,
+    ) -> MartinResult<Option<Tile>> {
+        let tile = self.get_tile_with_info(xyz, url_query).await?;
+        Ok((!tile.data.is_empty()).then_some(tile))
+    }
+
+    /// Get the tile with any gzip or brotli encoding removed, e.g. for clients that
+    /// do not accept the stored encoding. Also returns the tile info as it was stored.
+    async fn get_tile_decoded(
+        &self,
+        xyz: TileCoord,
+        url_query: Option<&UrlQuery>,
+    ) -> MartinResult<(Tile, TileInfo)> {
+        let tile = self.get_tile_with_info(xyz, url_query).await?;
+        let info = tile.info;
+        Ok((tile.decode()?, info))
+    }
+
+    /// Whether [`Source::get_tile_range`] can read a part of a tile without reading all of it.
+    fn supports_range(&self) -> bool {
+        false
+    }
+
+    /// Get a byte range of the tile data, e.g. to stream large raster tiles.
+    /// The end of the range is limited to the size of the tile.
+    /// By default, the whole tile is read, and then sliced.
+    async fn get_tile_range(
+        &self,
+        xyz: TileCoord,
+        url_query: Option<&UrlQuery>,
+        range: Range<usize>,
+    ) -> MartinResult<Tile> {
+        let tile = self.get_tile_with_info(xyz, url_query).await?;
+        let len = tile.data.len();
+        let Some(data) = tile.data.get(range.start..range.end.min(len)) else {
+            return Err(InvalidRange(range, len).into());
+        };
+        Ok(Tile::new(data.to_vec(), tile.info))
+    }
+
+    /// Stream all tiles of this source, e.g. to copy them to another storage.
+    /// The order of the tiles is unspecified. Sources that cannot enumerate
+    /// their tiles, e.g. dynamically generated ones, return `None`.
+    fn tile_stream(&self) -> Option<BoxStream<'_, MartinResult<(TileCoord, Tile)>>> {
+        None
+    }
+
+    /// Whether the source has a non-empty tile at the given coordinate, e.g. to skip sources
+    /// without data before merging. By default, the tile is fetched, so sources that can
+    /// check it more cheaply, e.g. with an index lookup, should override this.
+    async fn has_tile(&self, xyz: TileCoord, url_query: Option<&UrlQuery>) -> MartinResult<bool> {
+        if !self.covers(xyz) {
+            return Ok(false);
+        }
+        Ok(!self.get_tile(xyz, url_query).await?.is_empty())
+    }
+
+    /// Get multiple tiles at once, returning `None` for each missing tile.
+    /// Sources that can fetch many tiles with a single query should override this.
+    async fn get_tiles_batch(
+        &self,
+        coords: &[TileCoord],
+        url_query: Option<&UrlQuery>,
+    ) -> MartinResult<Vec<Option<Tile>>> {
+        let mut tiles = Vec::with_capacity(coords.len());
+        for xyz in coords {
+            tiles.push(self.get_tile_opt(*xyz, url_query).await?);
+        }
+        Ok(tiles)
+    }
+
+    /// Fetch all tiles within `bounds`, i.e. `[min_lng, min_lat, max_lng, max_lat]`, at the given
+    /// zooms, e.g. to fill a [`CachedSource`] on startup. Invalid zooms and tiles this source
+    /// does not cover are skipped. Returns the number of fetched tiles.
+    async fn prewarm(
+        &self,
+        bounds: [f64; 4],
+        zooms: Range<u8>,
+        url_query: Option<&UrlQuery>,
+    ) -> MartinResult<usize> {
+        let mut count = 0;
+        for zoom in zooms.filter(|zoom| self.is_valid_zoom(*zoom)) {
+            for xyz in TileCoord::tiles_in_bbox(bounds, zoom) {
+                if self.covers(xyz) {
+                    self.get_tile(xyz, url_query).await?;
+                    count += 1;
+                }
+            }
+        }
+        Ok(count)
+    }
+
+    /// Get a stable entity tag of the tile content, e.g. a hash stored alongside the tile.
+    /// Sources without a cheap way to identify tile content return `None`,
+    /// in which case no `ETag` header is sent.
+    async fn get_tile_etag(
+        &self,
+        _xyz: TileCoord,
+        _url_query: Option<&UrlQuery>,
+    ) -> MartinResult<Option<String>> {
+        Ok(None)
+    }
+
+    /// Tile formats this source can produce with [`Source::get_tile_as`].
+    /// By default, only the native format is supported.
+    fn supported_formats(&self) -> Vec<Format> {
+        vec![self.get_tile_info().format]
+    }
+
+    /// Get a tile in the given format. Sources that can transcode their tiles,
+    /// e.g. from WEBP to PNG, should override this and [`Source::supported_formats`].
+    async fn get_tile_as(
+        &self,
+        xyz: TileCoord,
+        url_query: Option<&UrlQuery>,
+        format: Format,
+    ) -> MartinResult<Tile> {
+        if format == self.get_tile_info().format {
+            self.get_tile_with_info(xyz, url_query).await
+        } else {
+            Err(UnsupportedFormat(self.get_id().to_string(), format))?
+        }
+    }
+
+    /// Same as [`Source::get_tile_as`], but with the output quality of raster tiles, e.g. parsed
+    /// from the URL query with [`TranscodeOptions::from_query`]. By default, PNG tiles are re-encoded
+    /// with the requested compression level, and requests with a quality fail with
+    /// [`SourceError::UnsupportedQuality`], because lossy formats cannot be encoded.
+    /// Sources that can encode lossy formats like JPEG should override this.
+    /// Non-raster sources fail with [`SourceError::UnsupportedFormat`].
+    async fn transcode_tile(
+        &self,
+        xyz: TileCoord,
+        url_query: Option<&UrlQuery>,
+        format: Format,
+        options: TranscodeOptions,
+    ) -> MartinResult<Tile> {
+        if !is_raster(self.get_tile_info().format) {
+            Err(UnsupportedFormat(self.get_id().to_string(), format))?;
+        }
+        if options.quality.is_some() {
+            Err(UnsupportedQuality(self.get_id().to_string(), format))?;
+        }
+        let tile = self.get_tile_as(xyz, url_query, format).await?;
+        let Some(level) = options.compression else {
+            return Ok(tile);
+        };
+        if tile.info.format != Format::Png || tile.data.is_empty() {
+            return Ok(tile);
+        }
+        let (width, height, rgba) = decode_rgba(&tile.data)
+            .map_err(|e| TranscodeError(self.get_id().to_string(), xyz, e))?;
+        let data = encode_rgba(width, height, &rgba, png_compression(level))
+            .map_err(|e| TranscodeError(self.get_id().to_string(), xyz, e.to_string()))?;
+        Ok(Tile::new(data, tile.info))
+    }
+
+    /// Whether this source can produce tiles in the given format with [`Source::get_tile_as`],
+    /// e.g. to serve raster tiles as WebP only to clients that accept it.
+    fn can_encode(&self, format: Format) -> bool {
+        self.supported_formats().contains(&format)
+    }
+
+    /// Encodings this source can serve its tiles with. By default, only the stored encoding.
+    /// Sources that can transcode their tiles should list every encoding they can produce,
+    /// so that responses vary by the client's `Accept-Encoding`.
+    fn available_encodings(&self) -> Vec<Encoding> {
+        vec![self.get_tile_info().encoding]
+    }
+
+    /// Approximate number of tiles in this source, if it can be cheaply determined.
+    fn approximate_tile_count(&self) -> Option<u64> {
+        None
+    }
+
+    /// Approximate size of all tiles in this source in bytes, if it can be cheaply determined.
+    fn approximate_total_bytes(&self) -> Option<u64> {
+        None
+    }
+
+    /// Highest zoom this source can serve by cutting out parts of the tiles at its maxzoom,
+    /// see [`OverzoomSource`]. By default, sources do not support overzooming.
+    fn max_overzoom(&self) -> Option<u8> {
+        None
+    }
+
+    /// Explicit list of zoom levels that have tiles, for sources with non-contiguous zooms.
+    /// If `None`, the valid zoom range is determined by the `TileJSON` min and max zoom.
+    fn available_zooms(&self) -> Option<&[u8]> {
+        None
+    }
+
+    /// Whether this source may have a tile at the given coordinate. Sources with irregular
+    /// coverage, e.g. a country outline, can override this to skip requests outside of their data.
+    /// Sources that do not cover a tile are not queried when merging tiles.
+    fn covers(&self, _xyz: TileCoord) -> bool {
+        true
+    }
+
+    /// Tiles that are likely to be requested soon after the given one, e.g. its
+    /// [children](TileCoord::children) or [neighbors](TileCoord::neighbors).
+    /// [`CachedSource`] fetches them in the background. By default, nothing is prefetched.
+    fn prefetch_hints(&self, _xyz: TileCoord) -> Vec<TileCoord> {
+        Vec::new()
+    }
+
+    /// Whether the source has tiles at this zoom. By default, this uses [`Source::zoom_check`].
+    /// Sources with a more accurate index, e.g. the zooms present in an `MBTiles` file,
+    /// can override it to reject zooms within the `TileJSON` range.
+    fn is_valid_zoom(&self, zoom: u8) -> bool {
+        self.zoom_check(zoom) == ZoomCheck::Ok
+    }
+
+    /// Check if the zoom is valid for this source, and if not, why.
+    fn zoom_check(&self, zoom: u8) -> ZoomCheck {
+        if let Some(zooms) = self.available_zooms() {
+            return if zooms.contains(&zoom) {
+                ZoomCheck::Ok
+            } else {
+                ZoomCheck::NotAvailable
+            };
+        }
+        let tj = self.get_tilejson();
+        let maxzoom = tj.maxzoom.map(|max| {
+            self.max_overzoom()
+                .map_or(max, |overzoom| overzoom.max(max))
+        });
+        match (tj.minzoom, maxzoom) {
+            (Some(min), _) if zoom < min => ZoomCheck::BelowMin(min),
+            (_, Some(max)) if zoom > max => ZoomCheck::AboveMax(max),
+            _ => ZoomCheck::Ok,
+        }
+    }
+
+    /// The content type and encoding are the ones of the [response headers](Source::response_headers)
+    fn get_catalog_entry(&self) -> CatalogSourceEntry {
+        let id = self.get_id();
+        let tilejson = self.get_tilejson();
+        let mut content_type = String::new();
+        let mut content_encoding = None;
+        for (name, value) in self.response_headers() {
+            match name {
+                "Content-Type" => content_type = value,
+                "Content-Encoding" => content_encoding = Some(value),
+                _ => {}
+            }
+        }
+        CatalogSourceEntry {
+            content_type,
+            content_encoding,
+            name: tilejson.name.as_ref().filter(|v| *v != id).cloned(),
+            description: tilejson.description.clone(),
+            attribution: tilejson.attribution.clone(),
+            bounds: tilejson.bounds.map(|b| [b.left, b.bottom, b.right, b.top]),
+            minzoom: tilejson.minzoom,
+            maxzoom: tilejson.maxzoom,
+            tile_size: Some(self.tile_size()).filter(|size| *size != DEFAULT_TILE_SIZE),
+            crs: Some(self.crs())
+                .filter(|crs| *crs != DEFAULT_CRS)
+                .map(ToString::to_string),
+            available_encodings: Some(self.available_encodings())
+                .filter(|encodings| encodings.len() > 1)
+                .map(|encodings| {
+                    encodings
+                        .iter()
+                        .map(|enc| enc.content_encoding().unwrap_or("identity").to_string())
+                        .collect()
+                }),
+            tile_count: self.approximate_tile_count(),
+            total_bytes: self.approximate_total_bytes(),
+            tags: self.tags().to_vec(),
+            other: tilejson
+                .other
+                .iter()
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect(),
+        }
+    }
+}
+
+impl Clone for TileInfoSource {
+    fn clone(&self) -> Self {
+        self.clone_source()
+    }
+}
+
+#[serde_with::skip_serializing_none]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct CatalogSourceEntry {
+    pub content_type: String,
+    pub content_encoding: Option<String>,
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub attribution: Option<String>,
+    /// Geographic extent of the source as `[min_lng, min_lat, max_lng, max_lat]`
+    pub bounds: Option<[f64; 4]>,
+    pub minzoom: Option<u8>,
+    pub maxzoom: Option<u8>,
+    /// Size of the tiles in pixels, omitted if it is the [`DEFAULT_TILE_SIZE`]
+    pub tile_size: Option<u32>,
+    /// Coordinate reference system of the tiles, omitted if it is the [`DEFAULT_CRS`]
+    pub crs: Option<String>,
+    /// Content encodings the tiles can be served with, e.g. `["gzip", "identity"]`,
+    /// omitted if the source only serves its stored encoding
+    pub available_encodings: Option<Vec<String>>,
+    pub tile_count: Option<u64>,
+    pub total_bytes: Option<u64>,
+    /// Labels of the source, see [`Source::tags`]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Additional non-standard `TileJSON` fields of the source, e.g. `license_url`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub other: BTreeMap<String, serde_json::Value>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Tile {
+    pub data: TileData,
+    pub info: TileInfo,
+}
+
+impl Tile {
+    #[must_use]
+    pub fn new(data: TileData, info: TileInfo) -> Self {
+        Self { data, info }
+    }
+
+    /// Remove the gzip or brotli encoding of the tile data, if any.
+    /// Empty tiles are returned as is.
+    pub fn decode(self) -> Result<Self, SourceError> {
+        let info = self.info;
+        if self.data.is_empty() || !info.encoding.is_encoded() {
+            return Ok(self);
+        }
+        let data = match info.encoding {
+            Encoding::Gzip => decode_gzip(&self.data),
+            Encoding::Brotli => decode_brotli(&self.data),
+            _ => Err(UnsupportedEncoding(info))?,
+        }
+        .map_err(|e| DecodingError(e, info))?;
+        Ok(Self::new(data, info.encoding(Encoding::Uncompressed)))
+    }
+
+    /// Change the encoding of the tile data, e.g. from gzip to brotli. Empty tiles, tiles
+    /// that already have the `target` encoding, and internally compressed tiles like PNG
+    /// are returned as is. Only `gzip`, `br`, and uncompressed targets are supported.
+    pub fn encode(self, target: Encoding) -> Result<Self, SourceError> {
+        let info = self.info;
+        if self.data.is_empty() || info.encoding == target || info.encoding == Encoding::Internal {
+            return Ok(self);
+        }
+        let data = self.decode()?.data;
+        let data = match target {
+            Encoding::Uncompressed => data,
+            Encoding::Gzip => encode_gzip(&data).map_err(|e| EncodingError(e, target))?,
+            Encoding::Brotli => encode_brotli(&data).map_err(|e| EncodingError(e, target))?,
+            _ => return Err(UnsupportedEncoding(info.encoding(target))),
+        };
+        Ok(Self::new(data, info.encoding(target)))
+    }
+
+    /// HTTP headers describing this tile, using its actual format and encoding
+    #[must_use]
+    pub fn response_headers(&self) -> Vec<(&'static str, String)> {
+        response_headers(self.info)
+    }
+}
+
+/// `Content-Type` header, followed by `Content-Encoding` if the tiles are encoded
+fn response_headers(info: TileInfo) -> Vec<(&'static str, String)> {
+    let mut headers = vec![("Content-Type", info.format.content_type().to_string())];
+    if let Some(encoding) = info.encoding.content_encoding() {
+        headers.push(("Content-Encoding", encoding.to_string()));
+    }
+    headers
+}
+
+/// A fully transparent 1x1 PNG image
+const EMPTY_PNG: &[u8] = &[
+    0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48, 0x44, 0x52,
+    0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x08, 0x06, 0x00, 0x00, 0x00, 0x1f, 0x15, 0xc4,
+    0x89, 0x00, 0x00, 0x00, 0x0b, 0x49, 0x44, 0x41, 0x54, 0x78, 0xda, 0x63, 0x60, 0x00, 0x02, 0x00,
+    0x00, 0x05, 0x00, 0x01, 0xe9, 0xfa, 0xdc, 0xd8, 0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4e, 0x44,
+    0xae, 0x42, 0x60, 0x82,
+];
+
+/// Merge MVT tiles into a single tile with the `target` encoding, e.g. to compress
+/// the merged tile once instead of serving it uncompressed. Tiles may have mixed encodings,
+/// and are decoded before being concatenated. Empty tiles are skipped, and if all of them are,
+/// the result is an empty uncompressed tile. See [`Tile::encode`] for the supported targets.
+pub fn encode_merged(tiles: Vec<Tile>, target: Encoding) -> Result<Tile, SourceError> {
+    let mut data = Vec::new();
+    for tile in tiles {
+        if tile.info.format != Format::Mvt {
+            return Err(CannotConcatenate(tile.info.format));
+        }
+        data.extend(tile.decode()?.data);
+    }
+    Tile::new(data, Format::Mvt.into()).encode(target)
+}
+
+/// A minimal valid tile of the given format, e.g. to respond to requests that have no tile data.
+/// MVT tiles without layers have no data, and PNG tiles are a transparent 1x1 image.
+/// Other formats have no data. The tile is never encoded, regardless of `info.encoding`.
+#[must_use]
+pub fn empty_tile(info: TileInfo) -> Tile {
+    let data = match info.format {
+        Format::Png => EMPTY_PNG.to_vec(),
+        _ => Vec::new(),
+    };
+    Tile::new(data, info.format.into())
+}
+
+/// Decode a PNG or JPEG image into 8-bit RGBA pixels, returning its width and height too
+fn decode_rgba(data: &[u8]) -> Result<(u32, u32, Vec<u8>), String> {
+    match TileInfo::detect(data).map(|info| info.format) {
+        Some(Format::Png) => decode_png_rgba(data).map_err(|e| e.to_string()),
+        Some(Format::Jpeg) => decode_jpeg_rgba(data),
+        Some(format) => Err(format!("{format} images cannot be decoded")),
+        None => Err("unknown image format".to_string()),
+    }
+}
+
+/// Decode a PNG image into 8-bit RGBA pixels, returning its width and height too
+fn decode_png_rgba(data: &[u8]) -> Result<(u32, u32, Vec<u8>), png::DecodingError> {
+    let mut decoder = png::Decoder::new(data);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info()?;
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let frame = reader.next_frame(&mut buf)?;
+    buf.truncate(frame.buffer_size());
+    let rgba = match frame.color_type {
+        png::ColorType::Rgba => buf,
+        png::ColorType::Rgb => buf
+            .chunks_exact(3)
+            .flat_map(|px| [px[0], px[1], px[2], u8::MAX])
+            .collect(),
+        png::ColorType::GrayscaleAlpha => buf
+            .chunks_exact(2)
+            .flat_map(|px| [px[0], px[0], px[0], px[1]])
+            .collect(),
+        // indexed images are expanded to RGB or RGBA
+        png::ColorType::Grayscale | png::ColorType::Indexed => {
+            buf.iter().flat_map(|&px| [px, px, px, u8::MAX]).collect()
+        }
+    };
+    Ok((frame.width, frame.height, rgba))
+}
+
+/// Decode a JPEG image into 8-bit RGBA pixels, returning its width and height too
+fn decode_jpeg_rgba(data: &[u8]) -> Result<(u32, u32, Vec<u8>), String> {
+    let mut decoder = jpeg_decoder::Decoder::new(data);
+    let pixels = decoder.decode().map_err(|e| e.to_string())?;
+    let info = decoder.info().ok_or("missing JPEG image info")?;
+    let rgba = match info.pixel_format {
+        jpeg_decoder::PixelFormat::RGB24 => pixels
+            .chunks_exact(3)
+            .flat_map(|px| [px[0], px[1], px[2], u8::MAX])
+            .collect(),
+        jpeg_decoder::PixelFormat::L8 => pixels
+            .iter()
+            .flat_map(|&px| [px, px, px, u8::MAX])
+            .collect(),
+        format => Err(format!("{format:?} JPEG images cannot be decoded"))?,
+    };
+    Ok((u32::from(info.width), u32::from(info.height), rgba))
+}
+
+/// Encode 8-bit RGBA pixels as a PNG image
+fn encode_rgba(
+    width: u32,
+    height: u32,
+    rgba: &[u8],
+    compression: png::Compression,
+) -> Result<Vec<u8>, png::EncodingError> {
+    let mut data = Vec::new();
+    let mut encoder = png::Encoder::new(&mut data, width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_compression(compression);
+    encoder.write_header()?.write_image_data(rgba)?;
+    Ok(data)
+}
+
+/// PNG compression for a level from 0 (fastest) to 9 (smallest), see [`TranscodeOptions::compression`].
+/// The levels are mapped to the three compression presets of the PNG encoder.
+fn png_compression(level: u8) -> png::Compression {
+    match level {
+        0..=3 => png::Compression::Fast,
+        4..=6 => png::Compression::Default,
+        _ => png::Compression::Best,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tilejson::{tilejson, Bounds};
+
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::TestSource;
+
+    pub(super) fn test_source(id: &'static str) -> TileInfoSource {
+        Box::new(TestSource {
+            id,
+            tj: tilejson! { tiles: vec![] },
+            info: TileInfo::new(Format::Mvt, Encoding::Uncompressed),
+            data: Vec::default(),
+        })
+    }
+
+    #[test]
+    fn debug_summary() {
+        let raster = Box::new(TestSource {
+            id: "raster",
+            tj: tilejson! { tiles: vec![] },
+            info: TileInfo::new(Format::Png, Encoding::Internal),
+            data: Vec::default(),
+        });
+        let sources = TileSources::new(vec![vec![raster, test_source("mvt_src")]]);
+        assert_eq!(
+            format!("{sources:?}"),
+            "TileSources { mvt_src: mvt/none, raster: png/none }"
+        );
+    }
+
+    #[test]
+    fn response_headers() {
+        let src = test_source("src");
+        assert_eq!(
+            src.response_headers(),
+            vec![("Content-Type", "application/x-protobuf".to_string())]
+        );
+
+        let tile = Tile::new(Vec::new(), TileInfo::new(Format::Mvt, Encoding::Gzip));
+        assert_eq!(
+            tile.response_headers(),
+            vec![
+                ("Content-Type", "application/x-protobuf".to_string()),
+                ("Content-Encoding", "gzip".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn xyz_format() {
+        let xyz = TileCoord { z: 1, x: 2, y: 3 };
+        assert_eq!(format!("{xyz}"), "1,2,3");
+        assert_eq!(format!("{xyz:#}"), "1/2/3");
+    }
+
+    #[test]
+    fn query_normalization() {
+        let query = |pairs: &[(&str, &str)]| {
+            pairs
+                .iter()
+                .map(|(k, v)| ((*k).to_string(), (*v).to_string()))
+                .collect::<UrlQuery>()
+        };
+        let key = normalize_query(&query(&[("b", "2"), ("a", "x y"), ("c", "&=")]));
+        assert_eq!(key, "a=x+y&b=2&c=%26%3D");
+        assert_eq!(
+            key,
+            normalize_query(&query(&[("c", "&="), ("b", "2"), ("a", "x y")]))
+        );
+        assert_eq!(normalize_query(&UrlQuery::new()), "");
+
+        let src = test_source("src");
+        assert_eq!(src.url_query_key(&query(&[("a", "1")])), "");
+    }
+
+    #[test]
+    fn tile_filename() {
+        let xyz = TileCoord { z: 1, x: 2, y: 3 };
+        assert_eq!(test_source("roads").tile_filename(xyz), "roads_1_2_3.pbf");
+        let src = TestSource {
+            id: "satellite",
+            tj: tilejson! { tiles: vec![] },
+            info: TileInfo::from(Format::Png),
+            data: Vec::default(),
+        };
+        assert_eq!(src.tile_filename(xyz), "satellite_1_2_3.png");
+    }
+
+    #[test]
+    fn tile_cache_key() {
+        let query = |pairs: &[(&str, &str)]| {
+            pairs
+                .iter()
+                .map(|(k, v)| ((*k).to_string(), (*v).to_string()))
+                .collect::<UrlQuery>()
+        };
+        let xyz = TileCoord { z: 1, x: 2, y: 3 };
+        let src = CountingSource(
+            TestSource {
+                id: "src",
+                tj: tilejson! { tiles: vec![] },
+                info: TileInfo::new(Format::Mvt, Encoding::Uncompressed),
+                data: Vec::default(),
+            },
+            Arc::default(),
+        );
+        let key = src.tile_cache_key(xyz, Some(&query(&[("b", "2"), ("a", "1")])));
+        assert_eq!(key, "src/1/2/3?a=1&b=2");
+        assert_eq!(
+            key,
+            src.tile_cache_key(xyz, Some(&query(&[("a", "1"), ("b", "2")])))
+        );
+        assert_eq!(src.tile_cache_key(xyz, None), "src/1/2/3");
+        assert_eq!(src.tile_cache_key(xyz, Some(&UrlQuery::new())), "src/1/2/3");
+
+        // the query is ignored by sources that do not support it
+        let src = test_source("plain");
+        let key = src.tile_cache_key(xyz, Some(&query(&[("a", "1")])));
+        assert_eq!(key, "plain/1/2/3");
+    }
+
+    #[test]
+    fn case_insensitive_lookup() {
+        let sources = TileSources::new(vec![vec![
+            test_source("MySource"),
+            test_source("other"),
+            test_source("Other"),
+        ]]);
+        assert!(sources.get_source("mysource").is_err());
+
+        let sources = sources.case_insensitive(true);
+        assert!(sources.contains("mysource"));
+        assert_eq!(sources.get_source("mysource").unwrap().get_id(), "MySource");
+        assert_eq!(sources.get_source("MYSOURCE").unwrap().get_id(), "MySource");
+        // exact matches win, and collisions resolve to the first ID alphabetically
+        assert_eq!(sources.get_source("other").unwrap().get_id(), "other");
+        assert_eq!(sources.get_source("Other").unwrap().get_id(), "Other");
+        assert_eq!(sources.get_source("OTHER").unwrap().get_id(), "Other");
+        assert!(sources.get_source("missing").is_err());
+
+        let sources = sources.case_insensitive(false);
+        assert!(sources.get_source("mysource").is_err());
+    }
+
+    #[test]
+    fn default_source_alias() {
+        let mut sources = TileSources::new(vec![vec![test_source("a"), test_source("b")]]);
+        assert!(sources.get_source(DEFAULT_SOURCE_ALIAS).is_err());
+        assert_eq!(sources.default_id(), None);
+
+        sources.set_default("b").unwrap();
+        assert_eq!(sources.default_id(), Some("b"));
+        assert!(sources.contains(DEFAULT_SOURCE_ALIAS));
+        assert_eq!(sources.get_source("_").unwrap().get_id(), "b");
+        let (srcs, ..) = sources.get_sources("a,_", None).unwrap();
+        assert_eq!(
+            srcs.iter().map(|s| s.get_id()).collect::<Vec<_>>(),
+            ["a", "b"]
+        );
+
+        // a source with the reserved ID takes precedence
+        sources.insert(test_source("_"));
+        assert_eq!(sources.get_source("_").unwrap().get_id(), "_");
+        sources.remove("_");
+
+        assert!(matches!(
+            sources.set_default("missing"),
+            Err(DefaultSourceNotFound(id)) if id == "missing"
+        ));
+        assert_eq!(sources.default_id(), Some("b"));
+
+        // the alias dangles after the source is removed
+        sources.remove("b");
+        let err = sources.get_source("_").err().unwrap();
+        assert_eq!(err.to_string(), "Default source b does not exist");
+        assert_eq!(err.error_response().status(), StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn source_aliases() {
+        let mut sources = TileSources::new(vec![vec![test_source("new"), test_source("b")]]);
+        sources.add_alias("old", "new").unwrap();
+        assert!(sources.contains("old"));
+        assert_eq!(sources.get_source("old").unwrap().get_id(), "new");
+        let (srcs, ..) = sources.get_sources("old,b", None).unwrap();
+        assert_eq!(
+            srcs.iter().map(|s| s.get_id()).collect::<Vec<_>>(),
+            ["new", "b"]
+        );
+        assert_eq!(sources.len(), 2);
+
+        assert!(matches!(
+            sources.add_alias("b", "new"),
+            Err(AliasConflict(id)) if id == "b"
+        ));
+        assert!(matches!(
+            sources.add_alias(DEFAULT_SOURCE_ALIAS, "new"),
+            Err(AliasConflict(..))
+        ));
+        assert_eq!(sources.get_source("b").unwrap().get_id(), "b");
+
+        // a source added later with the ID of an alias takes precedence
+        sources.add_alias("c", "new").unwrap();
+        sources.insert(test_source("c"));
+        assert_eq!(sources.get_source("c").unwrap().get_id(), "c");
+
+        sources.remove("new");
+        let err = sources.get_source("old").err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            "Alias old refers to source new, which does not exist"
+        );
+        assert_eq!(err.error_response().status(), StatusCode::NOT_FOUND);
+    }
+
+    /// Only accessible to authorized requests
+    #[derive(Debug, Clone)]
+    struct PrivateSource(TestSource);
+
+    #[async_trait]
+    impl Source for PrivateSource {
+        fn get_id(&self) -> &str {
+            self.0.get_id()
+        }
+
+        fn get_tilejson(&self) -> &TileJSON {
+            self.0.get_tilejson()
+        }
+
+        fn get_tile_info(&self) -> TileInfo {
+            self.0.get_tile_info()
+        }
+
+        fn clone_source(&self) -> TileInfoSource {
+            Box::new(self.clone())
+        }
+
+        async fn get_tile(
+            &self,
+            xyz: TileCoord,
+            url_query: Option<&UrlQuery>,
+        ) -> MartinResult<TileData> {
+            self.0.get_tile(xyz, url_query).await
+        }
+
+        fn requires_auth(&self) -> bool {
+            true
+        }
+    }
+
+    #[test]
+    fn authorized_sources() {
+        let private = PrivateSource(TestSource {
+            id: "private",
+            tj: tilejson! { tiles: vec![] },
+            info: TileInfo::new(Format::Mvt, Encoding::Uncompressed),
+            data: Vec::default(),
+        });
+        let sources = TileSources::new(vec![vec![test_source("public"), Box::new(private)]]);
+        let allow: &dyn Fn(&str) -> bool = &|id| id == "private";
+        let deny: &dyn Fn(&str) -> bool = &|_| false;
+
+        for authorize in [None, Some(allow), Some(deny)] {
+            let src = sources.get_source_authorized("public", authorize).unwrap();
+            assert_eq!(src.get_id(), "public");
+        }
+        let src = sources
+            .get_source_authorized("private", Some(allow))
+            .unwrap();
+        assert_eq!(src.get_id(), "private");
+        let (srcs, ..) = sources
+            .get_sources_authorized("public,private", None, Some(allow))
+            .unwrap();
+        assert_eq!(srcs.len(), 2);
+
+        for authorize in [None, Some(deny)] {
+            let err = sources
+                .get_source_authorized("private", authorize)
+                .err()
+                .unwrap();
+            assert_eq!(err.error_response().status(), StatusCode::FORBIDDEN);
+            let err = sources
+                .get_sources_authorized("public,private", None, authorize)
+                .err()
+                .unwrap();
+            assert_eq!(
+                err.to_string(),
+                "Access to source private is not authorized"
+            );
+        }
+
+        let err = sources
+            .get_source_authorized("missing", Some(allow))
+            .err()
+            .unwrap();
+        assert_eq!(err.error_response().status(), StatusCode::NOT_FOUND);
+
+        // unchecked access
+        assert!(sources.get_source("private").is_ok());
+    }
+
+    #[test]
+    fn duplicate_ids() {
+        let sources = TileSources::new(vec![
+            vec![test_source("a"), test_source("b")],
+            vec![test_source("a")],
+        ]);
+        assert_eq!(sources.len(), 2);
+
+        let sources = TileSourcesBuilder::new()
+            .add_source(test_source("a"))
+            .add_sources(vec![test_source("b"), test_source("c")])
+            .build()
+            .unwrap();
+        assert_eq!(sources.len(), 3);
+
+        let err = TileSourcesBuilder::new()
+            .add_sources(vec![test_source("b"), test_source("a"), test_source("c")])
+            .add_sources(vec![test_source("b"), test_source("a"), test_source("b")])
+            .build()
+            .unwrap_err();
+        assert!(matches!(&err, DuplicateSourceIds(ids) if ids == &["a", "b"]));
+        assert_eq!(err.to_string(), "Multiple sources have the same ID: a, b");
+    }
+
+    /// Responds to missing tiles with a transparent tile
+    #[derive(Debug, Clone)]
+    struct TransparentSource(TestSource);
+
+    #[async_trait]
+    impl Source for TransparentSource {
+        fn get_id(&self) -> &str {
+            self.0.get_id()
+        }
+
+        fn get_tilejson(&self) -> &TileJSON {
+            self.0.get_tilejson()
+        }
+
+        fn get_tile_info(&self) -> TileInfo {
+            self.0.get_tile_info()
+        }
+
+        fn clone_source(&self) -> TileInfoSource {
+            Box::new(self.clone())
+        }
+
+        fn miss_behavior(&self) -> MissBehavior {
+            MissBehavior::Transparent
+        }
+
+        async fn get_tile(
+            &self,
+            xyz: TileCoord,
+            url_query: Option<&UrlQuery>,
+        ) -> MartinResult<TileData> {
+            self.0.get_tile(xyz, url_query).await
+        }
+    }
+
+    #[test]
+    fn miss_behaviors() {
+        let transparent = |id, format: Format| -> TileInfoSource {
+            Box::new(TransparentSource(TestSource {
+                id,
+                tj: tilejson! { tiles: vec![] },
+                info: format.into(),
+                data: Vec::default(),
+            }))
+        };
+        assert_eq!(test_source("a").miss_behavior(), MissBehavior::NotFound);
+
+        let sources = TileSourcesBuilder::new()
+            .add_source(test_source("mvt"))
+            .add_source(transparent("png", Format::Png))
+            .add_source(transparent("jpeg", Format::Jpeg))
+            .build()
+            .unwrap();
+        assert!(sources.check_miss_behaviors().is_ok());
+
+        let err = TileSourcesBuilder::new()
+            .add_source(transparent("png", Format::Png))
+            .add_source(transparent("vector", Format::Mvt))
+            .build()
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Source vector (mvt) cannot respond with transparent tiles, because it is not a raster source"
+        );
+    }
+
+    /// Wraps the `x` coordinate at the antimeridian
+    #[derive(Debug, Clone)]
+    struct WrappingSource(TestSource);
+
+    #[async_trait]
+    impl Source for WrappingSource {
+        fn get_id(&self) -> &str {
+            self.0.get_id()
+        }
+
+        fn get_tilejson(&self) -> &TileJSON {
+            self.0.get_tilejson()
+        }
+
+        fn get_tile_info(&self) -> TileInfo {
+            self.0.get_tile_info()
+        }
+
+        fn clone_source(&self) -> TileInfoSource {
+            Box::new(self.clone())
+        }
+
+        fn wrap_x(&self) -> bool {
+            true
+        }
+
+        async fn get_tile(
+            &self,
+            xyz: TileCoord,
+            url_query: Option<&UrlQuery>,
+        ) -> MartinResult<TileData> {
+            self.0.get_tile(xyz, url_query).await
+        }
+    }
+
+    #[test]
+    fn wraps_x() {
+        let wrapping = |id| -> TileInfoSource {
+            Box::new(WrappingSource(TestSource {
+                id,
+                tj: tilejson! { tiles: vec![] },
+                info: TileInfo::new(Format::Mvt, Encoding::Uncompressed),
+                data: Vec::default(),
+            }))
+        };
+        let sources = TileSources::new(vec![vec![
+            wrapping("w1"),
+            wrapping("w2"),
+            test_source("plain"),
+        ]]);
+        assert!(!test_source("a").wrap_x());
+        assert!(sources.wraps_x("w1"));
+        assert!(sources.wraps_x("w1,w2"));
+        assert!(!sources.wraps_x("w1,plain"));
+        assert!(!sources.wraps_x("plain"));
+        assert!(!sources.wraps_x("missing"));
+
+        // IDs are resolved like the requested sources
+        let mut sources = sources;
+        assert!(!sources.wraps_x(DEFAULT_SOURCE_ALIAS));
+        sources.set_default("w1").unwrap();
+        sources.add_alias("world", "w2").unwrap();
+        assert!(sources.wraps_x(DEFAULT_SOURCE_ALIAS));
+        assert!(sources.wraps_x("world,w1"));
+
+        let policy = RetryPolicy {
+            max_attempts: 2,
+            backoff: Duration::ZERO,
+        };
+        let wrappers: Vec<TileInfoSource> = vec![
+            Box::new(TransformSource::new(wrapping("w"), Arc::new(Ok))),
+            Box::new(TimeoutSource::new(wrapping("w"), Duration::from_secs(1))),
+            Box::new(CachedSource::new(wrapping("w"), 10)),
+            Box::new(MeteredSource::new(wrapping("w"), Arc::new(|_, _, _| {}))),
+            Box::new(ValidatingSource::new(wrapping("w"), TileValidation::Strict)),
+            Box::new(ZoomShiftSource::new(wrapping("w"), 0)),
+            Box::new(RetrySource::new(wrapping("w"), policy)),
+            Box::new(TranscodeSource::new(wrapping("w"), Encoding::Gzip).unwrap()),
+            Box::new(RewriteUrlSource::new(wrapping("w"), "/tiles")),
+            Box::new(ShardedSource::new(vec![wrapping("w"), wrapping("w")]).unwrap()),
+        ];
+        for src in wrappers {
+            assert!(src.wrap_x(), "{src:?}");
+        }
+    }
+
+    #[test]
+    fn sources_from_iter() {
+        let vector = ["a", "b"].into_iter().map(test_source);
+        let raster = std::iter::once("c").map(test_source);
+        let sources: TileSources = vector.chain(raster).collect();
+        assert_eq!(
+            sources.source_ids().sorted().collect::<Vec<_>>(),
+            ["a", "b", "c"]
+        );
+
+        let sources: TileSources = ["a", "a"].into_iter().map(test_source).collect();
+        assert_eq!(sources.len(), 1);
+    }
+
+    #[test]
+    fn source_ids() {
+        let sources = TileSources::default();
+        assert!(sources.is_empty());
+        assert_eq!(sources.source_ids().count(), 0);
+
+        let sources = TileSources::new(vec![
+            vec![test_source("a"), test_source("b")],
+            vec![test_source("c")],
+        ]);
+        assert!(!sources.is_empty());
+        assert_eq!(sources.len(), 3);
+        assert!(sources.contains("a"));
+        assert!(!sources.contains("A"));
+        assert!(!sources.contains("d"));
+        assert_eq!(
+            sources.source_ids().sorted().collect::<Vec<_>>(),
+            vec!["a", "b", "c"]
+        );
+    }
+
+    #[test]
+    fn unboxed_sources() {
+        let src = |id| TestSource {
+            id,
+            tj: tilejson! { tiles: vec![] },
+            info: TileInfo::new(Format::Mvt, Encoding::Uncompressed),
+            data: Vec::default(),
+        };
+        let mut sources = TileSourcesOf::new(vec![vec![src("a"), src("b")]]);
+        let a: &TestSource = sources.get("a").unwrap();
+        assert_eq!(a.id, "a");
+        assert!(sources.get("c").is_none());
+        assert_eq!(sources.get_source("b").unwrap().get_id(), "b");
+
+        sources.insert(src("c"));
+        let (srcs, ..) = sources.get_sources("c,a", None).unwrap();
+        assert_eq!(srcs.len(), 2);
+        assert_eq!(sources.get_catalog().len(), 3);
+    }
+
+    #[actix_rt::test]
+    async fn catalog_async() {
+        let mut sources = TileSources::new(vec![vec![test_source("a"), test_source("b")]]);
+        let catalog = sources.get_catalog_async().await;
+        assert_eq!(catalog.keys().collect::<Vec<_>>(), vec!["a", "b"]);
+        assert_eq!(catalog, sources.get_catalog());
+
+        sources.insert(test_source("c"));
+        assert_eq!(sources.get_catalog_async().await.len(), 3);
+        assert_eq!(sources.get_catalog().len(), 3);
+    }
+
+    #[test]
+    fn catalog_iter() {
+        let sources = TileSources::new(vec![vec![
+            test_source("c"),
+            test_source("a"),
+            test_source("b"),
+        ]]);
+        let entries: Vec<_> = sources.catalog_iter().collect();
+        let ids: Vec<_> = entries.iter().map(|(id, _)| id.as_str()).collect();
+        assert_eq!(ids, ["a", "b", "c"]);
+        assert_eq!(
+            entries,
+            sources.get_catalog().into_iter().collect::<Vec<_>>()
+        );
+        assert_eq!(sources.catalog_iter().next().unwrap().0, "a");
+    }
+
+    #[test]
+    fn insert_and_remove() {
+        let mut sources = TileSources::new(vec![vec![test_source("a")]]);
+        assert!(sources.get_source("b").is_err());
+
+        assert!(sources.insert(test_source("b")).is_none());
+        assert_eq!(sources.get_source("b").unwrap().get_id(), "b");
+        assert_eq!(sources.insert(test_source("b")).unwrap().get_id(), "b");
+        assert_eq!(sources.len(), 2);
+
+        assert_eq!(sources.remove("a").unwrap().get_id(), "a");
+        let err = sources.get_source("a").err().unwrap();
+        assert_eq!(err.as_response_error().status_code(), StatusCode::NOT_FOUND);
+        assert!(sources.remove("a").is_none());
+
+        let mut sources = sources.case_insensitive(true);
+        sources.insert(test_source("Upper"));
+        assert_eq!(sources.get_source("upper").unwrap().get_id(), "Upper");
+        sources.remove("Upper");
+        assert!(sources.get_source("upper").is_err());
+    }
+
+    #[test]
+    fn snapshot() {
+        let mut sources = TileSources::new(vec![vec![test_source("a"), test_source("b")]]);
+        let snapshot = sources.snapshot();
+
+        // the sources are shared, not cloned
+        assert!(std::ptr::addr_eq(
+            snapshot.get_source("a").unwrap(),
+            sources.get_source("a").unwrap()
+        ));
+
+        sources.insert(test_source("c"));
+        let removed: Arc<dyn Source> = sources.remove("a").unwrap();
+        assert_eq!(Arc::strong_count(&removed), 2);
+        assert_eq!(
+            sources.source_ids().sorted().collect::<Vec<_>>(),
+            ["b", "c"]
+        );
+        assert_eq!(
+            snapshot.source_ids().sorted().collect::<Vec<_>>(),
+            ["a", "b"]
+        );
+        assert_eq!(snapshot.get_source("a").unwrap().get_id(), "a");
+    }
+
+    #[test]
+    fn validate_all() {
+        let source = |id: &'static str, tj: TileJSON| -> TileInfoSource {
+            Box::new(TestSource {
+                id,
+                tj,
+                info: TileInfo::new(Format::Mvt, Encoding::Uncompressed),
+                data: Vec::default(),
+            })
+        };
+        let sources = TileSources::new(vec![vec![
+            test_source("valid"),
+            source(
+                "zooms",
+                tilejson! { tiles: vec![], minzoom: 10, maxzoom: 5 },
+            ),
+            source(
+                "bounds",
+                tilejson! { tiles: vec![], bounds: Bounds::new(-200.0, -10.0, 10.0, 95.0) },
+            ),
+            source(
+                "flipped",
+                tilejson! { tiles: vec![], bounds: Bounds::new(170.0, 10.0, -170.0, -10.0) },
+            ),
+        ]]);
+
+        let problems = sources.validate_all();
+        let ids: Vec<_> = problems.iter().map(|(id, _)| id.as_str()).collect();
+        assert_eq!(ids, ["bounds", "flipped", "zooms"]);
+        assert_eq!(
+            problems[0].1,
+            [
+                "bounds -200,-10,10,95 have a longitude outside of [-180, 180]",
+                "bounds -200,-10,10,95 have a latitude outside of [-90, 90]",
+            ]
+        );
+        // crossing the antimeridian is valid, but not crossing the poles
+        assert_eq!(
+            problems[1].1,
+            ["bounds 170,10,-170,-10 have the south above the north"]
+        );
+        assert_eq!(problems[2].1, ["minzoom 10 is above maxzoom 5"]);
+    }
+
+    #[test]
+    fn owned_source() {
+        let raster: TileInfoSource = Box::new(TestSource {
+            id: "raster",
+            tj: tilejson! { tiles: vec![] },
+            info: TileInfo::from(Format::Png),
+            data: Vec::default(),
+        });
+        let sources = TileSources::new(vec![vec![raster]]);
+        let owned = sources.get_source_owned("raster").unwrap();
+        drop(sources);
+        assert_eq!(owned.get_id(), "raster");
+        assert_eq!(owned.get_tile_info(), TileInfo::from(Format::Png));
+
+        let sources = TileSources::new(vec![vec![test_source("a")]]);
+        assert!(sources.get_source_owned("missing").is_err());
+    }
+
+    #[test]
+    fn sources_by_prefix() {
+        let sources = TileSources::new(vec![vec![
+            test_source("country_us"),
+            test_source("country_de"),
+            test_source("country_fr"),
+            test_source("city_paris"),
+        ]]);
+        let ids = |prefix| {
+            sources
+                .get_sources_by_prefix(prefix)
+                .into_iter()
+                .map(Source::get_id)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            ids("country_"),
+            vec!["country_de", "country_fr", "country_us"]
+        );
+        assert_eq!(ids("city_"), vec!["city_paris"]);
+        assert_eq!(ids("").len(), 4);
+        assert!(ids("region_").is_empty());
+    }
+
+    #[test]
+    fn catalog_cache() {
+        let mut sources = TileSources::new(vec![vec![test_source("a")]]);
+        assert_eq!(sources.get_catalog().keys().collect::<Vec<_>>(), vec!["a"]);
+
+        sources.insert(test_source("b"));
+        assert_eq!(
+            sources.get_catalog().keys().collect::<Vec<_>>(),
+            vec!["a", "b"]
+        );
+
+        sources.remove("a");
+        assert_eq!(sources.get_catalog().keys().collect::<Vec<_>>(), vec!["b"]);
+        sources.remove("missing");
+        assert_eq!(sources.get_catalog().len(), 1);
+    }
+
+    #[actix_rt::test]
+    async fn tile_opt() {
+        let xyz = TileCoord { z: 0, x: 0, y: 0 };
+        let empty = test_source("empty");
+        assert!(empty.get_tile_opt(xyz, None).await.unwrap().is_none());
+
+        let src = TestSource {
+            id: "src",
+            tj: tilejson! { tiles: vec![] },
+            info: TileInfo::new(Format::Mvt, Encoding::Uncompressed),
+            data: vec![1, 2, 3],
+        };
+        let tile = src.get_tile_opt(xyz, None).await.unwrap().unwrap();
+        assert_eq!(tile.data, vec![1, 2, 3]);
+    }
+
+    #[actix_rt::test]
+    async fn tile_decoded() {
+        let xyz = TileCoord { z: 0, x: 0, y: 0 };
+        let mvt = vec![0x1a, 0x02, 0x78, 0x02];
+        let src = TestSource {
+            id: "gzip",
+            tj: tilejson! { tiles: vec![] },
+            info: TileInfo::new(Format::Mvt, Encoding::Gzip),
+            data: encode_gzip(&mvt).unwrap(),
+        };
+        let (tile, stored) = src.get_tile_decoded(xyz, None).await.unwrap();
+        assert_eq!(tile.data, mvt);
+        assert_eq!(
+            tile.info,
+            TileInfo::new(Format::Mvt, Encoding::Uncompressed)
+        );
+        assert_eq!(stored, TileInfo::new(Format::Mvt, Encoding::Gzip));
+
+        let src = TestSource {
+            info: TileInfo::new(Format::Mvt, Encoding::Zstd),
+            data: mvt,
+            ..src
+        };
+        let err = src.get_tile_decoded(xyz, None).await.unwrap_err();
+        assert!(matches!(
+            err,
+            MartinError::SourceError(UnsupportedEncoding(_))
+        ));
+    }
+
+    #[derive(Debug, Clone)]
+    pub(super) struct SlowSource(pub(super) TestSource, pub(super) Duration);
+
+    #[async_trait]
+    impl Source for SlowSource {
+        fn get_id(&self) -> &str {
+            self.0.get_id()
+        }
+
+        fn get_tilejson(&self) -> &TileJSON {
+            self.0.get_tilejson()
+        }
+
+        fn get_tile_info(&self) -> TileInfo {
+            self.0.get_tile_info()
+        }
+
+        fn clone_source(&self) -> TileInfoSource {
+            Box::new(self.clone())
+        }
+
+        async fn get_tile(
+            &self,
+            xyz: TileCoord,
+            url_query: Option<&UrlQuery>,
+        ) -> MartinResult<TileData> {
+            tokio::time::sleep(self.1).await;
+            self.0.get_tile(xyz, url_query).await
+        }
+    }
+
+    /// Counts how many tiles were requested from it, and supports URL queries
+    #[derive(Debug, Clone)]
+    pub(super) struct CountingSource(pub(super) TestSource, pub(super) Arc<AtomicUsize>);
+
+    #[async_trait]
+    impl Source for CountingSource {
+        fn get_id(&self) -> &str {
+            self.0.get_id()
+        }
+
+        fn get_tilejson(&self) -> &TileJSON {
+            self.0.get_tilejson()
+        }
+
+        fn get_tile_info(&self) -> TileInfo {
+            self.0.get_tile_info()
+        }
+
+        fn clone_source(&self) -> TileInfoSource {
+            Box::new(self.clone())
+        }
+
+        fn support_url_query(&self) -> bool {
+            true
+        }
+
+        async fn get_tile(
+            &self,
+            xyz: TileCoord,
+            url_query: Option<&UrlQuery>,
+        ) -> MartinResult<TileData> {
+            self.1.fetch_add(1, Ordering::Relaxed);
+            self.0.get_tile(xyz, url_query).await
+        }
+    }
+
+    #[actix_rt::test]
+    async fn prewarm() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let backend = CountingSource(
+            TestSource {
+                id: "counted",
+                tj: tilejson! { tiles: vec![], minzoom: 0, maxzoom: 2 },
+                info: TileInfo::new(Format::Mvt, Encoding::Uncompressed),
+                data: vec![1_u8],
+            },
+            calls.clone(),
+        );
+        let src = CachedSource::new(Box::new(backend), 100);
+
+        // the north-eastern quarter of the world, excluding zoom 3 which is above the maxzoom
+        let bounds = [1.0, 1.0, 179.0, 80.0];
+        assert_eq!(src.prewarm(bounds, 0..4, None).await.unwrap(), 1 + 1 + 4);
+        assert_eq!(calls.load(Ordering::Relaxed), 6);
+
+        // prewarmed tiles are served from the cache
+        src.get_tile(TileCoord { z: 2, x: 3, y: 0 }, None)
+            .await
+            .unwrap();
+        assert_eq!(calls.load(Ordering::Relaxed), 6);
+    }
+
+    /// Fails the first `failures` tile requests, with an error that is not retried if `terminal`
+    #[derive(Debug, Clone)]
+    pub(super) struct FlakySource {
+        pub(super) inner: TestSource,
+        pub(super) calls: Arc<AtomicUsize>,
+        pub(super) failures: usize,
+        pub(super) terminal: bool,
+    }
+
+    #[async_trait]
+    impl Source for FlakySource {
+        fn get_id(&self) -> &str {
+            self.inner.get_id()
+        }
+
+        fn get_tilejson(&self) -> &TileJSON {
+            self.inner.get_tilejson()
+        }
+
+        fn get_tile_info(&self) -> TileInfo {
+            self.inner.get_tile_info()
+        }
+
+        fn clone_source(&self) -> TileInfoSource {
+            Box::new(self.clone())
+        }
+
+        async fn get_tile(
+            &self,
+            xyz: TileCoord,
+            url_query: Option<&UrlQuery>,
+        ) -> MartinResult<TileData> {
+            if self.calls.fetch_add(1, Ordering::Relaxed) < self.failures {
+                if self.terminal {
+                    Err(InvalidRange(0..1, 0))?;
+                }
+                Err(std::io::Error::other("connection reset"))?;
+            }
+            self.inner.get_tile(xyz, url_query).await
+        }
+    }
+
+    #[actix_rt::test]
+    async fn source_tile_error_context() {
+        let src = FlakySource {
+            inner: TestSource {
+                id: "flaky",
+                tj: tilejson! { tiles: vec![] },
+                info: TileInfo::new(Format::Mvt, Encoding::Uncompressed),
+                data: vec![1_u8],
+            },
+            calls: Arc::new(AtomicUsize::new(0)),
+            failures: 1,
+            terminal: false,
+        };
+        let xyz = TileCoord { z: 3, x: 1, y: 2 };
+        let err = TileSources::get_source_tile(&src, xyz, None)
+            .await
+            .unwrap_err();
+        let MartinError::TileError(id, err_xyz, _) = &err else {
+            panic!("unexpected error {err:?}");
+        };
+        assert_eq!((id.as_str(), *err_xyz), ("flaky", xyz));
+        let msg = err.to_string();
+        assert!(msg.contains("flaky") && msg.contains("3,1,2"), "{msg}");
+        assert!(msg.ends_with("connection reset"), "{msg}");
+
+        let tile = TileSources::get_source_tile(&src, xyz, None).await.unwrap();
+        assert_eq!(tile.data, vec![1_u8]);
+    }
+
+    /// A 2x2 PNG tile filled with a single RGBA color
+    pub(super) fn solid_png(id: &'static str, rgba: [u8; 4]) -> TestSource {
+        TestSource {
+            id,
+            tj: tilejson! { tiles: vec![] },
+            info: TileInfo::from(Format::Png),
+            data: encode_rgba(2, 2, &rgba.repeat(4), png::Compression::Default).unwrap(),
+        }
+    }
+
+    #[test]
+    fn transcode_options() {
+        let query = |pairs: &[(&str, &str)]| -> UrlQuery {
+            pairs
+                .iter()
+                .map(|(k, v)| ((*k).to_string(), (*v).to_string()))
+                .collect()
+        };
+        assert_eq!(
+            TranscodeOptions::from_query(&query(&[])).unwrap(),
+            TranscodeOptions::default()
+        );
+        assert_eq!(
+            TranscodeOptions::from_query(&query(&[("quality", "80"), ("lang", "en")])).unwrap(),
+            TranscodeOptions {
+                quality: Some(80),
+                compression: None,
+            }
+        );
+        assert_eq!(
+            TranscodeOptions::from_query(&query(&[("quality", "100"), ("compression", "0")]))
+                .unwrap(),
+            TranscodeOptions {
+                quality: Some(100),
+                compression: Some(0),
+            }
+        );
+
+        for (key, value) in [
+            ("quality", "0"),
+            ("quality", "101"),
+            ("quality", "high"),
+            ("compression", "10"),
+            ("compression", "-1"),
+        ] {
+            let err = TranscodeOptions::from_query(&query(&[(key, value)]))
+                .err()
+                .unwrap();
+            assert!(matches!(&err, InvalidQueryValue(k, v, ..) if k == key && v == value));
+            assert_eq!(err.status_code(), StatusCode::BAD_REQUEST);
+        }
+        let err = TranscodeOptions::from_query(&query(&[("quality", "0")]))
+            .err()
+            .unwrap();
+        assert_eq!(
+            err.to_string(),
+            "URL query parameter quality=0 must be a number from 1 to 100"
+        );
+    }
+
+    #[actix_rt::test]
+    async fn transcode_tile() {
+        let xyz = TileCoord { z: 0, x: 0, y: 0 };
+        let src = solid_png("png", [0, 0, 255, 128]);
+        let options = TranscodeOptions {
+            quality: None,
+            compression: Some(9),
+        };
+        let tile = src
+            .transcode_tile(xyz, None, Format::Png, options)
+            .await
+            .unwrap();
+        assert_eq!(tile.info, TileInfo::from(Format::Png));
+        let (width, height, rgba) = decode_rgba(&tile.data).unwrap();
+        assert_eq!((width, height), (2, 2));
+        assert_eq!(rgba, [0, 0, 255, 128].repeat(4));
+
+        // without options, the tile is returned as is
+        let tile = src
+            .transcode_tile(xyz, None, Format::Png, TranscodeOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(tile.data, src.data);
+
+        let err = src
+            .transcode_tile(xyz, None, Format::Jpeg, options)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            MartinError::SourceError(UnsupportedFormat(_, Format::Jpeg))
+        ));
+
+        // the quality cannot be applied without a JPEG or WebP encoder
+        let options = TranscodeOptions {
+            quality: Some(80),
+            compression: None,
+        };
+        let err = src
+            .transcode_tile(xyz, None, Format::Png, options)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Source png cannot encode png tiles with a given quality"
+        );
+        let MartinError::SourceError(err) = err else {
+            panic!("unexpected error {err:?}");
+        };
+        assert_eq!(err.status_code(), StatusCode::BAD_REQUEST);
+
+        let mvt = test_source("mvt");
+        let err = mvt
+            .transcode_tile(xyz, None, Format::Mvt, options)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            MartinError::SourceError(UnsupportedFormat(_, Format::Mvt))
+        ));
+    }
+
+    /// Only has tiles with an even `x`, and can only check for them without fetching
+    #[derive(Debug, Clone)]
+    struct IndexedSource(TestSource);
+
+    #[async_trait]
+    impl Source for IndexedSource {
+        fn get_id(&self) -> &str {
+            self.0.get_id()
+        }
+
+        fn get_tilejson(&self) -> &TileJSON {
+            self.0.get_tilejson()
+        }
+
+        fn get_tile_info(&self) -> TileInfo {
+            self.0.get_tile_info()
+        }
+
+        fn clone_source(&self) -> TileInfoSource {
+            Box::new(self.clone())
+        }
+
+        async fn get_tile(
+            &self,
+            _xyz: TileCoord,
+            _url_query: Option<&UrlQuery>,
+        ) -> MartinResult<TileData> {
+            panic!("tiles must not be fetched")
+        }
+
+        async fn has_tile(
+            &self,
+            xyz: TileCoord,
+            _url_query: Option<&UrlQuery>,
+        ) -> MartinResult<bool> {
+            Ok(xyz.x % 2 == 0)
+        }
+    }
+
+    #[actix_rt::test]
+    async fn has_tile() {
+        let source = |id, data: &[u8]| TestSource {
+            id,
+            tj: tilejson! { tiles: vec![] },
+            info: TileInfo::new(Format::Mvt, Encoding::Uncompressed),
+            data: data.to_vec(),
+        };
+        let xyz = |x| TileCoord { z: 1, x, y: 0 };
+        let full = source("full", &[1]);
+        let empty = source("empty", &[]);
+        assert!(full.has_tile(xyz(0), None).await.unwrap());
+        assert!(!empty.has_tile(xyz(0), None).await.unwrap());
+
+        let indexed = IndexedSource(source("indexed", &[2]));
+        let sources = TileSources::new(vec![vec![
+            Box::new(full),
+            Box::new(empty),
+            Box::new(indexed),
+        ]]);
+        let (srcs, ..) = sources.get_sources("full,empty,indexed", None).unwrap();
+        let ids = |srcs: Vec<&dyn Source>| {
+            srcs.iter()
+                .map(|s| s.get_id().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        let found = TileSources::sources_with_tile(&srcs, xyz(0), None).await;
+        assert_eq!(ids(found.unwrap()), ["full", "indexed"]);
+        let found = TileSources::sources_with_tile(&srcs, xyz(1), None).await;
+        assert_eq!(ids(found.unwrap()), ["full"]);
+    }
+
+    #[actix_rt::test]
+    async fn has_tile_through_wrappers() {
+        let indexed = || -> TileInfoSource {
+            Box::new(IndexedSource(TestSource {
+                id: "indexed",
+                tj: tilejson! { tiles: vec![] },
+                info: TileInfo::new(Format::Mvt, Encoding::Uncompressed),
+                data: vec![1],
+            }))
+        };
+        let policy = RetryPolicy {
+            max_attempts: 2,
+            backoff: Duration::ZERO,
+        };
+        let wrappers: Vec<TileInfoSource> = vec![
+            Box::new(TransformSource::new(indexed(), Arc::new(Ok))),
+            Box::new(TimeoutSource::new(indexed(), Duration::from_secs(1))),
+            Box::new(CachedSource::new(indexed(), 10)),
+            Box::new(MeteredSource::new(indexed(), Arc::new(|_, _, _| {}))),
+            Box::new(ValidatingSource::new(indexed(), TileValidation::Strict)),
+            Box::new(ZoomShiftSource::new(indexed(), 0)),
+            Box::new(RetrySource::new(indexed(), policy)),
+            Box::new(TranscodeSource::new(indexed(), Encoding::Gzip).unwrap()),
+            Box::new(RewriteUrlSource::new(indexed(), "/tiles")),
+            Box::new(ShardedSource::new(vec![indexed(), indexed()]).unwrap()),
+        ];
+        // the wrapped source panics if the tile is fetched instead of checked
+        for src in wrappers {
+            assert!(src
+                .has_tile(TileCoord { z: 1, x: 0, y: 0 }, None)
+                .await
+                .unwrap());
+            assert!(!src
+                .has_tile(TileCoord { z: 1, x: 1, y: 0 }, None)
+                .await
+                .unwrap());
+        }
+    }
+
+    #[test]
+    fn catalog_diff() {
+        let entry = |content_type: &str| CatalogSourceEntry {
+            content_type: content_type.to_string(),
+            ..Default::default()
+        };
+        let old = TileCatalog::from([
+            ("kept".to_string(), entry("image/png")),
+            ("removed".to_string(), entry("image/png")),
+            ("changed".to_string(), entry("image/png")),
+        ]);
+        assert!(diff_catalogs(&old, &old).is_empty());
+
+        let mut new = old.clone();
+        new.remove("removed");
+        new.insert("added".to_string(), entry("image/png"));
+        new.insert("changed".to_string(), entry("image/webp"));
+        assert_eq!(
+            diff_catalogs(&old, &new),
+            CatalogDiff {
+                added: vec!["added".to_string()],
+                removed: vec!["removed".to_string()],
+                changed: vec!["changed".to_string()],
+            }
+        );
+
+        let diff = diff_catalogs(&TileCatalog::new(), &old);
+        assert_eq!(diff.added, ["changed", "kept", "removed"]);
+        assert!(diff.removed.is_empty() && diff.changed.is_empty());
+    }
+
+    #[test]
+    fn catalog_by_format() {
+        let raster = |id| -> TileInfoSource {
+            Box::new(TestSource {
+                id,
+                tj: tilejson! { tiles: vec![] },
+                info: TileInfo::from(Format::Png),
+                data: Vec::default(),
+            })
+        };
+        let sources = TileSources::new(vec![
+            vec![test_source("roads"), raster("satellite")],
+            vec![raster("hillshade"), test_source("buildings")],
+        ]);
+        assert_eq!(
+            sources.catalog_by_format(),
+            BTreeMap::from([
+                (
+                    "application/x-protobuf".to_string(),
+                    vec!["buildings".to_string(), "roads".to_string()]
+                ),
+                (
+                    "image/png".to_string(),
+                    vec!["hillshade".to_string(), "satellite".to_string()]
+                ),
+            ])
+        );
+        assert!(TileSources::default().catalog_by_format().is_empty());
+    }
+
+    #[test]
+    fn overall_bounds() {
+        let bounded = |id, bounds| -> TileInfoSource {
+            Box::new(TestSource {
+                id,
+                tj: tilejson! { tiles: vec![], bounds: bounds },
+                info: TileInfo::new(Format::Mvt, Encoding::Uncompressed),
+                data: Vec::default(),
+            })
+        };
+        let sources = TileSources::new(vec![vec![test_source("unbounded")]]);
+        assert_eq!(sources.overall_bounds(), None);
+
+        let sources = TileSources::new(vec![vec![
+            bounded("a", Bounds::new(-10.0, -20.0, 10.0, 20.0)),
+            bounded("b", Bounds::new(-20.0, -5.0, 5.0, 50.0)),
+            test_source("unbounded"),
+        ]]);
+        assert_eq!(sources.overall_bounds(), Some([-20.0, -20.0, 10.0, 50.0]));
+
+        let sources = TileSources::new(vec![vec![
+            bounded("a", Bounds::new(-10.0, -20.0, 10.0, 20.0)),
+            bounded("pacific", Bounds::new(170.0, -30.0, -170.0, 10.0)),
+        ]]);
+        assert_eq!(sources.overall_bounds(), Some([-180.0, -30.0, 180.0, 20.0]));
+    }
+
+    #[test]
+    fn catalog_filtered() {
+        let png = Box::new(TestSource {
+            id: "png",
+            tj: tilejson! { tiles: vec![] },
+            info: Format::Png.into(),
+            data: Vec::default(),
+        });
+        let sources = TileSources::new(vec![vec![test_source("mvt1"), png, test_source("mvt2")]]);
+
+        let catalog = sources.get_catalog_by_content_type("application/x-protobuf");
+        assert_eq!(catalog.keys().collect::<Vec<_>>(), vec!["mvt1", "mvt2"]);
+        let catalog = sources.get_catalog_by_content_type("image/png");
+        assert_eq!(catalog.keys().collect::<Vec<_>>(), vec!["png"]);
+        assert!(sources.get_catalog_by_content_type("image/webp").is_empty());
+
+        let catalog = sources.get_catalog_filtered(|entry| entry.content_encoding.is_some());
+        assert!(catalog.is_empty());
+        assert_eq!(sources.get_catalog().len(), 3);
+    }
+
+    /// Has the given tags, e.g. from its configuration
+    #[derive(Debug, Clone)]
+    struct TaggedSource(TestSource, Vec<String>);
+
+    #[async_trait]
+    impl Source for TaggedSource {
+        fn get_id(&self) -> &str {
+            self.0.get_id()
+        }
+
+        fn get_tilejson(&self) -> &TileJSON {
+            self.0.get_tilejson()
+        }
+
+        fn get_tile_info(&self) -> TileInfo {
+            self.0.get_tile_info()
+        }
+
+        fn clone_source(&self) -> TileInfoSource {
+            Box::new(self.clone())
+        }
+
+        async fn get_tile(
+            &self,
+            xyz: TileCoord,
+            url_query: Option<&UrlQuery>,
+        ) -> MartinResult<TileData> {
+            self.0.get_tile(xyz, url_query).await
+        }
+
+        fn tags(&self) -> &[String] {
+            &self.1
+        }
+    }
+
+    #[test]
+    fn catalog_with_tag() {
+        let tagged = |id: &'static str, tags: &[&str]| -> TileInfoSource {
+            Box::new(TaggedSource(
+                TestSource {
+                    id,
+                    tj: tilejson! { tiles: vec![] },
+                    info: TileInfo::new(Format::Mvt, Encoding::Uncompressed),
+                    data: Vec::default(),
+                },
+                tags.iter().map(ToString::to_string).collect(),
+            ))
+        };
+        let sources = TileSources::new(vec![vec![
+            tagged("streets", &["basemap"]),
+            tagged("hillshade", &["overlay", "experimental"]),
+            // wrappers keep the tags of their source
+            Box::new(TimeoutSource::new(
+                tagged("boundaries", &["overlay"]),
+                Duration::from_secs(1),
+            )),
+            test_source("untagged"),
+        ]]);
+
+        let catalog = sources.get_catalog();
+        assert_eq!(catalog["hillshade"].tags, ["overlay", "experimental"]);
+        assert!(catalog["untagged"].tags.is_empty());
+        let json = serde_json::to_value(&catalog["streets"]).unwrap();
+        assert_eq!(json["tags"], serde_json::json!(["basemap"]));
+        let json = serde_json::to_value(&catalog["untagged"]).unwrap();
+        assert!(json.get("tags").is_none());
+
+        let catalog = sources.get_catalog_with_tag("overlay");
+        assert_eq!(
+            catalog.keys().collect::<Vec<_>>(),
+            vec!["boundaries", "hillshade"]
+        );
+        let catalog = sources.get_catalog_with_tag("basemap");
+        assert_eq!(catalog.keys().collect::<Vec<_>>(), vec!["streets"]);
+        assert!(sources.get_catalog_with_tag("Overlay").is_empty());
+    }
+
+    #[actix_rt::test]
+    async fn tile_range() {
+        let xyz = TileCoord { z: 0, x: 0, y: 0 };
+        let src = TestSource {
+            id: "src",
+            tj: tilejson! { tiles: vec![] },
+            info: Format::Png.into(),
+            data: b"0123456789".to_vec(),
+        };
+        assert!(!src.supports_range());
+        let range = |r| src.get_tile_range(xyz, None, r);
+        assert_eq!(range(0..4).await.unwrap().data, b"0123");
+        assert_eq!(range(8..20).await.unwrap().data, b"89");
+        assert!(range(10..10).await.unwrap().data.is_empty());
+
+        let err = range(11..20).await.unwrap_err();
+        assert!(matches!(err, MartinError::SourceError(InvalidRange(_, 10))));
+        assert_eq!(
+            InvalidRange(11..20, 10).status_code(),
+            StatusCode::RANGE_NOT_SATISFIABLE
+        );
+    }
+
+    #[actix_rt::test]
+    async fn health_check_all() {
+        let sources = TileSources::new(vec![vec![test_source("a"), test_source("b")]]);
+        let health = sources.health_check_all().await;
+        assert_eq!(health.keys().collect::<Vec<_>>(), vec!["a", "b"]);
+        assert!(health.values().all(Result::is_ok));
+    }
+
+    #[test]
+    fn ids_with_delimiter() {
+        let sources = TileSources::new(vec![vec![
+            test_source("a"),
+            test_source("b,c"),
+            test_source("d,"),
+        ]]);
+        assert_eq!(sources.validate_ids(), vec!["b,c", "d,"]);
+        assert!(sources.get_source("b,c").is_ok());
+        assert!(sources.get_sources("b,c", None).is_err());
+
+        let sources = TileSources::new(vec![vec![test_source("a")]]);
+        assert!(sources.validate_ids().is_empty());
+    }
+
+    #[test]
+    fn max_merge_sources() {
+        let ids = ["s0", "s1", "s2", "s3", "s4", "s5", "s6", "s7", "s8"];
+        let sources = TileSources::new(vec![ids.into_iter().map(test_source).collect()]);
+        let ids = ids.join(",");
+
+        let (srcs, ..) = sources.get_sources(&ids, None).unwrap();
+        assert_eq!(srcs.len(), 9);
+        let all_ids = [ids.as_str(); 2].join(",");
+        assert!(sources.get_sources(&all_ids, None).is_err());
+
+        let sources = sources.max_merge_sources(2);
+        assert!(sources.get_sources("s0,s1", None).is_ok());
+        let err = sources.get_sources("s0,s1,s2", None).err().unwrap();
+        assert_eq!(
+            err.as_response_error().status_code(),
+            StatusCode::BAD_REQUEST
+        );
+        assert!(matches!(
+            err.as_error::<SourceError>(),
+            Some(TooManySources { count: 3, max: 2 })
+        ));
+    }
+
+    #[test]
+    fn sources_from_ids() {
+        let sources = TileSources::new(vec![vec![test_source("a"), test_source("b")]]);
+        let (srcs, use_url_query, info) = sources.get_sources_from_ids(&["b", "a"], None).unwrap();
+        assert_eq!(
+            srcs.iter().map(|s| s.get_id()).collect::<Vec<_>>(),
+            vec!["b", "a"]
+        );
+        assert!(!use_url_query);
+        assert_eq!(info, TileInfo::new(Format::Mvt, Encoding::Uncompressed));
+
+        let (srcs, ..) = sources.get_sources("b,a", None).unwrap();
+        assert_eq!(srcs.len(), 2);
+        assert!(sources.get_sources_from_ids(&["a", "c"], None).is_err());
+        // IDs are not split again, so a delimiter is part of the ID
+        assert!(sources.get_sources_from_ids(&["a,b"], None).is_err());
+    }
+
+    #[test]
+    fn skipped_sources() {
+        let low = TestSource {
+            id: "low",
+            tj: tilejson! { tiles: vec![], minzoom: 0, maxzoom: 5 },
+            info: TileInfo::new(Format::Mvt, Encoding::Uncompressed),
+            data: Vec::default(),
+        };
+        let sources = TileSources::new(vec![vec![test_source("any"), Box::new(low)]]);
+
+        let merged = sources.get_sources_detailed("low,any", Some(3)).unwrap();
+        assert_eq!(merged.sources.len(), 2);
+        assert!(merged.skipped.is_empty());
+        assert_eq!(merged.included, vec!["low", "any"]);
+
+        let merged = sources.get_sources_detailed("low,any", Some(14)).unwrap();
+        assert_eq!(merged.sources.len(), 1);
+        assert_eq!(merged.sources[0].get_id(), "any");
+        assert_eq!(merged.skipped, vec!["low".to_string()]);
+        assert_eq!(merged.included, vec!["any"]);
+
+        let merged = sources
+            .get_sources_detailed("any,low,any", Some(14))
+            .unwrap();
+        assert_eq!(merged.skipped, vec!["low"]);
+        assert_eq!(merged.included, vec!["any", "any"]);
+    }
+
+    #[test]
+    fn merged_sources() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counted = CountingSource(
+            TestSource {
+                id: "query",
+                tj: tilejson! { tiles: vec![] },
+                info: TileInfo::new(Format::Mvt, Encoding::Uncompressed),
+                data: Vec::default(),
+            },
+            calls,
+        );
+        let sources = TileSources::new(vec![vec![test_source("plain"), Box::new(counted)]]);
+
+        let merged = sources.get_sources_detailed("plain", None).unwrap();
+        assert!(!merged.use_url_query);
+        assert_eq!(
+            merged.info,
+            TileInfo::new(Format::Mvt, Encoding::Uncompressed)
+        );
+        let merged = sources.get_sources_detailed("plain,query", None).unwrap();
+        assert!(merged.use_url_query);
+        assert_eq!(merged.included, vec!["plain", "query"]);
+        let ids = merged
+            .sources
+            .iter()
+            .map(|s| s.get_id())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, merged.included);
+    }
+
+    #[test]
+    fn single_survivor() {
+        let low = TestSource {
+            id: "low",
+            tj: tilejson! { tiles: vec![], minzoom: 0, maxzoom: 5 },
+            info: TileInfo::new(Format::Mvt, Encoding::Uncompressed),
+            data: Vec::default(),
+        };
+        let gzip = TestSource {
+            id: "gzip",
+            tj: tilejson! { tiles: vec![] },
+            info: TileInfo::new(Format::Mvt, Encoding::Gzip),
+            data: Vec::default(),
+        };
+        let sources = TileSources::new(vec![vec![
+            test_source("any"),
+            Box::new(low),
+            Box::new(gzip),
+        ]]);
+
+        // differently encoded tiles must be merged uncompressed
+        let merged = sources.get_sources_detailed("low,gzip", Some(3)).unwrap();
+        assert_eq!(merged.sources.len(), 2);
+        assert_eq!(
+            merged.info,
+            TileInfo::new(Format::Mvt, Encoding::Uncompressed)
+        );
+        let merged = sources
+            .get_sources_detailed("low,any,gzip", Some(14))
+            .unwrap();
+        assert_eq!(merged.sources.len(), 2);
+        assert_eq!(
+            merged.info,
+            TileInfo::new(Format::Mvt, Encoding::Uncompressed)
+        );
+
+        // but a single remaining tile can be served as is
+        let merged = sources.get_sources_detailed("low,gzip", Some(14)).unwrap();
+        assert_eq!(merged.sources.len(), 1);
+        assert_eq!(merged.included, vec!["gzip"]);
+        assert_eq!(merged.info, TileInfo::new(Format::Mvt, Encoding::Gzip));
+        let (_, _, info) = sources.get_sources("low,gzip", Some(14)).unwrap();
+        assert_eq!(info, TileInfo::new(Format::Mvt, Encoding::Gzip));
+    }
+
+    #[test]
+    fn fetch_count() {
+        let low = TestSource {
+            id: "low",
+            tj: tilejson! { tiles: vec![], minzoom: 0, maxzoom: 5 },
+            info: TileInfo::new(Format::Mvt, Encoding::Uncompressed),
+            data: Vec::default(),
+        };
+        let sources = TileSources::new(vec![vec![test_source("any"), Box::new(low)]]);
+
+        assert_eq!(sources.estimate_fetch_count("any", None).unwrap(), 1);
+        assert_eq!(sources.estimate_fetch_count("low,any", None).unwrap(), 2);
+        assert_eq!(sources.estimate_fetch_count("low,any", Some(3)).unwrap(), 2);
+        assert_eq!(
+            sources.estimate_fetch_count("low,any", Some(14)).unwrap(),
+            1
+        );
+        assert_eq!(sources.estimate_fetch_count("low", Some(14)).unwrap(), 0);
+        assert!(sources.estimate_fetch_count("low,missing", None).is_err());
+    }
+
+    #[actix_rt::test]
+    async fn single_source_tile() {
+        let low = TestSource {
+            id: "low",
+            tj: tilejson! { tiles: vec![], minzoom: 0, maxzoom: 5 },
+            info: TileInfo::new(Format::Mvt, Encoding::Gzip),
+            data: vec![1_u8, 2],
+        };
+        let sources = TileSources::new(vec![vec![test_source("any"), Box::new(low)]]);
+        let xyz = TileCoord { z: 3, x: 1, y: 2 };
+
+        let (tile, info) = sources
+            .get_single_source_tile("low", xyz, None, Some(3))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(tile.data, vec![1_u8, 2]);
+        assert_eq!(info, TileInfo::new(Format::Mvt, Encoding::Gzip));
+        let tile = sources.get_single_source_tile("low", xyz, None, None).await;
+        assert!(tile.unwrap().is_some());
+
+        // same zoom filtering as get_sources
+        let (srcs, ..) = sources.get_sources("low", Some(14)).unwrap();
+        assert!(srcs.is_empty());
+        let tile = sources
+            .get_single_source_tile("low", xyz, None, Some(14))
+            .await;
+        assert!(tile.unwrap().is_none());
+
+        // zoom validation can be bypassed
+        let (srcs, ..) = sources
+            .get_sources_with_zoom_check("low", Some(14), true)
+            .unwrap();
+        assert!(srcs.is_empty());
+        let (srcs, ..) = sources
+            .get_sources_with_zoom_check("any,low", Some(14), false)
+            .unwrap();
+        assert_eq!(srcs.len(), 2);
+        assert_eq!(srcs[1].get_id(), "low");
+
+        assert!(sources
+            .get_single_source_tile("missing", xyz, None, None)
+            .await
+            .is_err());
+        assert!(sources
+            .get_single_source_tile("low,any", xyz, None, None)
+            .await
+            .is_err());
+    }
+
+    #[test]
+    fn merged_tile_encoding() {
+        let raw = Tile::new(
+            vec![1_u8, 2],
+            TileInfo::new(Format::Mvt, Encoding::Uncompressed),
+        );
+        let gzip = Tile::new(
+            encode_gzip(&[3_u8]).unwrap(),
+            TileInfo::new(Format::Mvt, Encoding::Gzip),
+        );
+        let brotli = Tile::new(
+            encode_brotli(&[4_u8]).unwrap(),
+            TileInfo::new(Format::Mvt, Encoding::Brotli),
+        );
+        let empty = Tile::new(Vec::new(), TileInfo::new(Format::Mvt, Encoding::Gzip));
+        let tiles = vec![raw, empty.clone(), gzip, brotli];
+
+        let tile = encode_merged(tiles.clone(), Encoding::Uncompressed).unwrap();
+        assert_eq!(
+            tile.info,
+            TileInfo::new(Format::Mvt, Encoding::Uncompressed)
+        );
+        assert_eq!(tile.data, vec![1_u8, 2, 3, 4]);
+
+        let tile = encode_merged(tiles.clone(), Encoding::Gzip).unwrap();
+        assert_eq!(tile.info, TileInfo::new(Format::Mvt, Encoding::Gzip));
+        assert_eq!(decode_gzip(&tile.data).unwrap(), vec![1_u8, 2, 3, 4]);
+
+        assert!(matches!(
+            encode_merged(tiles, Encoding::Zstd),
+            Err(UnsupportedEncoding(..))
+        ));
+        let tile = encode_merged(vec![empty], Encoding::Gzip).unwrap();
+        assert!(tile.data.is_empty());
+        assert_eq!(tile.info, Format::Mvt.into());
+        let png = Tile::new(vec![5_u8], Format::Png.into());
+        assert!(matches!(
+            encode_merged(vec![png], Encoding::Gzip),
+            Err(CannotConcatenate(Format::Png))
+        ));
+    }
+
+    #[test]
+    fn empty_tiles() {
+        let tile = empty_tile(TileInfo::new(Format::Png, Encoding::Internal));
+        assert_eq!(TileInfo::detect(&tile.data), Some(Format::Png.into()));
+        assert_eq!(tile.info, Format::Png.into());
+
+        let tile = empty_tile(TileInfo::new(Format::Mvt, Encoding::Gzip));
+        assert!(tile.data.is_empty());
+        assert_eq!(
+            tile.info,
+            TileInfo::new(Format::Mvt, Encoding::Uncompressed)
+        );
+    }
+
+    #[test]
+    fn composite_tilejson() {
+        let src = |id, tj| {
+            Box::new(TestSource {
+                id,
+                tj,
+                info: TileInfo::new(Format::Mvt, Encoding::Uncompressed),
+                data: Vec::default(),
+            })
+        };
+        let sources = TileSources::new(vec![vec![
+            src(
+                "a",
+                tilejson! { tiles: vec![], minzoom: 2, maxzoom: 8, attribution: "A".to_string() },
+            ),
+            src(
+                "b",
+                tilejson! { tiles: vec![], minzoom: 4, maxzoom: 12, attribution: "B".to_string() },
+            ),
+            Box::new(TestSource {
+                id: "png",
+                tj: tilejson! { tiles: vec![] },
+                info: Format::Png.into(),
+                data: Vec::default(),
+            }),
+        ]]);
+
+        let tj = sources.get_composite_tilejson("a,b").unwrap();
+        assert!(tj.tiles.is_empty());
+        assert_eq!((tj.minzoom, tj.maxzoom), (Some(2), Some(12)));
+        assert_eq!(tj.attribution, Some("A\nB".to_string()));
+
+        let tj = sources.get_composite_tilejson("b").unwrap();
+        assert!(tj.tiles.is_empty());
+        assert_eq!(tj.attribution, Some("B".to_string()));
+
+        let err = sources.get_composite_tilejson("a,png").unwrap_err();
+        assert_eq!(
+            err.as_response_error().status_code(),
+            StatusCode::BAD_REQUEST
+        );
+    }
+
+    #[test]
+    fn merge_format_mismatch() {
+        let png = TestSource {
+            id: "png",
+            tj: tilejson! { tiles: vec![] },
+            info: Format::Png.into(),
+            data: Vec::default(),
+        };
+        let sources = TileSources::new(vec![vec![
+            test_source("mvt"),
+            test_source("mvt2"),
+            Box::new(png),
+        ]]);
+
+        let err = sources.get_sources("mvt,mvt2,png", None).err().unwrap();
+        assert_eq!(
+            err.as_response_error().status_code(),
+            StatusCode::BAD_REQUEST
+        );
+        let Some(MergeFormatMismatch {
+            left_id,
+            left,
+            right_id,
+            right,
+        }) = err.as_error::<SourceError>()
+        else {
+            panic!("unexpected error {err:?}");
+        };
+        assert_eq!((left_id.as_str(), left.format), ("mvt", Format::Mvt));
+        assert_eq!((right_id.as_str(), right.format), ("png", Format::Png));
+        let msg = err.to_string();
+        assert!(
+            msg.contains("source mvt ") && msg.contains("source png "),
+            "{msg}"
+        );
+
+        let err = sources.get_sources("mvt,missing", None).err().unwrap();
+        assert_eq!(err.as_response_error().status_code(), StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn resolve_merged_info() {
+        let png = TestSource {
+            id: "png",
+            tj: tilejson! { tiles: vec![] },
+            info: Format::Png.into(),
+            data: Vec::default(),
+        };
+        let gzip = TestSource {
+            id: "gzip",
+            tj: tilejson! { tiles: vec![], minzoom: 5 },
+            info: TileInfo::new(Format::Mvt, Encoding::Gzip),
+            data: Vec::default(),
+        };
+        let sources = TileSources::new(vec![vec![
+            test_source("mvt"),
+            Box::new(gzip),
+            Box::new(png),
+        ]]);
+
+        let info = sources.resolve_merged_info("gzip").unwrap();
+        assert_eq!(info, TileInfo::new(Format::Mvt, Encoding::Gzip));
+        // sources are not filtered by zoom, so the encodings are merged
+        let info = sources.resolve_merged_info("mvt,gzip").unwrap();
+        assert_eq!(info, TileInfo::new(Format::Mvt, Encoding::Uncompressed));
+
+        let err = sources.resolve_merged_info("mvt,png").err().unwrap();
+        assert!(matches!(
+            err.as_error::<SourceError>(),
+            Some(MergeFormatMismatch { .. })
+        ));
+        assert!(sources.resolve_merged_info("mvt,missing").is_err());
+    }
+
+    #[test]
+    fn zoom_check() {
+        let src = TestSource {
+            id: "bounded",
+            tj: tilejson! { tiles: vec![], minzoom: 2, maxzoom: 5 },
+            info: TileInfo::new(Format::Mvt, Encoding::Uncompressed),
+            data: Vec::default(),
+        };
+        assert_eq!(src.zoom_check(1), ZoomCheck::BelowMin(2));
+        assert_eq!(src.zoom_check(2), ZoomCheck::Ok);
+        assert_eq!(src.zoom_check(5), ZoomCheck::Ok);
+        assert_eq!(src.zoom_check(6), ZoomCheck::AboveMax(5));
+        assert!(!TileSources::check_zoom(&src, "bounded", 6));
+        assert_eq!(
+            TileSources::check_zoom_detailed(&src, "bounded", 0),
+            ZoomCheck::BelowMin(2)
+        );
+    }
+
+    #[derive(Debug, Clone)]
+    pub(super) struct SparseZoomSource(pub(super) TestSource, pub(super) Vec<u8>);
+
+    #[async_trait]
+    impl Source for SparseZoomSource {
+        fn get_id(&self) -> &str {
+            self.0.get_id()
+        }
+
+        fn get_tilejson(&self) -> &TileJSON {
+            self.0.get_tilejson()
+        }
+
+        fn get_tile_info(&self) -> TileInfo {
+            self.0.get_tile_info()
+        }
+
+        fn clone_source(&self) -> TileInfoSource {
+            Box::new(self.clone())
+        }
+
+        async fn get_tile(
+            &self,
+            xyz: TileCoord,
+            url_query: Option<&UrlQuery>,
+        ) -> MartinResult<TileData> {
+            self.0.get_tile(xyz, url_query).await
+        }
+
+        fn available_zooms(&self) -> Option<&[u8]> {
+            Some(&self.1)
+        }
+    }
+
+    #[test]
+    fn sparse_zooms() {
+        let src = TestSource {
+            id: "sparse",
+            tj: tilejson! { tiles: vec![], minzoom: 0, maxzoom: 10 },
+            info: TileInfo::new(Format::Mvt, Encoding::Uncompressed),
+            data: Vec::default(),
+        };
+        assert!(src.is_valid_zoom(3));
+        assert!(!src.is_valid_zoom(11));
+
+        assert_eq!(src.zoom_check(3), ZoomCheck::Ok);
+        assert_eq!(src.zoom_check(11), ZoomCheck::AboveMax(10));
+
+        let src = SparseZoomSource(src, vec![0, 5, 10]);
+        assert_eq!(src.zoom_check(3), ZoomCheck::NotAvailable);
+        assert!(src.is_valid_zoom(0));
+        assert!(src.is_valid_zoom(5));
+        assert!(src.is_valid_zoom(10));
+        assert!(!src.is_valid_zoom(3));
+        assert!(!src.is_valid_zoom(11));
+    }
+
+    /// Declares zooms 0 to 10, but only has tiles at even zooms
+    #[derive(Debug, Clone)]
+    struct EvenZoomSource(TestSource);
+
+    #[async_trait]
+    impl Source for EvenZoomSource {
+        fn get_id(&self) -> &str {
+            self.0.get_id()
+        }
+
+        fn get_tilejson(&self) -> &TileJSON {
+            self.0.get_tilejson()
+        }
+
+        fn get_tile_info(&self) -> TileInfo {
+            self.0.get_tile_info()
+        }
+
+        fn clone_source(&self) -> TileInfoSource {
+            Box::new(self.clone())
+        }
+
+        async fn get_tile(
+            &self,
+            xyz: TileCoord,
+            url_query: Option<&UrlQuery>,
+        ) -> MartinResult<TileData> {
+            self.0.get_tile(xyz, url_query).await
+        }
+
+        fn is_valid_zoom(&self, zoom: u8) -> bool {
+            zoom % 2 == 0 && self.zoom_check(zoom) == ZoomCheck::Ok
+        }
+    }
+
+    #[actix_rt::test]
+    async fn is_valid_zoom_override() {
+        let src = EvenZoomSource(TestSource {
+            id: "even",
+            tj: tilejson! { tiles: vec![], minzoom: 0, maxzoom: 10 },
+            info: TileInfo::new(Format::Mvt, Encoding::Uncompressed),
+            data: vec![1_u8],
+        });
+        assert_eq!(src.zoom_check(3), ZoomCheck::Ok);
+        assert!(!src.is_valid_zoom(3));
+        assert!(TileSources::check_zoom(&src, "even", 4));
+        assert!(!TileSources::check_zoom(&src, "even", 3));
+        assert_eq!(
+            TileSources::check_zoom_detailed(&src, "even", 3),
+            ZoomCheck::NotAvailable
+        );
+        assert_eq!(
+            TileSources::check_zoom_detailed(&src, "even", 12),
+            ZoomCheck::AboveMax(10)
+        );
+
+        let sources = TileSources::new(vec![vec![Box::new(src)]]);
+        let tile =
+            |z| sources.get_single_source_tile("even", TileCoord { z, x: 0, y: 0 }, None, Some(z));
+        assert_eq!(tile(4).await.unwrap().unwrap().0.data, vec![1_u8]);
+        assert!(tile(3).await.unwrap().is_none());
+    }
+
+    #[test]
+    fn catalog_entry_zoom_serialization() {
+        let entry = CatalogSourceEntry {
+            content_type: "application/x-protobuf".to_string(),
+            ..Default::default()
+        };
+        let json = serde_json::to_value(&entry).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({ "content_type": "application/x-protobuf" })
+        );
+
+        let entry = CatalogSourceEntry {
+            minzoom: Some(0),
+            maxzoom: Some(14),
+            ..entry
+        };
+        let json = serde_json::to_value(&entry).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "content_type": "application/x-protobuf",
+                "minzoom": 0,
+                "maxzoom": 14,
+            })
+        );
+        let parsed: CatalogSourceEntry = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed, entry);
+    }
+
+    /// Reports high resolution tiles
+    #[derive(Debug, Clone)]
+    pub(super) struct RetinaSource(pub(super) TestSource);
+
+    #[async_trait]
+    impl Source for RetinaSource {
+        fn get_id(&self) -> &str {
+            self.0.get_id()
+        }
+
+        fn get_tilejson(&self) -> &TileJSON {
+            self.0.get_tilejson()
+        }
+
+        fn get_tile_info(&self) -> TileInfo {
+            self.0.get_tile_info()
+        }
+
+        fn clone_source(&self) -> TileInfoSource {
+            Box::new(self.clone())
+        }
+
+        async fn get_tile(
+            &self,
+            xyz: TileCoord,
+            url_query: Option<&UrlQuery>,
+        ) -> MartinResult<TileData> {
+            self.0.get_tile(xyz, url_query).await
+        }
+
+        fn tile_size(&self) -> u32 {
+            512
+        }
+    }
+
+    #[test]
+    fn catalog_entry_tile_size() {
+        let src = TestSource {
+            id: "src",
+            tj: tilejson! { tiles: vec![] },
+            info: TileInfo::from(Format::Png),
+            data: Vec::default(),
+        };
+        assert_eq!(src.tile_size(), DEFAULT_TILE_SIZE);
+        let entry = src.get_catalog_entry();
+        assert_eq!(entry.tile_size, None);
+        assert_eq!(
+            serde_json::to_value(&entry).unwrap(),
+            serde_json::json!({ "content_type": "image/png" })
+        );
+
+        let src = RetinaSource(src);
+        let entry = src.get_catalog_entry();
+        assert_eq!(entry.tile_size, Some(512));
+        let json = serde_json::to_value(&entry).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({ "content_type": "image/png", "tile_size": 512 })
+        );
+        let parsed: CatalogSourceEntry = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed, entry);
+
+        let wrapped = TimeoutSource::new(Box::new(src), Duration::from_secs(1));
+        assert_eq!(wrapped.tile_size(), 512);
+    }
+
+    /// Uses geographic coordinates
+    #[derive(Debug, Clone)]
+    struct GeographicSource(TestSource);
+
+    #[async_trait]
+    impl Source for GeographicSource {
+        fn get_id(&self) -> &str {
+            self.0.get_id()
+        }
+
+        fn get_tilejson(&self) -> &TileJSON {
+            self.0.get_tilejson()
+        }
+
+        fn get_tile_info(&self) -> TileInfo {
+            self.0.get_tile_info()
+        }
+
+        fn clone_source(&self) -> TileInfoSource {
+            Box::new(self.clone())
+        }
+
+        async fn get_tile(
+            &self,
+            xyz: TileCoord,
+            url_query: Option<&UrlQuery>,
+        ) -> MartinResult<TileData> {
+            self.0.get_tile(xyz, url_query).await
+        }
+
+        fn crs(&self) -> &'static str {
+            "EPSG:4326"
+        }
+    }
+
+    #[test]
+    fn source_crs() {
+        let geo = |id| {
+            GeographicSource(TestSource {
+                id,
+                tj: tilejson! { tiles: vec![] },
+                info: TileInfo::new(Format::Mvt, Encoding::Uncompressed),
+                data: Vec::default(),
+            })
+        };
+        assert_eq!(test_source("web").crs(), DEFAULT_CRS);
+        assert_eq!(test_source("web").get_catalog_entry().crs, None);
+        let entry = geo("geo").get_catalog_entry();
+        assert_eq!(entry.crs.as_deref(), Some("EPSG:4326"));
+        let json = serde_json::to_value(&entry).unwrap();
+        assert_eq!(json["crs"], "EPSG:4326");
+
+        let sources = TileSources::new(vec![vec![
+            test_source("web"),
+            Box::new(geo("geo")),
+            Box::new(geo("geo2")),
+        ]]);
+        assert!(sources.get_sources("geo,geo2", None).is_ok());
+        for ids in ["web,geo", "geo,web"] {
+            let err = sources.get_sources(ids, None).err().unwrap();
+            assert_eq!(err.error_response().status(), StatusCode::BAD_REQUEST);
+        }
+        let err = sources.get_sources("geo,geo2,web", None).err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            "Cannot merge source geo (EPSG:4326) with source web (EPSG:3857), because their coordinate reference systems differ"
+        );
+    }
+
+    #[test]
+    fn available_encodings() {
+        let src = TestSource {
+            id: "src",
+            tj: tilejson! { tiles: vec![] },
+            info: TileInfo::new(Format::Mvt, Encoding::Gzip),
+            data: Vec::default(),
+        };
+        assert_eq!(src.available_encodings(), vec![Encoding::Gzip]);
+        assert!(src.get_catalog_entry().available_encodings.is_none());
+
+        let src = TranscodeSource::new(Box::new(src), Encoding::Brotli).unwrap();
+        assert_eq!(src.available_encodings(), vec![Encoding::Brotli]);
+    }
+
+    #[test]
+    fn catalog_entry_other() {
+        let mut tj = tilejson! { tiles: vec![] };
+        tj.other.insert(
+            "license_url".to_string(),
+            serde_json::json!("https://example.com"),
+        );
+        let src = TestSource {
+            id: "src",
+            tj,
+            info: TileInfo::new(Format::Mvt, Encoding::Uncompressed),
+            data: Vec::default(),
+        };
+        let entry = src.get_catalog_entry();
+        assert_eq!(
+            serde_json::to_value(&entry).unwrap(),
+            serde_json::json!({
+                "content_type": "application/x-protobuf",
+                "other": { "license_url": "https://example.com" },
+            })
+        );
+    }
+}
@@ -0,0 +1,127 @@
+use std::fmt::{Debug, Formatter};
+use std::sync::Arc;
+
+use martin_tile_utils::{TileCoord, TileOffset};
+
+use crate::source::delegate::delegate_source;
+use crate::source::{Tile, TileData, TileInfoSource, UrlQuery};
+use crate::MartinResult;
+
+/// A function that cuts out the part of an ancestor tile at the given offset,
+/// and scales it to a full tile, see [`OverzoomSource`]
+pub type TileClip = Arc<dyn Fn(Tile, TileOffset) -> MartinResult<Tile> + Send + Sync>;
+
+/// Wraps a source to serve zooms above its maxzoom, up to `max_overzoom`.
+/// Such tiles are clipped from their ancestor tile at the maxzoom. Clipping is format specific,
+/// e.g. scaling a part of a raster image, or transforming vector tile coordinates.
+#[derive(Clone)]
+pub struct OverzoomSource {
+    source: TileInfoSource,
+    max_overzoom: u8,
+    clip: TileClip,
+}
+
+impl OverzoomSource {
+    #[must_use]
+    pub fn new(source: TileInfoSource, max_overzoom: u8, clip: TileClip) -> Self {
+        Self {
+            source,
+            max_overzoom,
+            clip,
+        }
+    }
+}
+
+impl Debug for OverzoomSource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "OverzoomSource {{ source: {:?}, max_overzoom: {} }}",
+            self.source, self.max_overzoom
+        )
+    }
+}
+
+delegate_source! {
+    impl Source for OverzoomSource => self.source;
+    except [
+        get_tile, get_tile_with_info, has_tile, get_tile_etag, covers,
+        max_overzoom, available_zooms, is_valid_zoom, zoom_check,
+        get_tile_opt, get_tile_decoded, supports_range, get_tile_range, get_tiles_batch, prewarm,
+        supported_formats, can_encode, get_tile_as, transcode_tile,
+    ];
+
+    async fn get_tile(
+        &self,
+        xyz: TileCoord,
+        url_query: Option<&UrlQuery>,
+    ) -> MartinResult<TileData> {
+        Ok(self.get_tile_with_info(xyz, url_query).await?.data)
+    }
+
+    async fn get_tile_with_info(
+        &self,
+        xyz: TileCoord,
+        url_query: Option<&UrlQuery>,
+    ) -> MartinResult<Tile> {
+        let Some(maxzoom) = self.get_tilejson().maxzoom.filter(|max| xyz.z > *max) else {
+            return self.source.get_tile_with_info(xyz, url_query).await;
+        };
+        let ancestor = xyz.ancestor(maxzoom).filter(|_| xyz.z <= self.max_overzoom);
+        let Some((ancestor, offset)) = ancestor else {
+            return Ok(Tile::new(Vec::new(), self.get_tile_info()));
+        };
+        let tile = self.source.get_tile_with_info(ancestor, url_query).await?;
+        if tile.data.is_empty() {
+            Ok(tile)
+        } else {
+            (self.clip)(tile, offset)
+        }
+    }
+
+    fn max_overzoom(&self) -> Option<u8> {
+        Some(self.max_overzoom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use martin_tile_utils::{Encoding, Format, TileInfo};
+    use tilejson::tilejson;
+
+    use super::*;
+    use crate::source::{Source, ZoomCheck};
+    use crate::TestSource;
+
+    #[actix_rt::test]
+    async fn overzoom_source() {
+        let src = Box::new(TestSource {
+            id: "src",
+            tj: tilejson! { tiles: vec![], minzoom: 0, maxzoom: 2 },
+            info: TileInfo::new(Format::Json, Encoding::Uncompressed),
+            data: b"tile".to_vec(),
+        });
+        assert!(!src.is_valid_zoom(3));
+
+        let clip: TileClip = Arc::new(|tile: Tile, offset: TileOffset| {
+            let data = format!(
+                "{}@{},{}/{}",
+                String::from_utf8(tile.data).unwrap(),
+                offset.x,
+                offset.y,
+                offset.scale
+            );
+            Ok(Tile::new(data.into_bytes(), tile.info))
+        });
+        let src = OverzoomSource::new(src, 4, clip);
+        assert!(src.is_valid_zoom(2));
+        assert!(src.is_valid_zoom(4));
+        assert_eq!(src.zoom_check(5), ZoomCheck::AboveMax(4));
+
+        let tile = |z, x, y| src.get_tile(TileCoord { z, x, y }, None);
+        assert_eq!(tile(2, 1, 1).await.unwrap(), b"tile");
+        assert_eq!(tile(3, 5, 2).await.unwrap(), b"tile@1,0/2");
+        assert_eq!(tile(4, 7, 9).await.unwrap(), b"tile@3,1/4");
+        assert!(tile(5, 0, 0).await.unwrap().is_empty());
+    }
+}
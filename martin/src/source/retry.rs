@@ -0,0 +1,168 @@
+use std::fmt::{Debug, Formatter};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use log::debug;
+use martin_tile_utils::{Format, TileCoord};
+
+use crate::source::delegate::delegate_source;
+use crate::source::SourceError::Timeout;
+use crate::source::{Source, Tile, TileData, TileInfoSource, UrlQuery};
+use crate::{MartinError, MartinResult};
+
+/// How [`RetrySource`] retries failed tile requests
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first one
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled after each failed attempt
+    pub backoff: Duration,
+}
+
+/// Wraps a source to retry failed tile requests, e.g. for network-backed sources with
+/// transient failures. Errors caused by the request itself, i.e. all [`SourceError`](crate::SourceError)s
+/// except [`SourceError::Timeout`](crate::SourceError::Timeout), are returned without retrying.
+#[derive(Clone)]
+pub struct RetrySource {
+    source: TileInfoSource,
+    policy: Arc<RetryPolicy>,
+}
+
+impl RetrySource {
+    #[must_use]
+    pub fn new(source: TileInfoSource, policy: RetryPolicy) -> Self {
+        Self {
+            source,
+            policy: Arc::new(policy),
+        }
+    }
+
+    fn is_retryable(error: &MartinError) -> bool {
+        !matches!(error, MartinError::SourceError(e) if !matches!(e, Timeout(..)))
+    }
+
+    async fn with_retry<T, F>(
+        &self,
+        xyz: TileCoord,
+        mut request: impl FnMut() -> F + Send,
+    ) -> MartinResult<T>
+    where
+        F: Future<Output = MartinResult<T>> + Send,
+    {
+        let mut delay = self.policy.backoff;
+        let mut attempt = 1;
+        loop {
+            match request().await {
+                Err(e) if attempt < self.policy.max_attempts && Self::is_retryable(&e) => {
+                    debug!(
+                        "Retrying tile {xyz} of source {} in {delay:?} after attempt {attempt} failed: {e}",
+                        self.get_id()
+                    );
+                }
+                result => return result,
+            }
+            // the error is not kept across the await, because it is not always `Send`
+            tokio::time::sleep(delay).await;
+            delay = delay.saturating_mul(2);
+            attempt += 1;
+        }
+    }
+}
+
+impl Debug for RetrySource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "RetrySource {{ source: {:?}, policy: {:?} }}",
+            self.source, self.policy
+        )
+    }
+}
+
+delegate_source! {
+    impl Source for RetrySource => self.source;
+    except [
+        get_tile, get_tile_with_info, get_tile_as,
+        get_tile_opt, get_tile_decoded, supports_range, get_tile_range, get_tiles_batch, prewarm,
+        transcode_tile,
+    ];
+
+    async fn get_tile(
+        &self,
+        xyz: TileCoord,
+        url_query: Option<&UrlQuery>,
+    ) -> MartinResult<TileData> {
+        self.with_retry(xyz, || self.source.get_tile(xyz, url_query)).await
+    }
+
+    async fn get_tile_with_info(
+        &self,
+        xyz: TileCoord,
+        url_query: Option<&UrlQuery>,
+    ) -> MartinResult<Tile> {
+        self.with_retry(xyz, || self.source.get_tile_with_info(xyz, url_query)).await
+    }
+
+    async fn get_tile_as(
+        &self,
+        xyz: TileCoord,
+        url_query: Option<&UrlQuery>,
+        format: Format,
+    ) -> MartinResult<Tile> {
+        self.with_retry(xyz, || self.source.get_tile_as(xyz, url_query, format)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use martin_tile_utils::{Encoding, TileInfo};
+    use tilejson::tilejson;
+
+    use super::*;
+    use crate::source::tests::FlakySource;
+    use crate::source::SourceError::InvalidRange;
+    use crate::TestSource;
+
+    #[actix_rt::test]
+    async fn retry_source() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let flaky = |failures, terminal| {
+            calls.store(0, Ordering::Relaxed);
+            Box::new(FlakySource {
+                inner: TestSource {
+                    id: "flaky",
+                    tj: tilejson! { tiles: vec![] },
+                    info: TileInfo::new(Format::Mvt, Encoding::Uncompressed),
+                    data: vec![1_u8, 2],
+                },
+                calls: calls.clone(),
+                failures,
+                terminal,
+            })
+        };
+        let policy = RetryPolicy {
+            max_attempts: 3,
+            backoff: Duration::from_millis(1),
+        };
+        let xyz = TileCoord { z: 0, x: 0, y: 0 };
+
+        let src = RetrySource::new(flaky(2, false), policy);
+        assert_eq!(src.get_id(), "flaky");
+        let tile = src.clone_source().get_tile(xyz, None).await.unwrap();
+        assert_eq!(tile, vec![1_u8, 2]);
+        assert_eq!(calls.load(Ordering::Relaxed), 3);
+
+        let src = RetrySource::new(flaky(3, false), policy);
+        let err = src.get_tile_with_info(xyz, None).await.unwrap_err();
+        assert!(matches!(err, MartinError::IoError(..)));
+        assert_eq!(calls.load(Ordering::Relaxed), 3);
+
+        let src = RetrySource::new(flaky(1, true), policy);
+        let err = src.get_tile(xyz, None).await.unwrap_err();
+        assert!(matches!(err, MartinError::SourceError(InvalidRange(..))));
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+    }
+}
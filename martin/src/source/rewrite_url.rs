@@ -0,0 +1,120 @@
+use std::fmt::{Debug, Formatter};
+
+use tilejson::TileJSON;
+
+use crate::source::delegate::delegate_source;
+use crate::source::TileInfoSource;
+
+/// Wraps a source to rewrite the `tiles` URLs of its `TileJSON`, e.g. when serving behind
+/// a reverse proxy with a path prefix. The scheme and host of each URL, if any, are replaced
+/// by the `base_url`, and its path is appended to it. Tiles are served unchanged.
+#[derive(Clone)]
+pub struct RewriteUrlSource {
+    source: TileInfoSource,
+    tilejson: TileJSON,
+}
+
+impl RewriteUrlSource {
+    /// The `base_url` can be a prefix like `/tiles`, or include the host, e.g. `https://example.com/tiles`
+    #[must_use]
+    pub fn new(source: TileInfoSource, base_url: &str) -> Self {
+        let mut tilejson = source.get_tilejson().clone();
+        for url in &mut tilejson.tiles {
+            *url = rewrite_tile_url(url, base_url);
+        }
+        Self { source, tilejson }
+    }
+}
+
+/// Replace the scheme and host of the URL with the `base_url`, keeping the path and query
+fn rewrite_tile_url(url: &str, base_url: &str) -> String {
+    let path = match url.split_once("://") {
+        Some((_, rest)) => rest.find('/').map_or("", |idx| &rest[idx..]),
+        None => url,
+    };
+    format!(
+        "{}/{}",
+        base_url.trim_end_matches('/'),
+        path.trim_start_matches('/')
+    )
+}
+
+impl Debug for RewriteUrlSource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "RewriteUrlSource {{ source: {:?}, tiles: {:?} }}",
+            self.source, self.tilejson.tiles
+        )
+    }
+}
+
+delegate_source! {
+    impl Source for RewriteUrlSource => self.source;
+    except [get_tilejson];
+
+    fn get_tilejson(&self) -> &TileJSON {
+        &self.tilejson
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use martin_tile_utils::{Encoding, Format, TileCoord, TileInfo};
+    use tilejson::tilejson;
+
+    use super::*;
+    use crate::source::tests::{RetinaSource, SparseZoomSource};
+    use crate::source::Source;
+    use crate::TestSource;
+
+    #[actix_rt::test]
+    async fn rewrite_url_source() {
+        let src = || -> TileInfoSource {
+            Box::new(TestSource {
+                id: "src",
+                tj: tilejson! {
+                    tiles: vec![
+                        "http://localhost:3000/src/{z}/{x}/{y}".to_string(),
+                        "/src/{z}/{x}/{y}?lang=en".to_string(),
+                    ]
+                },
+                info: TileInfo::new(Format::Mvt, Encoding::Uncompressed),
+                data: vec![1_u8],
+            })
+        };
+
+        let rewritten = RewriteUrlSource::new(src(), "https://example.com/tiles/");
+        assert_eq!(
+            rewritten.get_tilejson().tiles,
+            vec![
+                "https://example.com/tiles/src/{z}/{x}/{y}",
+                "https://example.com/tiles/src/{z}/{x}/{y}?lang=en",
+            ]
+        );
+        let rewritten = RewriteUrlSource::new(src(), "/proxy");
+        assert_eq!(
+            rewritten.get_tilejson().tiles,
+            vec!["/proxy/src/{z}/{x}/{y}", "/proxy/src/{z}/{x}/{y}?lang=en"]
+        );
+        let xyz = TileCoord { z: 0, x: 0, y: 0 };
+        assert_eq!(rewritten.get_tile(xyz, None).await.unwrap(), vec![1_u8]);
+    }
+
+    #[test]
+    fn rewrite_url_source_forwards() {
+        let tile_src = || TestSource {
+            id: "src",
+            tj: tilejson! { tiles: vec![] },
+            info: TileInfo::new(Format::Png, Encoding::Internal),
+            data: Vec::new(),
+        };
+        let retina = RewriteUrlSource::new(Box::new(RetinaSource(tile_src())), "/proxy");
+        assert_eq!(retina.tile_size(), 512);
+        let sparse =
+            RewriteUrlSource::new(Box::new(SparseZoomSource(tile_src(), vec![1, 3])), "/proxy");
+        assert_eq!(sparse.available_zooms(), Some([1_u8, 3].as_slice()));
+        assert!(!sparse.is_valid_zoom(2));
+        assert!(sparse.clone_source().is_valid_zoom(3));
+    }
+}
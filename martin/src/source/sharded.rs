@@ -0,0 +1,291 @@
+use std::fmt::{Debug, Formatter};
+use std::ops::Range;
+use std::time::SystemTime;
+
+use futures::stream::BoxStream;
+use futures::StreamExt as _;
+use martin_tile_utils::{Format, TileCoord, TileInfo};
+
+use crate::source::delegate::delegate_source;
+use crate::source::SourceError::{NoShards, ShardMismatch};
+use crate::source::{
+    Source, SourceError, Tile, TileData, TileInfoSource, TranscodeOptions, UrlQuery,
+};
+use crate::MartinResult;
+
+/// Splits one dataset over several identical sources, e.g. databases with a part of the tiles each.
+/// Each tile coordinate is always requested from the same shard, chosen by its hash.
+/// The ID and all metadata are the first shard's, which is checked to match the other shards.
+#[derive(Clone)]
+pub struct ShardedSource {
+    shards: Vec<TileInfoSource>,
+}
+
+impl ShardedSource {
+    /// Fails with [`SourceError::NoShards`] if there are no shards, and with
+    /// [`SourceError::ShardMismatch`] if their tile info, tile size, CRS, zoom range, bounds,
+    /// or URL query handling differ
+    pub fn new(shards: Vec<TileInfoSource>) -> Result<Self, SourceError> {
+        let Some(first) = shards.first() else {
+            return Err(NoShards);
+        };
+        let first_tj = first.get_tilejson();
+        for shard in &shards[1..] {
+            let tj = shard.get_tilejson();
+            let property = if shard.get_tile_info() != first.get_tile_info() {
+                "tile format"
+            } else if shard.tile_size() != first.tile_size() {
+                "tile size"
+            } else if shard.crs() != first.crs() {
+                "coordinate reference system"
+            } else if (tj.minzoom, tj.maxzoom) != (first_tj.minzoom, first_tj.maxzoom) {
+                "zoom range"
+            } else if tj.bounds != first_tj.bounds {
+                "bounding box"
+            } else if shard.support_url_query() != first.support_url_query()
+                || shard.required_query_keys() != first.required_query_keys()
+            {
+                "URL query handling"
+            } else {
+                continue;
+            };
+            return Err(ShardMismatch {
+                first_id: first.get_id().to_string(),
+                shard_id: shard.get_id().to_string(),
+                property,
+            });
+        }
+        Ok(Self { shards })
+    }
+
+    fn shard(&self, xyz: TileCoord) -> &dyn Source {
+        self.shards[shard_index(xyz, self.shards.len())].as_ref()
+    }
+}
+
+impl Debug for ShardedSource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ShardedSource {{ shards: {:?} }}", self.shards)
+    }
+}
+
+delegate_source! {
+    impl Source for ShardedSource => self.shards[0];
+    except [
+        get_tile, get_tile_with_info, get_tile_opt, get_tile_decoded, get_tile_range,
+        get_tile_as, transcode_tile, has_tile, get_tile_etag, covers, health_check,
+        last_modified, tile_stream, approximate_tile_count, approximate_total_bytes,
+        get_tiles_batch, prewarm,
+    ];
+
+    async fn get_tile(
+        &self,
+        xyz: TileCoord,
+        url_query: Option<&UrlQuery>,
+    ) -> MartinResult<TileData> {
+        self.shard(xyz).get_tile(xyz, url_query).await
+    }
+
+    async fn get_tile_with_info(
+        &self,
+        xyz: TileCoord,
+        url_query: Option<&UrlQuery>,
+    ) -> MartinResult<Tile> {
+        self.shard(xyz).get_tile_with_info(xyz, url_query).await
+    }
+
+    async fn get_tile_opt(
+        &self,
+        xyz: TileCoord,
+        url_query: Option<&UrlQuery>,
+    ) -> MartinResult<Option<Tile>> {
+        self.shard(xyz).get_tile_opt(xyz, url_query).await
+    }
+
+    async fn get_tile_decoded(
+        &self,
+        xyz: TileCoord,
+        url_query: Option<&UrlQuery>,
+    ) -> MartinResult<(Tile, TileInfo)> {
+        self.shard(xyz).get_tile_decoded(xyz, url_query).await
+    }
+
+    async fn get_tile_range(
+        &self,
+        xyz: TileCoord,
+        url_query: Option<&UrlQuery>,
+        range: Range<usize>,
+    ) -> MartinResult<Tile> {
+        self.shard(xyz).get_tile_range(xyz, url_query, range).await
+    }
+
+    async fn get_tile_as(
+        &self,
+        xyz: TileCoord,
+        url_query: Option<&UrlQuery>,
+        format: Format,
+    ) -> MartinResult<Tile> {
+        self.shard(xyz).get_tile_as(xyz, url_query, format).await
+    }
+
+    async fn transcode_tile(
+        &self,
+        xyz: TileCoord,
+        url_query: Option<&UrlQuery>,
+        format: Format,
+        options: TranscodeOptions,
+    ) -> MartinResult<Tile> {
+        self.shard(xyz)
+            .transcode_tile(xyz, url_query, format, options)
+            .await
+    }
+
+    async fn has_tile(&self, xyz: TileCoord, url_query: Option<&UrlQuery>) -> MartinResult<bool> {
+        self.shard(xyz).has_tile(xyz, url_query).await
+    }
+
+    async fn get_tile_etag(
+        &self,
+        xyz: TileCoord,
+        url_query: Option<&UrlQuery>,
+    ) -> MartinResult<Option<String>> {
+        self.shard(xyz).get_tile_etag(xyz, url_query).await
+    }
+
+    fn covers(&self, xyz: TileCoord) -> bool {
+        self.shard(xyz).covers(xyz)
+    }
+
+    /// Checks all shards, because each of them serves a part of the tiles
+    async fn health_check(&self) -> MartinResult<()> {
+        for shard in &self.shards {
+            shard.health_check().await?;
+        }
+        Ok(())
+    }
+
+    /// The latest modification of all shards, if all of them know it
+    fn last_modified(&self) -> Option<SystemTime> {
+        self.shards
+            .iter()
+            .map(|shard| shard.last_modified())
+            .collect::<Option<Vec<_>>>()?
+            .into_iter()
+            .max()
+    }
+
+    /// Streams the tiles of all shards, if all of them can enumerate their tiles
+    fn tile_stream(&self) -> Option<BoxStream<'_, MartinResult<(TileCoord, Tile)>>> {
+        let streams = self
+            .shards
+            .iter()
+            .map(|shard| shard.tile_stream())
+            .collect::<Option<Vec<_>>>()?;
+        Some(futures::stream::iter(streams).flatten().boxed())
+    }
+
+    fn approximate_tile_count(&self) -> Option<u64> {
+        self.shards
+            .iter()
+            .map(|shard| shard.approximate_tile_count())
+            .sum()
+    }
+
+    fn approximate_total_bytes(&self) -> Option<u64> {
+        self.shards
+            .iter()
+            .map(|shard| shard.approximate_total_bytes())
+            .sum()
+    }
+}
+
+/// Index of the shard of a tile. The hash is stable across runs and Rust versions,
+/// unlike [`std::hash::DefaultHasher`], and spreads neighboring tiles over all shards.
+fn shard_index(xyz: TileCoord, count: usize) -> usize {
+    // finalizer of the splitmix64 generator
+    let mix = |mut hash: u64| {
+        hash = (hash ^ (hash >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        hash = (hash ^ (hash >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        hash ^ (hash >> 31)
+    };
+    let hash = mix(mix(mix(u64::from(xyz.z)) ^ u64::from(xyz.x)) ^ u64::from(xyz.y));
+    usize::try_from(hash % count as u64).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use itertools::Itertools as _;
+    use martin_tile_utils::Encoding;
+    use tilejson::{tilejson, Bounds};
+
+    use super::*;
+    use crate::TestSource;
+
+    #[actix_rt::test]
+    async fn sharded_source() {
+        let shard = |id, data: u8| -> TileInfoSource {
+            Box::new(TestSource {
+                id,
+                tj: tilejson! { tiles: vec![] },
+                info: TileInfo::new(Format::Mvt, Encoding::Uncompressed),
+                data: vec![data],
+            })
+        };
+        let shards = || vec![shard("a", 0), shard("b", 1), shard("c", 2)];
+        let src = ShardedSource::new(shards()).unwrap();
+        assert_eq!(src.get_id(), "a");
+
+        // every tile is always served by the same shard, and all shards are used
+        let other = ShardedSource::new(shards()).unwrap();
+        let mut used = Vec::new();
+        for xyz in TileCoord::tiles_in_bbox([-180.0, -85.0, 180.0, 85.0], 3) {
+            let data = src.get_tile(xyz, None).await.unwrap();
+            assert_eq!(src.get_tile(xyz, None).await.unwrap(), data);
+            assert_eq!(other.get_tile(xyz, None).await.unwrap(), data);
+            used.extend(data);
+        }
+        assert_eq!(
+            used.iter().sorted().dedup().collect::<Vec<_>>(),
+            [&0, &1, &2]
+        );
+
+        assert!(matches!(ShardedSource::new(vec![]), Err(NoShards)));
+        let png = Box::new(TestSource {
+            id: "png",
+            tj: tilejson! { tiles: vec![] },
+            info: Format::Png.into(),
+            data: Vec::default(),
+        });
+        let err = ShardedSource::new(vec![shard("a", 0), png]).err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            "Shard png has a different tile format than the first shard a"
+        );
+        let zoomed = Box::new(TestSource {
+            id: "zoomed",
+            tj: tilejson! { tiles: vec![], maxzoom: 10 },
+            info: TileInfo::new(Format::Mvt, Encoding::Uncompressed),
+            data: Vec::default(),
+        });
+        let err = ShardedSource::new(vec![shard("a", 0), zoomed])
+            .err()
+            .unwrap();
+        assert_eq!(
+            err.to_string(),
+            "Shard zoomed has a different zoom range than the first shard a"
+        );
+        let bounded = Box::new(TestSource {
+            id: "bounded",
+            tj: tilejson! { tiles: vec![], bounds: Bounds::new(0.0, 0.0, 1.0, 1.0) },
+            info: TileInfo::new(Format::Mvt, Encoding::Uncompressed),
+            data: Vec::default(),
+        });
+        let err = ShardedSource::new(vec![shard("a", 0), bounded])
+            .err()
+            .unwrap();
+        assert_eq!(
+            err.to_string(),
+            "Shard bounded has a different bounding box than the first shard a"
+        );
+    }
+}
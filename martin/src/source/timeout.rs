@@ -0,0 +1,118 @@
+use std::fmt::{Debug, Formatter};
+use std::future::Future;
+use std::time::Duration;
+
+use martin_tile_utils::{Format, TileCoord};
+
+use crate::source::delegate::delegate_source;
+use crate::source::SourceError::Timeout;
+use crate::source::{Source, Tile, TileData, TileInfoSource, UrlQuery};
+use crate::MartinResult;
+
+/// Wraps a source to limit how long it may take to return a tile, e.g. a slow `PostgreSQL` function.
+/// Requests that take longer fail with [`SourceError::Timeout`](crate::SourceError::Timeout).
+#[derive(Clone)]
+pub struct TimeoutSource {
+    source: TileInfoSource,
+    timeout: Duration,
+}
+
+impl TimeoutSource {
+    #[must_use]
+    pub fn new(source: TileInfoSource, timeout: Duration) -> Self {
+        Self { source, timeout }
+    }
+
+    async fn with_timeout<T>(
+        &self,
+        xyz: TileCoord,
+        future: impl Future<Output = MartinResult<T>> + Send,
+    ) -> MartinResult<T> {
+        tokio::time::timeout(self.timeout, future)
+            .await
+            .map_err(|_| Timeout(self.get_id().to_string(), xyz, self.timeout))?
+    }
+}
+
+impl Debug for TimeoutSource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "TimeoutSource {{ source: {:?}, timeout: {:?} }}",
+            self.source, self.timeout
+        )
+    }
+}
+
+delegate_source! {
+    impl Source for TimeoutSource => self.source;
+    except [
+        get_tile, get_tile_with_info, get_tile_as,
+        get_tile_opt, get_tile_decoded, supports_range, get_tile_range, get_tiles_batch, prewarm,
+        transcode_tile,
+    ];
+
+    async fn get_tile(
+        &self,
+        xyz: TileCoord,
+        url_query: Option<&UrlQuery>,
+    ) -> MartinResult<TileData> {
+        self.with_timeout(xyz, self.source.get_tile(xyz, url_query)).await
+    }
+
+    async fn get_tile_with_info(
+        &self,
+        xyz: TileCoord,
+        url_query: Option<&UrlQuery>,
+    ) -> MartinResult<Tile> {
+        self.with_timeout(xyz, self.source.get_tile_with_info(xyz, url_query)).await
+    }
+
+    async fn get_tile_as(
+        &self,
+        xyz: TileCoord,
+        url_query: Option<&UrlQuery>,
+        format: Format,
+    ) -> MartinResult<Tile> {
+        self.with_timeout(xyz, self.source.get_tile_as(xyz, url_query, format)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use martin_tile_utils::{Encoding, TileInfo};
+    use tilejson::tilejson;
+
+    use super::*;
+    use crate::source::tests::SlowSource;
+    use crate::{MartinError, TestSource};
+
+    #[actix_rt::test]
+    async fn timeout_source() {
+        let slow = |delay| {
+            Box::new(SlowSource(
+                TestSource {
+                    id: "slow",
+                    tj: tilejson! { tiles: vec![] },
+                    info: TileInfo::new(Format::Mvt, Encoding::Uncompressed),
+                    data: vec![1_u8, 2],
+                },
+                delay,
+            ))
+        };
+        let xyz = TileCoord { z: 0, x: 0, y: 0 };
+
+        let src = TimeoutSource::new(slow(Duration::ZERO), Duration::from_secs(10));
+        assert_eq!(src.get_id(), "slow");
+        assert_eq!(src.get_tile(xyz, None).await.unwrap(), vec![1_u8, 2]);
+
+        let src = TimeoutSource::new(slow(Duration::from_secs(10)), Duration::from_millis(10));
+        let err = src.clone_source().get_tile(xyz, None).await.unwrap_err();
+        let MartinError::SourceError(Timeout(id, tile, _)) = err else {
+            panic!("unexpected error {err:?}");
+        };
+        assert_eq!((id.as_str(), tile), ("slow", xyz));
+        let err = src.get_tile_with_info(xyz, None).await.unwrap_err();
+        assert!(matches!(err, MartinError::SourceError(Timeout(..))));
+    }
+}
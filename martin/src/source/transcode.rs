@@ -0,0 +1,156 @@
+use std::fmt::{Debug, Formatter};
+
+use futures::stream::BoxStream;
+use futures::StreamExt as _;
+use martin_tile_utils::{Encoding, TileCoord, TileInfo};
+
+use crate::source::delegate::delegate_source;
+use crate::source::SourceError::UnsupportedEncoding;
+use crate::source::{SourceError, Tile, TileData, TileInfoSource, UrlQuery};
+use crate::MartinResult;
+
+/// Serves the tiles of a source with a different encoding, e.g. brotli instead of gzip,
+/// to avoid re-encoding them for every client. Internally compressed tiles like PNG,
+/// and tiles that already have the target encoding, are passed through as is.
+#[derive(Clone)]
+pub struct TranscodeSource {
+    source: TileInfoSource,
+    encoding: Encoding,
+}
+
+impl TranscodeSource {
+    /// Fails with [`SourceError::UnsupportedEncoding`] unless the target encoding
+    /// is `gzip`, `br`, or uncompressed
+    pub fn new(source: TileInfoSource, encoding: Encoding) -> Result<Self, SourceError> {
+        match encoding {
+            Encoding::Uncompressed | Encoding::Gzip | Encoding::Brotli => {
+                Ok(Self { source, encoding })
+            }
+            _ => Err(UnsupportedEncoding(
+                source.get_tile_info().encoding(encoding),
+            )),
+        }
+    }
+}
+
+impl Debug for TranscodeSource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "TranscodeSource {{ source: {:?}, encoding: {:?} }}",
+            self.source, self.encoding
+        )
+    }
+}
+
+delegate_source! {
+    impl Source for TranscodeSource => self.source;
+    except [
+        get_tile_info, get_tile, get_tile_with_info, tile_stream, available_encodings,
+        get_tile_opt, get_tile_decoded, supports_range, get_tile_range, get_tiles_batch, prewarm,
+        supported_formats, can_encode, get_tile_as, transcode_tile,
+    ];
+
+    fn get_tile_info(&self) -> TileInfo {
+        let info = self.source.get_tile_info();
+        if info.encoding == Encoding::Internal {
+            info
+        } else {
+            info.encoding(self.encoding)
+        }
+    }
+
+    async fn get_tile(
+        &self,
+        xyz: TileCoord,
+        url_query: Option<&UrlQuery>,
+    ) -> MartinResult<TileData> {
+        Ok(self.get_tile_with_info(xyz, url_query).await?.data)
+    }
+
+    async fn get_tile_with_info(
+        &self,
+        xyz: TileCoord,
+        url_query: Option<&UrlQuery>,
+    ) -> MartinResult<Tile> {
+        let tile = self.source.get_tile_with_info(xyz, url_query).await?;
+        Ok(tile.encode(self.encoding)?)
+    }
+
+    fn tile_stream(&self) -> Option<BoxStream<'_, MartinResult<(TileCoord, Tile)>>> {
+        let stream = self.source.tile_stream()?;
+        Some(
+            stream
+                .map(|res| res.and_then(|(xyz, tile)| Ok((xyz, tile.encode(self.encoding)?))))
+                .boxed(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use martin_tile_utils::{decode_brotli, encode_gzip, Format};
+    use tilejson::tilejson;
+
+    use super::*;
+    use crate::source::Source;
+    use crate::TestSource;
+
+    #[actix_rt::test]
+    async fn transcode_source() {
+        let gzip = || -> TileInfoSource {
+            Box::new(TestSource {
+                id: "gz",
+                tj: tilejson! { tiles: vec![] },
+                info: TileInfo::new(Format::Mvt, Encoding::Gzip),
+                data: encode_gzip(&[1_u8, 2, 3]).unwrap(),
+            })
+        };
+        let xyz = TileCoord { z: 0, x: 0, y: 0 };
+
+        let src = TranscodeSource::new(gzip(), Encoding::Brotli).unwrap();
+        let info = TileInfo::new(Format::Mvt, Encoding::Brotli);
+        assert_eq!(src.get_tile_info(), info);
+        let tile = src.get_tile_with_info(xyz, None).await.unwrap();
+        assert_eq!(tile.info, info);
+        assert_eq!(decode_brotli(&tile.data).unwrap(), vec![1_u8, 2, 3]);
+
+        let src = TranscodeSource::new(gzip(), Encoding::Uncompressed).unwrap();
+        assert_eq!(src.get_tile(xyz, None).await.unwrap(), vec![1_u8, 2, 3]);
+
+        let err = TranscodeSource::new(gzip(), Encoding::Zstd).err();
+        assert!(matches!(err, Some(UnsupportedEncoding(..))));
+    }
+
+    #[actix_rt::test]
+    async fn transcode_source_passthrough() {
+        let data = encode_gzip(&[1_u8, 2, 3]).unwrap();
+        let src = TranscodeSource::new(
+            Box::new(TestSource {
+                id: "gz",
+                tj: tilejson! { tiles: vec![] },
+                info: TileInfo::new(Format::Mvt, Encoding::Gzip),
+                data: data.clone(),
+            }),
+            Encoding::Gzip,
+        )
+        .unwrap();
+        let xyz = TileCoord { z: 0, x: 0, y: 0 };
+        let tile = src.get_tile_with_info(xyz, None).await.unwrap();
+        assert_eq!(tile.data, data);
+        assert_eq!(tile.info, TileInfo::new(Format::Mvt, Encoding::Gzip));
+
+        let png = TranscodeSource::new(
+            Box::new(TestSource {
+                id: "png",
+                tj: tilejson! { tiles: vec![] },
+                info: TileInfo::from(Format::Png),
+                data: vec![7_u8],
+            }),
+            Encoding::Brotli,
+        )
+        .unwrap();
+        assert_eq!(png.get_tile_info(), TileInfo::from(Format::Png));
+        assert_eq!(png.get_tile(xyz, None).await.unwrap(), vec![7_u8]);
+    }
+}
@@ -0,0 +1,108 @@
+use std::fmt::{Debug, Formatter};
+use std::sync::Arc;
+
+use futures::stream::BoxStream;
+use futures::StreamExt as _;
+use martin_tile_utils::TileCoord;
+
+use crate::source::delegate::delegate_source;
+use crate::source::{Tile, TileData, TileInfoSource, UrlQuery};
+use crate::MartinResult;
+
+/// A function applied to every tile of a [`TransformSource`]
+pub type TileTransform = Arc<dyn Fn(Tile) -> MartinResult<Tile> + Send + Sync>;
+
+/// Wraps a source, and applies a transformation to each of its tiles,
+/// e.g. to strip some vector layers or to re-compress tiles, without modifying the backend.
+#[derive(Clone)]
+pub struct TransformSource {
+    source: TileInfoSource,
+    transform: TileTransform,
+}
+
+impl TransformSource {
+    #[must_use]
+    pub fn new(source: TileInfoSource, transform: TileTransform) -> Self {
+        Self { source, transform }
+    }
+}
+
+impl Debug for TransformSource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "TransformSource {{ source: {:?} }}", self.source)
+    }
+}
+
+delegate_source! {
+    impl Source for TransformSource => self.source;
+    except [
+        get_tile, get_tile_with_info, tile_stream,
+        get_tile_opt, get_tile_decoded, supports_range, get_tile_range, get_tiles_batch, prewarm,
+        supported_formats, can_encode, get_tile_as, transcode_tile,
+    ];
+
+    async fn get_tile(
+        &self,
+        xyz: TileCoord,
+        url_query: Option<&UrlQuery>,
+    ) -> MartinResult<TileData> {
+        Ok(self.get_tile_with_info(xyz, url_query).await?.data)
+    }
+
+    async fn get_tile_with_info(
+        &self,
+        xyz: TileCoord,
+        url_query: Option<&UrlQuery>,
+    ) -> MartinResult<Tile> {
+        let tile = self.source.get_tile_with_info(xyz, url_query).await?;
+        (self.transform)(tile)
+    }
+
+    fn tile_stream(&self) -> Option<BoxStream<'_, MartinResult<(TileCoord, Tile)>>> {
+        let stream = self.source.tile_stream()?;
+        Some(
+            stream
+                .map(|res| res.and_then(|(xyz, tile)| Ok((xyz, (self.transform)(tile)?))))
+                .boxed(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use martin_tile_utils::{Encoding, Format, TileInfo};
+    use tilejson::tilejson;
+
+    use super::*;
+    use crate::source::Source;
+    use crate::TestSource;
+
+    #[actix_rt::test]
+    async fn transform_source() {
+        let src = Box::new(TestSource {
+            id: "src",
+            tj: tilejson! { tiles: vec![] },
+            info: TileInfo::new(Format::Json, Encoding::Uncompressed),
+            data: b"abc".to_vec(),
+        });
+        let src = TransformSource::new(
+            src,
+            Arc::new(|mut tile: Tile| {
+                tile.data.make_ascii_uppercase();
+                Ok(tile)
+            }),
+        );
+        assert_eq!(src.get_id(), "src");
+        assert_eq!(src.get_tile_info().format, Format::Json);
+
+        let xyz = TileCoord { z: 0, x: 0, y: 0 };
+        assert_eq!(src.get_tile(xyz, None).await.unwrap(), b"ABC");
+        let cloned = src.clone_source();
+        assert_eq!(
+            cloned.get_tile_with_info(xyz, None).await.unwrap().data,
+            b"ABC"
+        );
+        // the test source cannot enumerate its tiles
+        assert!(src.tile_stream().is_none());
+    }
+}
@@ -0,0 +1,249 @@
+use std::fmt::{Debug, Formatter};
+
+use futures::stream::BoxStream;
+use futures::StreamExt as _;
+use martin_tile_utils::{Format, TileCoord, TileInfo};
+
+use crate::source::delegate::delegate_source;
+use crate::source::SourceError::InvalidTile;
+use crate::source::{Source, Tile, TileData, TileInfoSource, UrlQuery};
+use crate::MartinResult;
+
+/// How thoroughly [`ValidatingSource`] checks tiles
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TileValidation {
+    /// Images must start with the magic bytes of their format, MVT tiles must be valid
+    /// protobuf messages, and JSON tiles must be valid JSON
+    #[default]
+    Basic,
+    /// Same as [`TileValidation::Basic`], but MVT tiles must also have at least one layer
+    Strict,
+}
+
+/// Wraps a source to check that its tiles are not corrupted before they are served.
+/// Empty tiles are considered missing, and are not checked. Encoded tiles are decoded to be checked.
+#[derive(Clone)]
+pub struct ValidatingSource {
+    source: TileInfoSource,
+    validation: TileValidation,
+}
+
+impl ValidatingSource {
+    #[must_use]
+    pub fn new(source: TileInfoSource, validation: TileValidation) -> Self {
+        Self { source, validation }
+    }
+
+    /// Pass the tile through, or fail with [`InvalidTile`] if it is invalid
+    fn check(&self, xyz: TileCoord, tile: Tile) -> MartinResult<Tile> {
+        if let Err(reason) = self.validate(&tile) {
+            Err(InvalidTile(
+                self.get_id().to_string(),
+                xyz,
+                tile.info.format,
+                reason,
+            ))?;
+        }
+        Ok(tile)
+    }
+
+    /// Check the tile content, returning the reason if it is invalid
+    fn validate(&self, tile: &Tile) -> Result<(), &'static str> {
+        if tile.data.is_empty() {
+            return Ok(());
+        }
+        let decoded;
+        let data = if tile.info.encoding.is_encoded() {
+            decoded = tile
+                .clone()
+                .decode()
+                .map_err(|_| "unable to decode the tile")?;
+            &decoded.data
+        } else {
+            &tile.data
+        };
+        let format = tile.info.format;
+        match format {
+            Format::Mvt => match mvt_layer_count(data) {
+                None => Err("not a valid protobuf message"),
+                Some(0) if self.validation == TileValidation::Strict => Err("no layers"),
+                Some(_) => Ok(()),
+            },
+            Format::Json => serde_json::from_slice::<serde::de::IgnoredAny>(data)
+                .map(|_| ())
+                .map_err(|_| "not valid JSON"),
+            Format::Gif | Format::Jpeg | Format::Png | Format::Webp => {
+                match TileInfo::detect(data) {
+                    Some(info) if info.format == format => Ok(()),
+                    _ => Err("unexpected magic bytes"),
+                }
+            }
+        }
+    }
+}
+
+/// Count the layers of an uncompressed MVT tile, or return `None` if it is not a valid protobuf message.
+/// Only the top level message is parsed, i.e. the layers themselves are not validated.
+fn mvt_layer_count(mut data: &[u8]) -> Option<usize> {
+    fn varint(data: &mut &[u8]) -> Option<u64> {
+        let mut value = 0_u64;
+        for shift in (0..64).step_by(7) {
+            let (&byte, rest) = data.split_first()?;
+            *data = rest;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Some(value);
+            }
+        }
+        None
+    }
+
+    let mut layers = 0;
+    while !data.is_empty() {
+        let key = varint(&mut data)?;
+        let len = match key & 0x7 {
+            0 => {
+                varint(&mut data)?;
+                0
+            }
+            1 => 8,
+            2 => usize::try_from(varint(&mut data)?).ok()?,
+            5 => 4,
+            _ => return None,
+        };
+        data = data.get(len..)?;
+        // layers are the repeated field 3 of the tile message
+        if key == (3 << 3) | 2 {
+            layers += 1;
+        }
+    }
+    Some(layers)
+}
+
+impl Debug for ValidatingSource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "ValidatingSource {{ source: {:?}, validation: {:?} }}",
+            self.source, self.validation
+        )
+    }
+}
+
+delegate_source! {
+    impl Source for ValidatingSource => self.source;
+    except [
+        get_tile, get_tile_with_info, tile_stream,
+        get_tile_opt, get_tile_decoded, supports_range, get_tile_range, get_tiles_batch, prewarm,
+        supported_formats, can_encode, get_tile_as, transcode_tile,
+    ];
+
+    async fn get_tile(
+        &self,
+        xyz: TileCoord,
+        url_query: Option<&UrlQuery>,
+    ) -> MartinResult<TileData> {
+        Ok(self.get_tile_with_info(xyz, url_query).await?.data)
+    }
+
+    async fn get_tile_with_info(
+        &self,
+        xyz: TileCoord,
+        url_query: Option<&UrlQuery>,
+    ) -> MartinResult<Tile> {
+        let tile = self.source.get_tile_with_info(xyz, url_query).await?;
+        self.check(xyz, tile)
+    }
+
+    fn tile_stream(&self) -> Option<BoxStream<'_, MartinResult<(TileCoord, Tile)>>> {
+        let stream = self.source.tile_stream()?;
+        Some(
+            stream
+                .map(|res| res.and_then(|(xyz, tile)| Ok((xyz, self.check(xyz, tile)?))))
+                .boxed(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use martin_tile_utils::{encode_gzip, Encoding};
+    use tilejson::tilejson;
+
+    use super::*;
+    use crate::source::EMPTY_PNG;
+    use crate::{MartinError, TestSource};
+
+    #[actix_rt::test]
+    async fn validating_source() {
+        let validating = |info: TileInfo, data: Vec<u8>, validation| {
+            ValidatingSource::new(
+                Box::new(TestSource {
+                    id: "checked",
+                    tj: tilejson! { tiles: vec![] },
+                    info,
+                    data,
+                }),
+                validation,
+            )
+        };
+        let mvt = TileInfo::new(Format::Mvt, Encoding::Uncompressed);
+        let gzip_mvt = TileInfo::new(Format::Mvt, Encoding::Gzip);
+        let png = TileInfo::from(Format::Png);
+        let json = TileInfo::from(Format::Json);
+        // a tile with a single layer named "a", version 2
+        let layer = vec![0x1a_u8, 0x05, 0x78, 0x02, 0x0a, 0x01, 0x61];
+        // a valid message with an unknown varint field, but no layers
+        let no_layers = vec![0x08_u8, 0x01];
+        let xyz = TileCoord { z: 0, x: 0, y: 0 };
+
+        for (info, data, validation) in [
+            (mvt, layer.clone(), TileValidation::Strict),
+            (
+                gzip_mvt,
+                encode_gzip(&layer).unwrap(),
+                TileValidation::Strict,
+            ),
+            (mvt, no_layers.clone(), TileValidation::Basic),
+            (mvt, Vec::new(), TileValidation::Strict),
+            (png, EMPTY_PNG.to_vec(), TileValidation::Basic),
+            (json, br#"{"a":1}"#.to_vec(), TileValidation::Basic),
+        ] {
+            let src = validating(info, data.clone(), validation);
+            assert_eq!(src.get_tile(xyz, None).await.unwrap(), data);
+        }
+
+        for (info, data, validation, reason) in [
+            (mvt, no_layers, TileValidation::Strict, "no layers"),
+            (
+                mvt,
+                vec![0x1a, 0x10, 0x00],
+                TileValidation::Basic,
+                "not a valid protobuf message",
+            ),
+            (
+                gzip_mvt,
+                layer,
+                TileValidation::Basic,
+                "unable to decode the tile",
+            ),
+            (
+                png,
+                b"GIF89a".to_vec(),
+                TileValidation::Basic,
+                "unexpected magic bytes",
+            ),
+            (json, b"{".to_vec(), TileValidation::Basic, "not valid JSON"),
+        ] {
+            let src = validating(info, data, validation);
+            let err = src.clone_source().get_tile(xyz, None).await.unwrap_err();
+            let MartinError::SourceError(InvalidTile(id, tile, format, msg)) = err else {
+                panic!("unexpected error {err:?}");
+            };
+            assert_eq!(
+                (id.as_str(), tile, format, msg),
+                ("checked", xyz, info.format, reason)
+            );
+        }
+    }
+}
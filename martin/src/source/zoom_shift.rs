@@ -0,0 +1,271 @@
+use std::fmt::{Debug, Formatter};
+
+use futures::future::ready;
+use futures::stream::BoxStream;
+use futures::{StreamExt as _, TryStreamExt as _};
+use martin_tile_utils::{Format, TileCoord, MAX_ZOOM};
+use tilejson::TileJSON;
+
+use crate::source::delegate::delegate_source;
+use crate::source::SourceError::ZoomShiftOutOfRange;
+use crate::source::{Source, Tile, TileData, TileInfoSource, UrlQuery};
+use crate::MartinResult;
+
+/// Wraps a source to serve its tiles at a different zoom, e.g. a dataset authored
+/// with a different zoom origin. A positive `shift` serves the tiles of zoom `z` at zoom `z + shift`.
+/// The `x` and `y` coordinates are unchanged, so requests for tiles that do not exist
+/// at the shifted zoom fail with [`SourceError::ZoomShiftOutOfRange`](crate::SourceError::ZoomShiftOutOfRange).
+#[derive(Clone)]
+pub struct ZoomShiftSource {
+    source: TileInfoSource,
+    shift: i8,
+    tilejson: TileJSON,
+    zooms: Option<Vec<u8>>,
+}
+
+impl ZoomShiftSource {
+    /// The `TileJSON` zoom range is shifted too, clamped to the valid zooms.
+    #[must_use]
+    pub fn new(source: TileInfoSource, shift: i8) -> Self {
+        let clamp = |zoom: u8| {
+            let zoom = (i16::from(zoom) + i16::from(shift)).clamp(0, i16::from(MAX_ZOOM));
+            u8::try_from(zoom).unwrap_or_default()
+        };
+        let mut tilejson = source.get_tilejson().clone();
+        tilejson.minzoom = tilejson.minzoom.map(clamp);
+        tilejson.maxzoom = tilejson.maxzoom.map(clamp);
+        let zooms = source.available_zooms().map(|zooms| {
+            zooms
+                .iter()
+                .filter_map(|&zoom| shift_zoom(zoom, shift))
+                .collect()
+        });
+        Self {
+            source,
+            shift,
+            tilejson,
+            zooms,
+        }
+    }
+
+    /// Coordinates of the tile in the wrapped source
+    fn inner_coord(&self, xyz: TileCoord) -> MartinResult<TileCoord> {
+        shift_zoom(xyz.z, self.shift.saturating_neg())
+            .map(|z| TileCoord { z, ..xyz })
+            .filter(TileCoord::is_valid)
+            .ok_or_else(|| ZoomShiftOutOfRange(self.get_id().to_string(), xyz, self.shift).into())
+    }
+
+    /// Coordinates of a tile of the wrapped source as served by this one, if they are valid
+    fn outer_coord(&self, xyz: TileCoord) -> Option<TileCoord> {
+        shift_zoom(xyz.z, self.shift)
+            .map(|z| TileCoord { z, ..xyz })
+            .filter(TileCoord::is_valid)
+    }
+}
+
+/// Shift the zoom, or return `None` if the result is not a valid zoom
+fn shift_zoom(zoom: u8, shift: i8) -> Option<u8> {
+    u8::try_from(i16::from(zoom) + i16::from(shift))
+        .ok()
+        .filter(|zoom| *zoom <= MAX_ZOOM)
+}
+
+impl Debug for ZoomShiftSource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "ZoomShiftSource {{ source: {:?}, shift: {} }}",
+            self.source, self.shift
+        )
+    }
+}
+
+delegate_source! {
+    impl Source for ZoomShiftSource => self.source;
+    except [
+        get_tilejson, get_tile, get_tile_with_info, get_tile_as, has_tile, get_tile_etag,
+        tile_stream, covers, prefetch_hints,
+        max_overzoom, available_zooms, is_valid_zoom, zoom_check,
+        get_tile_opt, get_tile_decoded, supports_range, get_tile_range, get_tiles_batch, prewarm,
+        transcode_tile,
+    ];
+
+    fn get_tilejson(&self) -> &TileJSON {
+        &self.tilejson
+    }
+
+    async fn get_tile(
+        &self,
+        xyz: TileCoord,
+        url_query: Option<&UrlQuery>,
+    ) -> MartinResult<TileData> {
+        let inner = self.inner_coord(xyz)?;
+        self.source.get_tile(inner, url_query).await
+    }
+
+    async fn get_tile_with_info(
+        &self,
+        xyz: TileCoord,
+        url_query: Option<&UrlQuery>,
+    ) -> MartinResult<Tile> {
+        let inner = self.inner_coord(xyz)?;
+        self.source.get_tile_with_info(inner, url_query).await
+    }
+
+    async fn get_tile_as(
+        &self,
+        xyz: TileCoord,
+        url_query: Option<&UrlQuery>,
+        format: Format,
+    ) -> MartinResult<Tile> {
+        let inner = self.inner_coord(xyz)?;
+        self.source.get_tile_as(inner, url_query, format).await
+    }
+
+    async fn has_tile(&self, xyz: TileCoord, url_query: Option<&UrlQuery>) -> MartinResult<bool> {
+        let Ok(inner) = self.inner_coord(xyz) else {
+            return Ok(false);
+        };
+        self.source.has_tile(inner, url_query).await
+    }
+
+    async fn get_tile_etag(
+        &self,
+        xyz: TileCoord,
+        url_query: Option<&UrlQuery>,
+    ) -> MartinResult<Option<String>> {
+        let inner = self.inner_coord(xyz)?;
+        self.source.get_tile_etag(inner, url_query).await
+    }
+
+    /// Tiles that cannot be served at the shifted zoom are skipped
+    fn tile_stream(&self) -> Option<BoxStream<'_, MartinResult<(TileCoord, Tile)>>> {
+        let stream = self.source.tile_stream()?;
+        let shift = self.shift;
+        Some(
+            stream
+                .try_filter(|(xyz, _)| ready(self.outer_coord(*xyz).is_some()))
+                .map_ok(move |(xyz, tile)| {
+                    let z = xyz.z.saturating_add_signed(shift);
+                    (TileCoord { z, ..xyz }, tile)
+                })
+                .boxed(),
+        )
+    }
+
+    fn covers(&self, xyz: TileCoord) -> bool {
+        self.inner_coord(xyz)
+            .is_ok_and(|inner| self.source.covers(inner))
+    }
+
+    fn prefetch_hints(&self, xyz: TileCoord) -> Vec<TileCoord> {
+        let Ok(inner) = self.inner_coord(xyz) else {
+            return Vec::new();
+        };
+        self.source
+            .prefetch_hints(inner)
+            .into_iter()
+            .filter_map(|hint| self.outer_coord(hint))
+            .collect()
+    }
+
+    fn max_overzoom(&self) -> Option<u8> {
+        self.source
+            .max_overzoom()
+            .and_then(|zoom| shift_zoom(zoom, self.shift))
+    }
+
+    fn available_zooms(&self) -> Option<&[u8]> {
+        self.zooms.as_deref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+    use martin_tile_utils::TileInfo;
+    use tilejson::tilejson;
+
+    use super::*;
+    use crate::source::tests::SparseZoomSource;
+    use crate::{MartinError, TestSource};
+
+    #[actix_rt::test]
+    async fn zoom_shift_source() {
+        /// Returns the requested coordinates as the tile data
+        #[derive(Debug, Clone)]
+        struct CoordSource(TileJSON);
+
+        #[async_trait]
+        impl Source for CoordSource {
+            fn get_id(&self) -> &'static str {
+                "coords"
+            }
+
+            fn get_tilejson(&self) -> &TileJSON {
+                &self.0
+            }
+
+            fn get_tile_info(&self) -> TileInfo {
+                TileInfo::from(Format::Json)
+            }
+
+            fn clone_source(&self) -> TileInfoSource {
+                Box::new(self.clone())
+            }
+
+            async fn get_tile(
+                &self,
+                xyz: TileCoord,
+                _url_query: Option<&UrlQuery>,
+            ) -> MartinResult<TileData> {
+                Ok(xyz.to_string().into_bytes())
+            }
+        }
+
+        let tile = |src: &ZoomShiftSource, z, x, y| {
+            let src = src.clone_source();
+            async move { src.get_tile(TileCoord { z, x, y }, None).await }
+        };
+        let inner = || {
+            Box::new(CoordSource(
+                tilejson! { tiles: vec![], minzoom: 0, maxzoom: 30 },
+            ))
+        };
+
+        let src = ZoomShiftSource::new(inner(), 1);
+        assert_eq!(src.get_tilejson().minzoom, Some(1));
+        assert_eq!(src.get_tilejson().maxzoom, Some(MAX_ZOOM));
+        assert!(!src.is_valid_zoom(0));
+        assert_eq!(tile(&src, 1, 0, 0).await.unwrap(), b"0,0,0");
+        assert_eq!(tile(&src, 3, 2, 1).await.unwrap(), b"2,2,1");
+        assert!(!src.covers(TileCoord { z: 1, x: 1, y: 0 }));
+        let err = tile(&src, 1, 1, 0).await.unwrap_err();
+        let MartinError::SourceError(ZoomShiftOutOfRange(id, xyz, shift)) = err else {
+            panic!("unexpected error {err:?}");
+        };
+        assert_eq!(
+            (id.as_str(), xyz, shift),
+            ("coords", TileCoord { z: 1, x: 1, y: 0 }, 1)
+        );
+        assert!(tile(&src, 0, 0, 0).await.is_err());
+
+        let src = ZoomShiftSource::new(inner(), -1);
+        assert_eq!(src.get_tilejson().minzoom, Some(0));
+        assert_eq!(src.get_tilejson().maxzoom, Some(MAX_ZOOM - 1));
+        assert_eq!(tile(&src, 0, 0, 0).await.unwrap(), b"1,0,0");
+        assert_eq!(tile(&src, 2, 3, 1).await.unwrap(), b"3,3,1");
+        assert!(tile(&src, MAX_ZOOM, 0, 0).await.is_err());
+
+        let sparse = TestSource {
+            id: "sparse",
+            tj: tilejson! { tiles: vec![] },
+            info: TileInfo::from(Format::Json),
+            data: Vec::new(),
+        };
+        let sparse = SparseZoomSource(sparse, vec![0, 2, 5]);
+        let src = ZoomShiftSource::new(Box::new(sparse), -1);
+        assert_eq!(src.available_zooms(), Some([1, 4].as_slice()));
+    }
+}
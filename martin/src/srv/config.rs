@@ -5,6 +5,19 @@ use crate::args::PreferredEncoding;
 pub const KEEP_ALIVE_DEFAULT: u64 = 75;
 pub const LISTEN_ADDRESSES_DEFAULT: &str = "0.0.0.0:3000";
 
+/// How to respond to a tile request when none of the requested sources support its zoom level
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MissingTileResponse {
+    /// Respond with `404 Not Found`
+    #[default]
+    NotFound,
+    /// Respond with `204 No Content`
+    NoContent,
+    /// Respond with a valid empty tile of the requested format, see [`crate::empty_tile`]
+    EmptyTile,
+}
+
 #[serde_with::skip_serializing_none]
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Default)]
 pub struct SrvConfig {
@@ -13,6 +26,9 @@ pub struct SrvConfig {
     pub base_path: Option<String>,
     pub worker_processes: Option<usize>,
     pub preferred_encoding: Option<PreferredEncoding>,
+    pub max_merge_sources: Option<usize>,
+    pub default_source: Option<String>,
+    pub missing_tile: Option<MissingTileResponse>,
     #[cfg(feature = "webui")]
     pub web_ui: Option<crate::args::WebUiMode>,
 }
@@ -72,5 +88,19 @@ mod tests {
                 ..Default::default()
             }
         );
+        assert_eq!(
+            serde_yaml::from_str::<SrvConfig>(indoc! {"
+                max_merge_sources: 4
+                default_source: points
+                missing_tile: empty-tile
+            "})
+            .unwrap(),
+            SrvConfig {
+                max_merge_sources: Some(4),
+                default_source: some("points"),
+                missing_tile: Some(MissingTileResponse::EmptyTile),
+                ..Default::default()
+            }
+        );
     }
 }
@@ -1,17 +1,18 @@
 mod config;
-pub use config::{SrvConfig, KEEP_ALIVE_DEFAULT, LISTEN_ADDRESSES_DEFAULT};
+pub use config::{MissingTileResponse, SrvConfig, KEEP_ALIVE_DEFAULT, LISTEN_ADDRESSES_DEFAULT};
 
 #[cfg(feature = "fonts")]
 mod fonts;
 
 mod server;
-pub use server::{new_server, router, Catalog, RESERVED_KEYWORDS};
+pub use server::{new_server, router, Catalog, SourceAuthorizer, RESERVED_KEYWORDS};
 
 mod tiles;
+pub(crate) use tiles::map_tile_error;
 pub use tiles::{DynTileSource, TileRequest};
 
 mod tiles_info;
-pub use tiles_info::{merge_tilejson, SourceIDsRequest};
+pub use tiles_info::{merge_attributions, merge_tilejson, SourceIDsRequest};
 
 #[cfg(feature = "sprites")]
 mod sprites;
@@ -1,14 +1,15 @@
 use std::future::Future;
 use std::pin::Pin;
 use std::string::ToString;
+use std::sync::Arc;
 use std::time::Duration;
 
 use actix_cors::Cors;
 use actix_web::error::ErrorInternalServerError;
 use actix_web::http::header::CACHE_CONTROL;
 use actix_web::middleware::TrailingSlash;
-use actix_web::web::Data;
-use actix_web::{middleware, route, web, App, HttpResponse, HttpServer, Responder};
+use actix_web::web::{Data, Query};
+use actix_web::{middleware, route, web, App, HttpRequest, HttpResponse, HttpServer, Responder};
 use futures::TryFutureExt;
 #[cfg(feature = "lambda")]
 use lambda_web::{is_running_on_lambda, run_actix_on_lambda};
@@ -18,7 +19,7 @@ use serde::{Deserialize, Serialize};
 #[cfg(feature = "webui")]
 use crate::args::WebUiMode;
 use crate::config::ServerState;
-use crate::source::TileCatalog;
+use crate::source::{TileCatalog, TileSources};
 use crate::srv::config::{SrvConfig, KEEP_ALIVE_DEFAULT, LISTEN_ADDRESSES_DEFAULT};
 use crate::srv::tiles::get_tile;
 use crate::srv::tiles_info::get_source_info;
@@ -40,7 +41,7 @@ pub const RESERVED_KEYWORDS: &[&str] = &[
     "reload", "sprite", "status",
 ];
 
-#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
 pub struct Catalog {
     pub tiles: TileCatalog,
     #[cfg(feature = "sprites")]
@@ -61,6 +62,29 @@ impl Catalog {
     }
 }
 
+/// Decides per request whether the sources that [require authorization](crate::source::Source::requires_auth)
+/// can be accessed, e.g. by checking the tenant of the request. It must be registered as app data
+/// with [`Data`], otherwise such sources are neither served nor listed in the catalog.
+#[derive(Clone)]
+pub struct SourceAuthorizer(Arc<AuthorizeFn>);
+
+type AuthorizeFn = dyn Fn(&HttpRequest, &str) -> bool + Send + Sync;
+
+impl SourceAuthorizer {
+    pub fn new(authorize: impl Fn(&HttpRequest, &str) -> bool + Send + Sync + 'static) -> Self {
+        Self(Arc::new(authorize))
+    }
+
+    /// The access check of a request, to pass to [`TileSources::check_authorized`] and similar.
+    /// Denies access if no [`SourceAuthorizer`] is registered.
+    pub fn for_request(req: &HttpRequest) -> impl Fn(&str) -> bool + '_ {
+        move |id| {
+            req.app_data::<Data<Self>>()
+                .is_some_and(|authorizer| (authorizer.0)(req, id))
+        }
+    }
+}
+
 pub fn map_internal_error<T: std::fmt::Display>(e: T) -> actix_web::Error {
     error!("{e}");
     ErrorInternalServerError(e.to_string())
@@ -103,10 +127,34 @@ async fn get_health() -> impl Responder {
     wrap = "middleware::Compress::default()"
 )]
 #[allow(clippy::unused_async)]
-async fn get_catalog(catalog: Data<Catalog>) -> impl Responder {
+async fn get_catalog(
+    req: HttpRequest,
+    catalog: Data<Catalog>,
+    sources: Data<TileSources>,
+    query: Query<CatalogRequest>,
+) -> impl Responder {
+    let mut catalog = if let Some(content_type) = &query.content_type {
+        Catalog {
+            tiles: sources.get_catalog_by_content_type(content_type),
+            ..catalog.as_ref().clone()
+        }
+    } else {
+        catalog.as_ref().clone()
+    };
+    // sources the request may not access are not listed
+    let authorize = SourceAuthorizer::for_request(&req);
+    catalog
+        .tiles
+        .retain(|id, _| sources.check_authorized(id, Some(&authorize)).is_ok());
     HttpResponse::Ok().json(catalog)
 }
 
+#[derive(Deserialize)]
+struct CatalogRequest {
+    /// Only list the tile sources with this content type, e.g. `application/x-protobuf`
+    content_type: Option<String>,
+}
+
 pub fn router(cfg: &mut web::ServiceConfig, #[allow(unused_variables)] usr_cfg: &SrvConfig) {
     cfg.service(get_health)
         .service(get_catalog)
@@ -195,46 +243,117 @@ pub fn new_server(config: SrvConfig, state: ServerState) -> MartinResult<(Server
 }
 
 #[cfg(test)]
-pub mod tests {
+mod tests {
+    use actix_web::http::StatusCode;
+    use actix_web::test::{call_service, init_service, read_body_json, TestRequest};
     use async_trait::async_trait;
-    use martin_tile_utils::{Encoding, Format, TileCoord, TileInfo};
-    use tilejson::TileJSON;
+    use martin_tile_utils::{Format, TileCoord, TileInfo};
+    use tilejson::{tilejson, TileJSON};
 
     use super::*;
-    use crate::source::{Source, TileData, TileInfoSource};
-    use crate::UrlQuery;
+    use crate::source::{Source, TileData, TileInfoSource, UrlQuery};
+    use crate::utils::OptMainCache;
+    use crate::{MartinResult, TestSource};
 
+    /// Only accessible to authorized requests
     #[derive(Debug, Clone)]
-    pub struct TestSource {
-        pub id: &'static str,
-        pub tj: TileJSON,
-        pub data: TileData,
-    }
+    struct PrivateSource(TestSource);
 
     #[async_trait]
-    impl Source for TestSource {
+    impl Source for PrivateSource {
         fn get_id(&self) -> &str {
-            self.id
+            self.0.get_id()
         }
 
         fn get_tilejson(&self) -> &TileJSON {
-            &self.tj
+            self.0.get_tilejson()
         }
 
         fn get_tile_info(&self) -> TileInfo {
-            TileInfo::new(Format::Mvt, Encoding::Uncompressed)
+            self.0.get_tile_info()
         }
 
         fn clone_source(&self) -> TileInfoSource {
-            unimplemented!()
+            Box::new(self.clone())
         }
 
         async fn get_tile(
             &self,
-            _xyz: TileCoord,
-            _url_query: Option<&UrlQuery>,
+            xyz: TileCoord,
+            url_query: Option<&UrlQuery>,
         ) -> MartinResult<TileData> {
-            Ok(self.data.clone())
+            self.0.get_tile(xyz, url_query).await
+        }
+
+        fn requires_auth(&self) -> bool {
+            true
+        }
+    }
+
+    #[actix_rt::test]
+    async fn authorized_sources() {
+        let source = |id| TestSource {
+            id,
+            tj: tilejson! { tiles: vec![] },
+            info: Format::Mvt.into(),
+            data: vec![1_u8],
+        };
+        let sources = TileSources::new(vec![vec![
+            Box::new(source("public")),
+            Box::new(PrivateSource(source("private"))),
+        ]]);
+        let catalog = Catalog {
+            tiles: sources.get_catalog(),
+            ..Catalog::default()
+        };
+        let authorizer = SourceAuthorizer::new(|req, id| {
+            req.headers()
+                .get("x-tenant")
+                .is_some_and(|tenant| tenant == id)
+        });
+
+        for with_authorizer in [false, true] {
+            let mut app = App::new()
+                .app_data(Data::new(sources.clone()))
+                .app_data(Data::new(OptMainCache::None))
+                .app_data(Data::new(catalog.clone()))
+                .app_data(Data::new(SrvConfig::default()));
+            if with_authorizer {
+                app = app.app_data(Data::new(authorizer.clone()));
+            }
+            let app = init_service(app.configure(|c| router(c, &SrvConfig::default()))).await;
+
+            for tenant in [None, Some("public"), Some("private")] {
+                let allowed = with_authorizer && tenant == Some("private");
+                let get = |path| {
+                    let req = TestRequest::get().uri(path);
+                    match tenant {
+                        Some(tenant) => req.insert_header(("x-tenant", tenant)),
+                        None => req,
+                    }
+                    .to_request()
+                };
+                let expected = if allowed {
+                    StatusCode::OK
+                } else {
+                    StatusCode::FORBIDDEN
+                };
+
+                for path in ["/private/0/0/0", "/public,private/0/0/0", "/private"] {
+                    let response = call_service(&app, get(path)).await;
+                    assert_eq!(response.status(), expected, "{path} {tenant:?}");
+                }
+                for path in ["/public/0/0/0", "/public"] {
+                    let response = call_service(&app, get(path)).await;
+                    assert_eq!(response.status(), StatusCode::OK, "{path} {tenant:?}");
+                }
+
+                let response = call_service(&app, get("/catalog")).await;
+                let body: serde_json::Value = read_body_json(response).await;
+                let tiles = body["tiles"].as_object().unwrap();
+                assert!(tiles.contains_key("public"));
+                assert_eq!(tiles.contains_key("private"), allowed, "{tenant:?}");
+            }
         }
     }
 }
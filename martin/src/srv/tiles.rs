@@ -1,25 +1,28 @@
+use std::time::SystemTime;
+
 use actix_http::header::Quality;
 use actix_http::ContentEncoding;
-use actix_web::error::{ErrorBadRequest, ErrorNotAcceptable, ErrorNotFound};
+use actix_web::error::{ErrorBadRequest, ErrorNotAcceptable, ErrorNotFound, InternalError};
 use actix_web::http::header::{
-    AcceptEncoding, Encoding as HeaderEnc, Preference, CONTENT_ENCODING,
+    Accept, AcceptEncoding, Encoding as HeaderEnc, EntityTag, HttpDate, IfModifiedSince,
+    IfNoneMatch, LastModified, Preference, CACHE_CONTROL, ETAG, VARY,
 };
 use actix_web::web::{Data, Path, Query};
-use actix_web::{route, HttpMessage, HttpRequest, HttpResponse, Result as ActixResult};
-use futures::future::try_join_all;
-use log::trace;
-use martin_tile_utils::{
-    decode_brotli, decode_gzip, encode_brotli, encode_gzip, Encoding, Format, TileCoord, TileInfo,
+use actix_web::{
+    route, HttpMessage, HttpRequest, HttpResponse, ResponseError as _, Result as ActixResult,
 };
+use futures::future::try_join_all;
+use log::{error, trace};
+use martin_tile_utils::{Encoding, Format, TileCoord, TileCoordError, TileInfo};
 use serde::Deserialize;
 
 use crate::args::PreferredEncoding;
-use crate::source::{Source, TileSources, UrlQuery};
-use crate::srv::server::map_internal_error;
-use crate::srv::SrvConfig;
+use crate::source::{MergedSources, MissBehavior, Source, TileSources, UrlQuery, MERGE_DELIMITER};
+use crate::srv::server::{map_internal_error, SourceAuthorizer};
+use crate::srv::{MissingTileResponse, SrvConfig};
 use crate::utils::cache::get_or_insert_cached_value;
 use crate::utils::{CacheKey, CacheValue, MainCache, OptMainCache};
-use crate::{Tile, TileData};
+use crate::{concat_mvt, empty_tile, MartinError, Tile};
 
 static SUPPORTED_ENC: &[HeaderEnc] = &[
     HeaderEnc::gzip(),
@@ -27,6 +30,9 @@ static SUPPORTED_ENC: &[HeaderEnc] = &[
     HeaderEnc::identity(),
 ];
 
+/// Lists the sources that were merged into a composite tile, in the requested order
+pub const SOURCES_HEADER: &str = "X-Martin-Sources";
+
 #[derive(Deserialize, Clone)]
 pub struct TileRequest {
     source_ids: String,
@@ -43,7 +49,22 @@ async fn get_tile(
     sources: Data<TileSources>,
     cache: Data<OptMainCache>,
 ) -> ActixResult<HttpResponse> {
-    let src = DynTileSource::new(
+    let xyz = TileCoord {
+        z: path.z,
+        x: path.x,
+        y: path.y,
+    };
+    let xyz = if xyz.is_valid() {
+        xyz
+    } else if xyz.wrapped().is_valid() && sources.wraps_x(&path.source_ids) {
+        xyz.wrapped()
+    } else {
+        return Err(ErrorBadRequest(TileCoordError::OutOfRange(xyz)));
+    };
+
+    let authorizer = SourceAuthorizer::for_request(&req);
+    sources.check_authorized(&path.source_ids, Some(&authorizer))?;
+    let mut src = DynTileSource::new(
         sources.as_ref(),
         &path.source_ids,
         Some(path.z),
@@ -52,26 +73,35 @@ async fn get_tile(
         srv_config.preferred_encoding,
         cache.as_ref().as_ref(),
     )?;
+    src.if_none_match = req.get_header::<IfNoneMatch>();
+    src.if_modified_since = req.get_header::<IfModifiedSince>();
+    src.missing_tile = srv_config.missing_tile.unwrap_or_default();
+    src.negotiate_format(req.get_header::<Accept>().as_ref());
 
-    src.get_http_response(TileCoord {
-        z: path.z,
-        x: path.x,
-        y: path.y,
-    })
-    .await
+    src.get_http_response(xyz).await
 }
 
 pub struct DynTileSource<'a> {
+    /// All sources, to get the tiles of single-source requests, see [`TileSources::get_single_source_tile`]
+    tile_sources: &'a TileSources,
     pub sources: Vec<&'a dyn Source>,
+    /// IDs of the merged sources, only set for composite requests
+    pub merged_ids: Option<Vec<String>>,
     pub info: TileInfo,
     pub query_str: Option<&'a str>,
     pub query_obj: Option<UrlQuery>,
     pub accept_enc: Option<AcceptEncoding>,
     pub preferred_enc: Option<PreferredEncoding>,
     pub cache: Option<&'a MainCache>,
+    pub if_none_match: Option<IfNoneMatch>,
+    pub if_modified_since: Option<IfModifiedSince>,
+    /// Response if none of the sources support the requested zoom
+    pub missing_tile: MissingTileResponse,
 }
 
 impl<'a> DynTileSource<'a> {
+    /// Access to sources that [require authorization](Source::requires_auth) is not checked,
+    /// callers serving client requests must use [`TileSources::check_authorized`] first.
     pub fn new(
         sources: &'a TileSources,
         source_ids: &str,
@@ -81,10 +111,16 @@ impl<'a> DynTileSource<'a> {
         preferred_enc: Option<PreferredEncoding>,
         cache: Option<&'a MainCache>,
     ) -> ActixResult<Self> {
-        let (sources, use_url_query, info) = sources.get_sources(source_ids, zoom)?;
-
-        if sources.is_empty() {
-            return Err(ErrorNotFound("No valid sources found"));
+        let tile_sources = sources;
+        let MergedSources {
+            sources,
+            use_url_query,
+            mut info,
+            included,
+            ..
+        } = sources.get_sources_detailed(source_ids, zoom)?;
+        if Self::all_prefer_uncompressed(&sources) {
+            info = info.encoding(Encoding::Uncompressed);
         }
 
         let mut query_obj = None;
@@ -93,56 +129,275 @@ impl<'a> DynTileSource<'a> {
             query_obj = Some(Query::<UrlQuery>::from_query(query)?.into_inner());
             query_str = Some(query);
         }
+        TileSources::check_query(&sources, query_obj.as_ref())?;
+
+        let merged_ids = source_ids.contains(MERGE_DELIMITER).then_some(included);
 
         Ok(Self {
+            tile_sources,
             sources,
+            merged_ids,
             info,
             query_str,
             query_obj,
             accept_enc,
             preferred_enc,
             cache,
+            if_none_match: None,
+            if_modified_since: None,
+            missing_tile: MissingTileResponse::default(),
         })
     }
 
+    /// Whether the response depends on the client's `Accept-Encoding`, i.e. if the tiles
+    /// may be compressed or decompressed, which is the case for all but [`Encoding::Internal`] tiles,
+    /// or if any source can serve several encodings, see [`Source::available_encodings`]
+    fn varies_by_encoding(&self) -> bool {
+        self.info.encoding != Encoding::Internal
+            || self
+                .sources
+                .iter()
+                .any(|s| s.available_encodings().len() > 1)
+    }
+
+    /// Whether the response depends on the client's `Accept` header, i.e. if all sources
+    /// can produce both WebP and PNG tiles, see [`DynTileSource::negotiate_format`]
+    fn varies_by_format(&self) -> bool {
+        !self.sources.is_empty()
+            && self
+                .sources
+                .iter()
+                .all(|s| s.can_encode(Format::Webp) && s.can_encode(Format::Png))
+    }
+
+    /// Serve raster tiles as WebP if the client's `Accept` header includes `image/webp`,
+    /// and as PNG otherwise. Only applies if all sources [can encode](Source::can_encode)
+    /// both formats, other tiles keep their format.
+    pub fn negotiate_format(&mut self, accept: Option<&Accept>) {
+        if !self.varies_by_format() {
+            return;
+        }
+        let webp = accept.is_some_and(|accept| {
+            accept
+                .iter()
+                .any(|mime| mime.item.essence_str() == "image/webp" && mime.quality > Quality::ZERO)
+        });
+        self.info = if webp { Format::Webp } else { Format::Png }.into();
+    }
+
+    /// Tiles are always served uncompressed if all sources prefer it, see [`Source::prefers_uncompressed`]
+    fn all_prefer_uncompressed(sources: &[&dyn Source]) -> bool {
+        !sources.is_empty() && sources.iter().all(|s| s.prefers_uncompressed())
+    }
+
     pub async fn get_http_response(&self, xyz: TileCoord) -> ActixResult<HttpResponse> {
+        if self.sources.is_empty() {
+            return match self.missing_tile {
+                MissingTileResponse::NotFound => Err(ErrorNotFound("No valid sources found")),
+                MissingTileResponse::NoContent => Ok(HttpResponse::NoContent().finish()),
+                MissingTileResponse::EmptyTile => {
+                    Ok(Self::empty_tile_response(empty_tile(self.info)))
+                }
+            };
+        }
+
+        let etag = self.get_etag(xyz).await?.map(EntityTag::new_weak);
+        let last_modified = self.get_last_modified().map(HttpDate::from);
+        if self.is_not_modified(etag.as_ref(), last_modified) {
+            let mut response = HttpResponse::NotModified();
+            if let Some(etag) = etag {
+                response.insert_header((ETAG, etag.to_string()));
+            }
+            if let Some(last_modified) = last_modified {
+                response.insert_header(LastModified(last_modified));
+            }
+            if self.varies_by_encoding() {
+                response.insert_header((VARY, "Accept-Encoding"));
+            }
+            if self.varies_by_format() {
+                response.append_header((VARY, "Accept"));
+            }
+            if let Some(max_age) = self.get_cache_control_max_age() {
+                response.insert_header((CACHE_CONTROL, format!("max-age={max_age}")));
+            }
+            return Ok(response.finish());
+        }
+
         let tile = self.get_tile_content(xyz).await?;
+        if tile.data.is_empty() {
+            // composite sources respond like their first source, see `Source::miss_behavior`
+            return match self.sources[0].miss_behavior() {
+                MissBehavior::NotFound => Err(ErrorNotFound("Tile not found")),
+                MissBehavior::Empty => Ok(Self::empty_tile_response(empty_tile(self.info))),
+                MissBehavior::Transparent => {
+                    Ok(Self::empty_tile_response(empty_tile(Format::Png.into())))
+                }
+            };
+        }
+
+        let mut response = HttpResponse::Ok();
+        for header in tile.response_headers() {
+            response.insert_header(header);
+        }
+        if let Some(etag) = etag {
+            response.insert_header((ETAG, etag.to_string()));
+        }
+        if let Some(last_modified) = last_modified {
+            response.insert_header(LastModified(last_modified));
+        }
+        if let Some(ids) = &self.merged_ids {
+            response.insert_header((SOURCES_HEADER, ids.join(",")));
+        }
+        if self.varies_by_encoding() {
+            response.insert_header((VARY, "Accept-Encoding"));
+        }
+        if self.varies_by_format() {
+            response.append_header((VARY, "Accept"));
+        }
+        if let Some(max_age) = self.get_cache_control_max_age() {
+            response.insert_header((CACHE_CONTROL, format!("max-age={max_age}")));
+        }
+        Ok(response.body(tile.data))
+    }
+
+    /// Respond with a tile generated for a request without tile data, see [`empty_tile`]
+    fn empty_tile_response(tile: Tile) -> HttpResponse {
+        let mut response = HttpResponse::Ok();
+        for header in tile.response_headers() {
+            response.insert_header(header);
+        }
+        response.body(tile.data)
+    }
+
+    /// Get the entity tag of the tile. Merged tiles get a hash of the component tags
+    /// in the requested order, and have no tag unless every source provides one.
+    /// The tags of the sources are cached like their tiles, so that requests answered
+    /// from the cache do not query the sources. Tiles that may be served as WebP or PNG
+    /// get a different tag for each format, see [`DynTileSource::negotiate_format`].
+    pub async fn get_etag(&self, xyz: TileCoord) -> ActixResult<Option<String>> {
+        let etags = try_join_all(self.sources.iter().map(|s| async {
+            get_or_insert_cached_value!(
+                self.cache,
+                CacheValue::TileEtag,
+                s.get_tile_etag(TileSources::source_coord(*s, xyz), self.query_obj.as_ref()),
+                {
+                    let query = self.query_obj.as_ref().map(|q| s.url_query_key(q));
+                    CacheKey::TileEtag(s.get_id().to_string(), xyz, query.unwrap_or_default())
+                }
+            )
+        }))
+        .await
+        .map_err(map_tile_error)?;
 
-        Ok(if tile.data.is_empty() {
-            HttpResponse::NoContent().finish()
+        let Some(etags) = etags.into_iter().collect::<Option<Vec<_>>>() else {
+            return Ok(None);
+        };
+        let etag = match etags.as_slice() {
+            [etag] => etag.clone(),
+            _ => format!("{:x}", md5::compute(etags.join("\n"))),
+        };
+        Ok(Some(if self.varies_by_format() {
+            format!("{etag}-{}", self.info.format)
         } else {
-            let mut response = HttpResponse::Ok();
-            response.content_type(tile.info.format.content_type());
-            if let Some(val) = tile.info.encoding.content_encoding() {
-                response.insert_header((CONTENT_ENCODING, val));
-            }
-            response.body(tile.data)
-        })
+            etag
+        }))
+    }
+
+    /// Get the time the data of the sources last changed. Merged tiles use the most recent time,
+    /// and have no time unless every source provides one.
+    #[must_use]
+    pub fn get_last_modified(&self) -> Option<SystemTime> {
+        self.sources
+            .iter()
+            .map(|s| s.last_modified())
+            .collect::<Option<Vec<_>>>()?
+            .into_iter()
+            .max()
+    }
+
+    /// Get how long the tile may be cached, in seconds. Merged tiles use the shortest
+    /// time of the sources that provide one, so that no source is cached for too long.
+    #[must_use]
+    pub fn get_cache_control_max_age(&self) -> Option<u32> {
+        self.sources
+            .iter()
+            .filter_map(|s| s.cache_control_max_age())
+            .min()
+    }
+
+    /// Weak comparison is used because the tile may be re-encoded depending on `Accept-Encoding`.
+    /// `If-Modified-Since` is ignored if `If-None-Match` is present.
+    fn is_not_modified(&self, etag: Option<&EntityTag>, last_modified: Option<HttpDate>) -> bool {
+        match (&self.if_none_match, etag) {
+            (Some(IfNoneMatch::Any), Some(_)) => true,
+            (Some(IfNoneMatch::Items(items)), Some(etag)) => items.iter().any(|v| v.weak_eq(etag)),
+            (Some(_), None) => false,
+            (None, _) => match (&self.if_modified_since, last_modified) {
+                (Some(IfModifiedSince(since)), Some(modified)) => modified <= *since,
+                _ => false,
+            },
+        }
     }
 
     pub async fn get_tile_content(&self, xyz: TileCoord) -> ActixResult<Tile> {
-        let mut tiles = try_join_all(self.sources.iter().map(|s| async {
+        if let (None, [src]) = (&self.merged_ids, self.sources.as_slice()) {
+            if !src.prefers_uncompressed() && src.get_tile_info().format == self.info.format {
+                return self.get_single_tile_content(*src, xyz).await;
+            }
+        }
+
+        let uncompressed = Self::all_prefer_uncompressed(&self.sources);
+        let tiles = try_join_all(self.sources.iter().map(|s| async {
+            let coord = TileSources::source_coord(*s, xyz);
+            if !s.covers(coord) {
+                return Ok(Tile::new(Vec::new(), s.get_tile_info()));
+            }
+            if uncompressed {
+                return s
+                    .get_tile_decoded(coord, self.query_obj.as_ref())
+                    .await
+                    .map(|(tile, _)| tile);
+            }
+            if s.get_tile_info().format != self.info.format {
+                // transcoded tiles are not cached because the cache key does not include the format
+                return s
+                    .get_tile_as(coord, self.query_obj.as_ref(), self.info.format)
+                    .await;
+            }
             get_or_insert_cached_value!(
                 self.cache,
                 CacheValue::Tile,
-                s.get_tile(xyz, self.query_obj.as_ref()),
-                {
-                    let id = s.get_id().to_string();
-                    if let Some(query_str) = self.query_str {
-                        CacheKey::TileWithQuery(id, xyz, query_str.to_string())
-                    } else {
-                        CacheKey::Tile(id, xyz)
-                    }
-                }
+                TileSources::get_source_tile(*s, xyz, self.query_obj.as_ref()),
+                self.cache_key(*s, xyz)
             )
         }))
         .await
-        .map_err(map_internal_error)?;
+        .map_err(map_tile_error)?;
+
+        // Use the actual encoding of the returned tiles, which may differ from the declared one.
+        // Tiles with a different encoding than the merged one must be decoded first.
+        let encoding = tiles
+            .iter()
+            .filter(|tile| !tile.data.is_empty())
+            .map(|tile| tile.info.encoding)
+            .reduce(TileSources::merge_encoding)
+            .unwrap_or(self.info.encoding);
+        let info = self.info.encoding(encoding);
+        let mut tiles = tiles
+            .into_iter()
+            .map(|tile| {
+                if tile.data.is_empty() || tile.info.encoding == encoding {
+                    Ok(tile)
+                } else {
+                    decode(tile)
+                }
+            })
+            .collect::<ActixResult<Vec<_>>>()?;
 
         let mut layer_count = 0;
         let mut last_non_empty_layer = 0;
         for (idx, tile) in tiles.iter().enumerate() {
-            if !tile.is_empty() {
+            if !tile.data.is_empty() {
                 layer_count += 1;
                 last_non_empty_layer = idx;
             }
@@ -150,28 +405,64 @@ impl<'a> DynTileSource<'a> {
 
         // Minor optimization to prevent concatenation if there are less than 2 tiles
         let data = match layer_count {
-            1 => tiles.swap_remove(last_non_empty_layer),
+            1 => tiles.swap_remove(last_non_empty_layer).data,
             0 => return Ok(Tile::new(Vec::new(), self.info)),
             _ => {
                 // Make sure tiles can be concatenated, or if not, that there is only one non-empty tile for each zoom level
                 // TODO: can zlib, brotli, or zstd be concatenated?
                 // TODO: implement decompression step for other concatenate-able formats
-                let can_join = self.info.format == Format::Mvt
-                    && (self.info.encoding == Encoding::Uncompressed
-                        || self.info.encoding == Encoding::Gzip);
+                let can_join = info.format == Format::Mvt
+                    && (info.encoding == Encoding::Uncompressed || info.encoding == Encoding::Gzip);
                 if !can_join {
                     return Err(ErrorBadRequest(format!(
-                        "Can't merge {} tiles. Make sure there is only one non-empty tile source at zoom level {}",
-                        self.info,
+                        "Can't merge {info} tiles. Make sure there is only one non-empty tile source at zoom level {}",
                         xyz.z
                     )))?;
                 }
-                tiles.concat()
+                concat_mvt(&tiles).data
             }
         };
 
+        if uncompressed {
+            return Ok(Tile::new(data, info));
+        }
         // decide if (re-)encoding of the tile data is needed, and recompress if so
-        self.recompress(data)
+        self.recompress(Tile::new(data, info))
+    }
+
+    /// Get the tile of a request for a single source that neither prefers uncompressed tiles
+    /// nor needs transcoding, without the merging logic of [`DynTileSource::get_tile_content`].
+    async fn get_single_tile_content(&self, src: &dyn Source, xyz: TileCoord) -> ActixResult<Tile> {
+        let tile = get_or_insert_cached_value!(
+            self.cache,
+            CacheValue::Tile,
+            async {
+                let tile = self
+                    .tile_sources
+                    .get_single_source_tile(src.get_id(), xyz, self.query_obj.as_ref(), None)
+                    .await?;
+                Ok::<_, actix_web::Error>(match tile {
+                    Some((tile, _)) => tile,
+                    None => Tile::new(Vec::new(), src.get_tile_info()),
+                })
+            },
+            self.cache_key(src, xyz)
+        )?;
+        if tile.data.is_empty() {
+            return Ok(Tile::new(Vec::new(), self.info));
+        }
+        // use the actual encoding of the tile, which may differ from the declared one
+        let info = self.info.encoding(tile.info.encoding);
+        self.recompress(Tile::new(tile.data, info))
+    }
+
+    /// The key of a source tile in the main cache, including the URL query if the source uses it
+    fn cache_key(&self, src: &dyn Source, xyz: TileCoord) -> CacheKey {
+        let id = src.get_id().to_string();
+        match self.query_obj.as_ref().map(|q| src.url_query_key(q)) {
+            Some(key) if !key.is_empty() => CacheKey::TileWithQuery(id, xyz, key),
+            _ => CacheKey::Tile(id, xyz),
+        }
     }
 
     /// Decide which encoding to use for the uncompressed tile data, based on the client's Accept-Encoding header
@@ -217,10 +508,9 @@ impl<'a> DynTileSource<'a> {
         }
     }
 
-    fn recompress(&self, tile: TileData) -> ActixResult<Tile> {
-        let mut tile = Tile::new(tile, self.info);
+    fn recompress(&self, mut tile: Tile) -> ActixResult<Tile> {
         if let Some(accept_enc) = &self.accept_enc {
-            if self.info.encoding.is_encoded() {
+            if tile.info.encoding.is_encoded() {
                 // already compressed, see if we can send it as is, or need to re-compress
                 if !accept_enc.iter().any(|e| {
                     if let Preference::Specific(HeaderEnc::Known(enc)) = e.item {
@@ -235,9 +525,9 @@ impl<'a> DynTileSource<'a> {
             }
 
             if tile.info.encoding == Encoding::Uncompressed {
-                if let Some(enc) = self.decide_encoding(accept_enc)? {
+                if let Some(enc) = self.decide_encoding(accept_enc)?.and_then(to_encoding) {
                     // (re-)compress the tile into the preferred encoding
-                    tile = encode(tile, enc)?;
+                    tile = tile.encode(enc)?;
                 }
             }
 
@@ -249,38 +539,8 @@ impl<'a> DynTileSource<'a> {
     }
 }
 
-fn encode(tile: Tile, enc: ContentEncoding) -> ActixResult<Tile> {
-    Ok(match enc {
-        ContentEncoding::Brotli => Tile::new(
-            encode_brotli(&tile.data)?,
-            tile.info.encoding(Encoding::Brotli),
-        ),
-        ContentEncoding::Gzip => {
-            Tile::new(encode_gzip(&tile.data)?, tile.info.encoding(Encoding::Gzip))
-        }
-        _ => tile,
-    })
-}
-
 fn decode(tile: Tile) -> ActixResult<Tile> {
-    let info = tile.info;
-    Ok(if info.encoding.is_encoded() {
-        match info.encoding {
-            Encoding::Gzip => Tile::new(
-                decode_gzip(&tile.data)?,
-                info.encoding(Encoding::Uncompressed),
-            ),
-            Encoding::Brotli => Tile::new(
-                decode_brotli(&tile.data)?,
-                info.encoding(Encoding::Uncompressed),
-            ),
-            _ => Err(ErrorBadRequest(format!(
-                "Tile is is stored as {info}, but the client does not accept this encoding"
-            )))?,
-        }
-    } else {
-        tile
-    })
+    Ok(tile.decode()?)
 }
 
 pub fn to_encoding(val: ContentEncoding) -> Option<Encoding> {
@@ -293,13 +553,39 @@ pub fn to_encoding(val: ContentEncoding) -> Option<Encoding> {
     })
 }
 
+/// Map a tile error to the status of the [`SourceError`](crate::SourceError) that caused it, if any,
+/// e.g. `504 Gateway Timeout` for [`SourceError::Timeout`](crate::SourceError::Timeout).
+/// Other errors are internal server errors.
+pub fn map_tile_error(e: MartinError) -> actix_web::Error {
+    let mut cause = &e;
+    while let MartinError::TileError(_, _, inner) = cause {
+        cause = inner;
+    }
+    match cause {
+        MartinError::SourceError(err) => {
+            let status = err.status_code();
+            if status.is_server_error() {
+                error!("{e}");
+            }
+            InternalError::new(e.to_string(), status).into()
+        }
+        _ => map_internal_error(e),
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use std::time::Duration;
+
+    use actix_web::http::StatusCode;
+    use martin_tile_utils::{decode_gzip, encode_gzip};
     use rstest::rstest;
     use tilejson::tilejson;
 
     use super::*;
-    use crate::srv::server::tests::TestSource;
+    use crate::source::delegate::delegate_source;
+    use crate::source::{TileData, TileInfoSource};
+    use crate::{MartinResult, TestSource, TimeoutSource, ZoomShiftSource};
 
     #[actix_rt::test]
     async fn test_deleteme() {
@@ -323,6 +609,7 @@ mod tests {
         let sources = TileSources::new(vec![vec![Box::new(TestSource {
             id: "test_source",
             tj: tilejson! { tiles: vec![] },
+            info: TileInfo::new(Format::Mvt, Encoding::Uncompressed),
             data: vec![1_u8, 2, 3],
         })]]);
 
@@ -351,11 +638,13 @@ mod tests {
         let non_empty_source = TestSource {
             id: "non-empty",
             tj: tilejson! { tiles: vec![] },
+            info: TileInfo::new(Format::Mvt, Encoding::Uncompressed),
             data: vec![1_u8, 2, 3],
         };
         let empty_source = TestSource {
             id: "empty",
             tj: tilejson! { tiles: vec![] },
+            info: TileInfo::new(Format::Mvt, Encoding::Uncompressed),
             data: Vec::default(),
         };
         let sources = TileSources::new(vec![vec![
@@ -378,4 +667,689 @@ mod tests {
             assert_eq!(expected, &src.get_tile_content(xyz).await.unwrap().data);
         }
     }
+
+    #[actix_rt::test]
+    async fn test_missing_tile_response() {
+        let sources = TileSources::new(vec![vec![Box::new(TestSource {
+            id: "png",
+            tj: tilejson! { tiles: vec![], maxzoom: 5 },
+            info: Format::Png.into(),
+            data: vec![1_u8, 2, 3],
+        })]]);
+        let xyz = TileCoord { z: 6, x: 0, y: 0 };
+        let mut src = DynTileSource::new(&sources, "png", Some(6), "", None, None, None).unwrap();
+        assert!(src.sources.is_empty());
+
+        let err = src.get_http_response(xyz).await.unwrap_err();
+        assert_eq!(err.error_response().status(), StatusCode::NOT_FOUND);
+
+        src.missing_tile = MissingTileResponse::NoContent;
+        let resp = src.get_http_response(xyz).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+
+        src.missing_tile = MissingTileResponse::EmptyTile;
+        let resp = src.get_http_response(xyz).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers().get("Content-Type").unwrap(), "image/png");
+    }
+
+    #[actix_rt::test]
+    async fn test_merge_mixed_encodings() {
+        let gzip_source = TestSource {
+            id: "gzip",
+            tj: tilejson! { tiles: vec![] },
+            info: TileInfo::new(Format::Mvt, Encoding::Gzip),
+            data: encode_gzip(&[1_u8, 2, 3]).unwrap(),
+        };
+        let raw_source = TestSource {
+            id: "raw",
+            tj: tilejson! { tiles: vec![] },
+            info: TileInfo::new(Format::Mvt, Encoding::Uncompressed),
+            data: vec![4_u8, 5],
+        };
+        let png_source = TestSource {
+            id: "png",
+            tj: tilejson! { tiles: vec![] },
+            info: Format::Png.into(),
+            data: vec![6_u8],
+        };
+        let sources = TileSources::new(vec![vec![
+            Box::new(gzip_source),
+            Box::new(raw_source),
+            Box::new(png_source),
+        ]]);
+
+        let src = DynTileSource::new(&sources, "gzip,raw", None, "", None, None, None).unwrap();
+        assert_eq!(src.info, TileInfo::new(Format::Mvt, Encoding::Uncompressed));
+        assert_eq!(src.merged_ids, Some(vec!["gzip".into(), "raw".into()]));
+        let xyz = TileCoord { z: 0, x: 0, y: 0 };
+        let tile = src.get_tile_content(xyz).await.unwrap();
+        assert_eq!(tile.data, vec![1_u8, 2, 3, 4, 5]);
+        assert_eq!(tile.info.encoding, Encoding::Uncompressed);
+
+        let resp = src.get_http_response(xyz).await.unwrap();
+        assert_eq!(resp.headers().get(SOURCES_HEADER).unwrap(), "gzip,raw");
+
+        let src = DynTileSource::new(&sources, "raw", None, "", None, None, None).unwrap();
+        assert_eq!(src.merged_ids, None);
+        let resp = src.get_http_response(xyz).await.unwrap();
+        assert!(resp.headers().get(SOURCES_HEADER).is_none());
+
+        let accept_enc = Some(AcceptEncoding(vec!["gzip".parse().unwrap()]));
+        let src =
+            DynTileSource::new(&sources, "raw,gzip", None, "", accept_enc, None, None).unwrap();
+        let tile = src.get_tile_content(xyz).await.unwrap();
+        assert_eq!(tile.info.encoding, Encoding::Gzip);
+        assert_eq!(decode_gzip(&tile.data).unwrap(), vec![4_u8, 5, 1, 2, 3]);
+
+        assert!(DynTileSource::new(&sources, "gzip,png", None, "", None, None, None).is_err());
+    }
+
+    /// Wraps a [`TestSource`] to change some of its behavior, forwarding everything else.
+    /// Tests create it with [`mock`], and only set the fields of the behavior they check.
+    #[derive(Debug, Clone)]
+    struct MockSource {
+        source: TestSource,
+        /// Declared tile info, if different from the info of the returned tiles
+        info: Option<TileInfo>,
+        /// Whether a tile is covered, the source panics if asked for any other tile
+        covers: fn(TileCoord) -> bool,
+        /// Never responds with a tile
+        stalled: bool,
+        prefers_uncompressed: bool,
+        uses_tms: bool,
+        max_age: Option<u32>,
+        etag: Option<&'static str>,
+        miss_behavior: MissBehavior,
+        /// Encodings the tiles can be served with, in addition to the stored one
+        encodings: Vec<Encoding>,
+        /// Formats the tiles can be transcoded to, by reversing their data
+        formats: Vec<Format>,
+        required_query_keys: &'static [&'static str],
+    }
+
+    fn mock(source: TestSource) -> MockSource {
+        MockSource {
+            source,
+            info: None,
+            covers: |_| true,
+            stalled: false,
+            prefers_uncompressed: false,
+            uses_tms: false,
+            max_age: None,
+            etag: None,
+            miss_behavior: MissBehavior::default(),
+            encodings: Vec::new(),
+            formats: Vec::new(),
+            required_query_keys: &[],
+        }
+    }
+
+    delegate_source! {
+        impl Source for MockSource => self.source;
+        except [
+            get_tile_info, covers, prefers_uncompressed, uses_tms, cache_control_max_age,
+            get_tile_etag, miss_behavior, available_encodings, supported_formats, can_encode,
+            support_url_query, required_query_keys,
+            get_tile, get_tile_with_info, get_tile_opt, get_tile_decoded, get_tile_as, has_tile,
+        ];
+
+        fn get_tile_info(&self) -> TileInfo {
+            self.info.unwrap_or(self.source.info)
+        }
+
+        fn covers(&self, xyz: TileCoord) -> bool {
+            (self.covers)(xyz)
+        }
+
+        fn prefers_uncompressed(&self) -> bool {
+            self.prefers_uncompressed
+        }
+
+        fn uses_tms(&self) -> bool {
+            self.uses_tms
+        }
+
+        fn cache_control_max_age(&self) -> Option<u32> {
+            self.max_age
+        }
+
+        async fn get_tile_etag(
+            &self,
+            _xyz: TileCoord,
+            _url_query: Option<&UrlQuery>,
+        ) -> MartinResult<Option<String>> {
+            Ok(self.etag.map(str::to_string))
+        }
+
+        fn miss_behavior(&self) -> MissBehavior {
+            self.miss_behavior
+        }
+
+        fn available_encodings(&self) -> Vec<Encoding> {
+            let mut encodings = vec![self.get_tile_info().encoding];
+            encodings.extend(&self.encodings);
+            encodings
+        }
+
+        fn supported_formats(&self) -> Vec<Format> {
+            let mut formats = vec![self.get_tile_info().format];
+            formats.extend(&self.formats);
+            formats
+        }
+
+        fn support_url_query(&self) -> bool {
+            !self.required_query_keys.is_empty()
+        }
+
+        fn required_query_keys(&self) -> &[&str] {
+            self.required_query_keys
+        }
+
+        async fn get_tile(
+            &self,
+            xyz: TileCoord,
+            url_query: Option<&UrlQuery>,
+        ) -> MartinResult<TileData> {
+            assert!(self.covers(xyz), "requested uncovered tile {xyz}");
+            if self.stalled {
+                std::future::pending::<()>().await;
+            }
+            self.source.get_tile(xyz, url_query).await
+        }
+
+        async fn get_tile_with_info(
+            &self,
+            xyz: TileCoord,
+            url_query: Option<&UrlQuery>,
+        ) -> MartinResult<Tile> {
+            let data = self.get_tile(xyz, url_query).await?;
+            Ok(Tile::new(data, self.source.get_tile_info()))
+        }
+
+        async fn get_tile_as(
+            &self,
+            xyz: TileCoord,
+            url_query: Option<&UrlQuery>,
+            format: Format,
+        ) -> MartinResult<Tile> {
+            assert!(self.can_encode(format), "requested unsupported format {format}");
+            let mut tile = self.get_tile_with_info(xyz, url_query).await?;
+            if format != tile.info.format {
+                // pretend to transcode the tile
+                tile.data.reverse();
+                tile.info = format.into();
+            }
+            Ok(tile)
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_per_tile_info() {
+        // declares gzip encoding, but returns uncompressed tiles, reporting it per tile
+        let source = MockSource {
+            info: Some(TileInfo::new(Format::Mvt, Encoding::Gzip)),
+            ..mock(TestSource {
+                id: "per_tile",
+                tj: tilejson! { tiles: vec![] },
+                info: TileInfo::new(Format::Mvt, Encoding::Uncompressed),
+                data: vec![1_u8, 2, 3],
+            })
+        };
+        let sources = TileSources::new(vec![vec![Box::new(source)]]);
+
+        let src = DynTileSource::new(&sources, "per_tile", None, "", None, None, None).unwrap();
+        assert_eq!(src.info.encoding, Encoding::Gzip);
+        let xyz = TileCoord { z: 0, x: 0, y: 0 };
+        let tile = src.get_tile_content(xyz).await.unwrap();
+        assert_eq!(tile.info.encoding, Encoding::Uncompressed);
+        assert_eq!(tile.data, vec![1_u8, 2, 3]);
+    }
+
+    #[actix_rt::test]
+    async fn test_uncovered_tiles() {
+        let even = MockSource {
+            covers: |xyz| xyz.x % 2 == 0,
+            ..mock(TestSource {
+                id: "even",
+                tj: tilejson! { tiles: vec![] },
+                info: TileInfo::new(Format::Mvt, Encoding::Uncompressed),
+                data: vec![1_u8, 2],
+            })
+        };
+        let all = TestSource {
+            id: "all",
+            tj: tilejson! { tiles: vec![] },
+            info: TileInfo::new(Format::Mvt, Encoding::Uncompressed),
+            data: vec![3_u8],
+        };
+        let sources = TileSources::new(vec![vec![Box::new(even), Box::new(all)]]);
+        let src = DynTileSource::new(&sources, "even,all", None, "", None, None, None).unwrap();
+
+        let tile = src.get_tile_content(TileCoord { z: 1, x: 0, y: 0 }).await;
+        assert_eq!(tile.unwrap().data, vec![1_u8, 2, 3]);
+        let tile = src.get_tile_content(TileCoord { z: 1, x: 1, y: 0 }).await;
+        assert_eq!(tile.unwrap().data, vec![3_u8]);
+    }
+
+    #[actix_rt::test]
+    async fn test_tms_sources() {
+        let source = |id, data, uses_tms, covers| MockSource {
+            covers,
+            uses_tms,
+            ..mock(TestSource {
+                id,
+                tj: tilejson! { tiles: vec![] },
+                info: TileInfo::new(Format::Mvt, Encoding::Uncompressed),
+                data,
+            })
+        };
+        // both sources only have the top row of tiles at zoom 2
+        let sources = TileSources::new(vec![vec![
+            Box::new(source("tms", vec![1_u8], true, |xyz| xyz.y == 3)),
+            Box::new(source("xyz", vec![2_u8], false, |xyz| xyz.y == 0)),
+        ]]);
+        let tile = |ids, y| {
+            let src = DynTileSource::new(&sources, ids, None, "", None, None, None).unwrap();
+            async move {
+                let tile = src.get_tile_content(TileCoord { z: 2, x: 1, y }).await;
+                tile.unwrap().data
+            }
+        };
+
+        assert_eq!(tile("tms", 0).await, vec![1_u8]);
+        assert_eq!(tile("xyz", 0).await, vec![2_u8]);
+        assert_eq!(tile("tms,xyz", 0).await, vec![1_u8, 2]);
+        assert!(tile("tms,xyz", 3).await.is_empty());
+    }
+
+    #[actix_rt::test]
+    async fn test_prefers_uncompressed() {
+        let source = MockSource {
+            prefers_uncompressed: true,
+            ..mock(TestSource {
+                id: "plain",
+                tj: tilejson! { tiles: vec![] },
+                info: TileInfo::new(Format::Mvt, Encoding::Gzip),
+                data: encode_gzip(&[1_u8, 2, 3]).unwrap(),
+            })
+        };
+        let gzip_source = TestSource {
+            id: "gzip",
+            tj: tilejson! { tiles: vec![] },
+            info: TileInfo::new(Format::Mvt, Encoding::Gzip),
+            data: encode_gzip(&[4_u8]).unwrap(),
+        };
+        let sources = TileSources::new(vec![vec![Box::new(source), Box::new(gzip_source)]]);
+        let xyz = TileCoord { z: 0, x: 0, y: 0 };
+
+        let accept_enc = || Some(AcceptEncoding(vec!["gzip".parse().unwrap()]));
+        let src =
+            DynTileSource::new(&sources, "plain", None, "", accept_enc(), None, None).unwrap();
+        assert_eq!(src.info, TileInfo::new(Format::Mvt, Encoding::Uncompressed));
+        let tile = src.get_tile_content(xyz).await.unwrap();
+        assert_eq!(tile.info, src.info);
+        assert_eq!(tile.data, vec![1_u8, 2, 3]);
+
+        let resp = src.get_http_response(xyz).await.unwrap();
+        assert!(resp.headers().get("Content-Encoding").is_none());
+
+        // other sources are still compressed for clients that accept it
+        let src =
+            DynTileSource::new(&sources, "plain,gzip", None, "", accept_enc(), None, None).unwrap();
+        assert_eq!(src.info.encoding, Encoding::Gzip);
+        let tile = src.get_tile_content(xyz).await.unwrap();
+        assert_eq!(tile.info.encoding, Encoding::Gzip);
+    }
+
+    #[actix_rt::test]
+    async fn test_cache_control() {
+        let source = |id| TestSource {
+            id,
+            tj: tilejson! { tiles: vec![] },
+            info: TileInfo::new(Format::Mvt, Encoding::Uncompressed),
+            data: vec![1_u8],
+        };
+        let max_age = |id, max_age| MockSource {
+            max_age: Some(max_age),
+            ..mock(source(id))
+        };
+        let sources = TileSources::new(vec![vec![
+            Box::new(max_age("static", 86400)),
+            Box::new(max_age("live", 60)),
+            Box::new(source("plain")),
+        ]]);
+        let xyz = TileCoord { z: 0, x: 0, y: 0 };
+        let cache_control = |ids| {
+            let src = DynTileSource::new(&sources, ids, None, "", None, None, None).unwrap();
+            async move {
+                let resp = src.get_http_response(xyz).await.unwrap();
+                resp.headers().get(CACHE_CONTROL).cloned()
+            }
+        };
+
+        assert!(cache_control("plain").await.is_none());
+        assert_eq!(cache_control("static").await.unwrap(), "max-age=86400");
+        assert_eq!(cache_control("static,live").await.unwrap(), "max-age=60");
+        assert_eq!(
+            cache_control("live,static,plain").await.unwrap(),
+            "max-age=60"
+        );
+        assert_eq!(
+            cache_control("plain,static").await.unwrap(),
+            "max-age=86400"
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_etag() {
+        let source = |id, etag, formats| MockSource {
+            etag,
+            formats,
+            ..mock(TestSource {
+                id,
+                tj: tilejson! { tiles: vec![] },
+                info: Format::Png.into(),
+                data: b"png".to_vec(),
+            })
+        };
+        let sources = TileSources::new(vec![vec![
+            Box::new(source("webp", Some("a"), vec![Format::Webp])),
+            Box::new(source("png", Some("b"), Vec::new())),
+            Box::new(source("untagged", None, Vec::new())),
+        ]]);
+        let cache = MainCache::new(100);
+        let xyz = TileCoord { z: 0, x: 0, y: 0 };
+        let etag = |ids, accept: &'static str| {
+            let mut src =
+                DynTileSource::new(&sources, ids, None, "", None, None, Some(&cache)).unwrap();
+            src.negotiate_format(Some(&Accept(vec![accept.parse().unwrap()])));
+            async move { src.get_etag(xyz).await.unwrap() }
+        };
+
+        assert_eq!(etag("png", "image/webp").await.as_deref(), Some("b"));
+        assert_eq!(etag("untagged", "image/png").await, None);
+        assert_eq!(etag("png,untagged", "image/png").await, None);
+        let merged = etag("png,png", "image/png").await.unwrap();
+        assert_ne!(merged, "b");
+
+        // WebP and PNG tiles have different content
+        assert_eq!(etag("webp", "image/webp").await.as_deref(), Some("a-webp"));
+        assert_eq!(etag("webp", "image/png").await.as_deref(), Some("a-png"));
+
+        // the tags are cached, so that cached tiles do not query the sources
+        let key = CacheKey::TileEtag("webp".to_string(), xyz, String::new());
+        let cached = cache.get(&key).await;
+        assert!(matches!(cached, Some(CacheValue::TileEtag(Some(tag))) if tag == "a"));
+    }
+
+    #[actix_rt::test]
+    async fn test_webp_negotiation() {
+        let png = |id| TestSource {
+            id,
+            tj: tilejson! { tiles: vec![] },
+            info: Format::Png.into(),
+            data: b"png".to_vec(),
+        };
+        let sources = TileSources::new(vec![vec![
+            Box::new(MockSource {
+                formats: vec![Format::Webp],
+                ..mock(png("webp"))
+            }),
+            Box::new(png("png")),
+        ]]);
+        let xyz = TileCoord { z: 0, x: 0, y: 0 };
+        let response = |id, accept: Option<&'static str>| {
+            let mut req = actix_web::test::TestRequest::default();
+            if let Some(accept) = accept {
+                req = req.insert_header((actix_web::http::header::ACCEPT, accept));
+            }
+            let accept = req.to_http_request().get_header::<Accept>();
+            let mut src = DynTileSource::new(&sources, id, None, "", None, None, None).unwrap();
+            src.negotiate_format(accept.as_ref());
+            async move {
+                let resp = src.get_http_response(xyz).await.unwrap();
+                let header = |name| {
+                    resp.headers()
+                        .get(name)
+                        .map(|v| v.to_str().unwrap().to_string())
+                };
+                let (content_type, vary) = (header("content-type"), header(VARY.as_str()));
+                let body = actix_web::body::to_bytes(resp.into_body()).await.unwrap();
+                (content_type.unwrap(), vary, body)
+            }
+        };
+
+        let (content_type, vary, body) = response("webp", Some("image/webp,*/*;q=0.8")).await;
+        assert_eq!(content_type, "image/webp");
+        assert_eq!(vary.as_deref(), Some("Accept"));
+        assert_eq!(body, "gnp");
+
+        for accept in [None, Some("image/png,*/*;q=0.8"), Some("image/webp;q=0")] {
+            let (content_type, vary, body) = response("webp", accept).await;
+            assert_eq!(content_type, "image/png");
+            assert_eq!(vary.as_deref(), Some("Accept"));
+            assert_eq!(body, "png");
+        }
+
+        // sources that cannot produce WebP are not negotiated
+        let (content_type, vary, body) = response("png", Some("image/webp")).await;
+        assert_eq!(content_type, "image/png");
+        assert_eq!(vary, None);
+        assert_eq!(body, "png");
+    }
+
+    #[actix_rt::test]
+    async fn test_miss_behavior() {
+        let source = |id, format: Format, miss| -> TileInfoSource {
+            Box::new(MockSource {
+                miss_behavior: miss,
+                ..mock(TestSource {
+                    id,
+                    tj: tilejson! { tiles: vec![] },
+                    info: format.into(),
+                    data: Vec::default(),
+                })
+            })
+        };
+        let sources = TileSources::new(vec![vec![
+            source("default", Format::Mvt, MissBehavior::default()),
+            source("empty", Format::Mvt, MissBehavior::Empty),
+            source("empty_png", Format::Png, MissBehavior::Empty),
+            source("transparent", Format::Webp, MissBehavior::Transparent),
+        ]]);
+        let xyz = TileCoord { z: 0, x: 0, y: 0 };
+        let response = |ids| {
+            let src = DynTileSource::new(&sources, ids, None, "", None, None, None).unwrap();
+            async move { src.get_http_response(xyz).await }
+        };
+
+        let err = response("default").await.unwrap_err();
+        assert_eq!(err.error_response().status(), StatusCode::NOT_FOUND);
+
+        let resp = response("empty").await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.headers().get("Content-Type").unwrap(),
+            "application/x-protobuf"
+        );
+        let resp = response("empty_png").await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers().get("Content-Type").unwrap(), "image/png");
+
+        let resp = response("transparent").await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers().get("Content-Type").unwrap(), "image/png");
+        let body = actix_web::body::to_bytes(resp.into_body()).await.unwrap();
+        assert_eq!(body, empty_tile(Format::Png.into()).data);
+
+        // composite requests respond like their first source
+        let resp = response("empty,default").await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let err = response("default,empty").await.unwrap_err();
+        assert_eq!(err.error_response().status(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_rt::test]
+    async fn test_source_error_status() {
+        let source = |id| TestSource {
+            id,
+            tj: tilejson! { tiles: vec![] },
+            info: Format::Mvt.into(),
+            data: vec![1_u8],
+        };
+        let sources = TileSources::new(vec![vec![
+            Box::new(TimeoutSource::new(
+                Box::new(MockSource {
+                    stalled: true,
+                    ..mock(source("stalled"))
+                }),
+                Duration::from_millis(10),
+            )),
+            Box::new(ZoomShiftSource::new(Box::new(source("shifted")), 1)),
+            Box::new(source("ok")),
+        ]]);
+        let status = |ids, xyz| {
+            let src = DynTileSource::new(&sources, ids, None, "", None, None, None).unwrap();
+            async move {
+                match src.get_http_response(xyz).await {
+                    Ok(resp) => resp.status(),
+                    Err(err) => err.error_response().status(),
+                }
+            }
+        };
+
+        let xyz = TileCoord { z: 0, x: 0, y: 0 };
+        assert_eq!(status("stalled", xyz).await, StatusCode::GATEWAY_TIMEOUT);
+        assert_eq!(status("ok,stalled", xyz).await, StatusCode::GATEWAY_TIMEOUT);
+        assert_eq!(status("shifted", xyz).await, StatusCode::NOT_FOUND);
+        let xyz = TileCoord { z: 1, x: 0, y: 0 };
+        assert_eq!(status("shifted", xyz).await, StatusCode::OK);
+    }
+
+    #[actix_rt::test]
+    async fn test_vary_header() {
+        let source = |id| TestSource {
+            id,
+            tj: tilejson! { tiles: vec![] },
+            info: TileInfo::new(Format::Mvt, Encoding::Gzip),
+            data: encode_gzip(&[1_u8]).unwrap(),
+        };
+        let sources = TileSources::new(vec![vec![
+            Box::new(MockSource {
+                encodings: vec![Encoding::Uncompressed],
+                ..mock(source("multi"))
+            }),
+            Box::new(source("single")),
+            Box::new(TestSource {
+                id: "png",
+                tj: tilejson! { tiles: vec![] },
+                info: TileInfo::new(Format::Png, Encoding::Internal),
+                data: vec![1_u8],
+            }),
+        ]]);
+        let xyz = TileCoord { z: 0, x: 0, y: 0 };
+        let vary = |ids| {
+            let sources = &sources;
+            async move {
+                let src = DynTileSource::new(sources, ids, None, "", None, None, None).unwrap();
+                let resp = src.get_http_response(xyz).await.unwrap();
+                resp.headers().get(VARY).cloned()
+            }
+        };
+
+        assert!(vary("png").await.is_none());
+        assert_eq!(vary("single").await.unwrap(), "Accept-Encoding");
+        assert_eq!(vary("multi").await.unwrap(), "Accept-Encoding");
+        assert_eq!(vary("single,multi").await.unwrap(), "Accept-Encoding");
+
+        let catalog = sources.get_catalog();
+        let encodings = catalog["multi"].available_encodings.as_deref();
+        assert_eq!(
+            encodings,
+            Some(&["gzip".to_string(), "identity".to_string()][..])
+        );
+        assert!(catalog["single"].available_encodings.is_none());
+    }
+
+    #[actix_rt::test]
+    async fn test_merge_transcoded() {
+        let webp = MockSource {
+            formats: vec![Format::Png],
+            ..mock(TestSource {
+                id: "webp",
+                tj: tilejson! { tiles: vec![] },
+                info: Format::Webp.into(),
+                data: vec![1_u8, 2, 3],
+            })
+        };
+        let png = TestSource {
+            id: "png",
+            tj: tilejson! { tiles: vec![] },
+            info: Format::Png.into(),
+            data: Vec::default(),
+        };
+        let mvt = TestSource {
+            id: "mvt",
+            tj: tilejson! { tiles: vec![] },
+            info: TileInfo::new(Format::Mvt, Encoding::Uncompressed),
+            data: Vec::default(),
+        };
+        let sources = TileSources::new(vec![vec![Box::new(webp), Box::new(png), Box::new(mvt)]]);
+        let xyz = TileCoord { z: 0, x: 0, y: 0 };
+
+        let src = DynTileSource::new(&sources, "webp", None, "", None, None, None).unwrap();
+        assert_eq!(src.info.format, Format::Webp);
+        let tile = src.get_tile_content(xyz).await.unwrap();
+        assert_eq!(tile.data, vec![1_u8, 2, 3]);
+
+        for ids in ["webp,png", "png,webp"] {
+            let src = DynTileSource::new(&sources, ids, None, "", None, None, None).unwrap();
+            assert_eq!(src.info.format, Format::Png);
+            let tile = src.get_tile_content(xyz).await.unwrap();
+            assert_eq!(tile.info.format, Format::Png);
+            assert_eq!(tile.data, vec![3_u8, 2, 1]);
+        }
+
+        assert!(DynTileSource::new(&sources, "webp,mvt", None, "", None, None, None).is_err());
+    }
+
+    #[actix_rt::test]
+    async fn test_required_query_keys() {
+        let filtered = MockSource {
+            required_query_keys: &["time"],
+            ..mock(TestSource {
+                id: "filtered",
+                tj: tilejson! { tiles: vec![] },
+                info: TileInfo::new(Format::Mvt, Encoding::Uncompressed),
+                data: vec![1_u8],
+            })
+        };
+        let other = TestSource {
+            id: "other",
+            tj: tilejson! { tiles: vec![] },
+            info: TileInfo::new(Format::Mvt, Encoding::Uncompressed),
+            data: vec![2_u8],
+        };
+        let sources = TileSources::new(vec![vec![Box::new(filtered), Box::new(other)]]);
+        let new = |ids, query| DynTileSource::new(&sources, ids, None, query, None, None, None);
+
+        for (ids, query) in [
+            ("filtered", ""),
+            ("filtered", "date=2024"),
+            ("other,filtered", "date=2024"),
+        ] {
+            let err = new(ids, query).err().unwrap();
+            assert_eq!(
+                err.to_string(),
+                "Source filtered requires the URL query parameter time"
+            );
+            assert_eq!(err.error_response().status(), StatusCode::BAD_REQUEST);
+        }
+
+        assert!(new("other", "").is_ok());
+        let src = new("other,filtered", "time=2024").unwrap();
+        let tile = src.get_tile_content(TileCoord { z: 0, x: 0, y: 0 }).await;
+        assert_eq!(tile.unwrap().data, vec![2_u8, 1]);
+    }
 }
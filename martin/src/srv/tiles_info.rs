@@ -9,7 +9,7 @@ use serde::Deserialize;
 use tilejson::{tilejson, TileJSON};
 
 use crate::source::{Source, TileSources};
-use crate::srv::SrvConfig;
+use crate::srv::{SourceAuthorizer, SrvConfig};
 
 #[derive(Deserialize)]
 pub struct SourceIDsRequest {
@@ -29,7 +29,10 @@ async fn get_source_info(
     sources: Data<TileSources>,
     srv_config: Data<SrvConfig>,
 ) -> ActixResult<HttpResponse> {
-    let sources = sources.get_sources(&path.source_ids, None)?.0;
+    let authorize = SourceAuthorizer::for_request(&req);
+    let sources = sources
+        .get_sources_authorized(&path.source_ids, None, Some(&authorize))?
+        .0;
 
     let tiles_path = if let Some(base_path) = &srv_config.base_path {
         format!("{base_path}/{}", path.source_ids)
@@ -61,6 +64,19 @@ async fn get_source_info(
     Ok(HttpResponse::Ok().json(merge_tilejson(&sources, tiles_url)))
 }
 
+/// Combine the distinct non-empty attributions of the sources in order, one per line.
+/// Returns `None` if none of the sources have an attribution.
+#[must_use]
+pub fn merge_attributions(sources: &[&dyn Source]) -> Option<String> {
+    let attributions = sources
+        .iter()
+        .filter_map(|src| src.get_tilejson().attribution.as_deref())
+        .filter(|v| !v.is_empty())
+        .unique()
+        .join("\n");
+    (!attributions.is_empty()).then_some(attributions)
+}
+
 #[must_use]
 pub fn merge_tilejson(sources: &[&dyn Source], tiles_url: String) -> TileJSON {
     if sources.len() == 1 {
@@ -69,7 +85,6 @@ pub fn merge_tilejson(sources: &[&dyn Source], tiles_url: String) -> TileJSON {
         return tj;
     }
 
-    let mut attributions = vec![];
     let mut descriptions = vec![];
     let mut names = vec![];
     let mut result = tilejson! {
@@ -87,12 +102,6 @@ pub fn merge_tilejson(sources: &[&dyn Source], tiles_url: String) -> TileJSON {
             }
         }
 
-        if let Some(v) = &tj.attribution {
-            if !attributions.contains(&v) {
-                attributions.push(v);
-            }
-        }
-
         if let Some(bounds) = tj.bounds {
             if let Some(a) = result.bounds {
                 result.bounds = Some(a + bounds);
@@ -139,9 +148,7 @@ pub fn merge_tilejson(sources: &[&dyn Source], tiles_url: String) -> TileJSON {
         }
     }
 
-    if !attributions.is_empty() {
-        result.attribution = Some(attributions.into_iter().join("\n"));
-    }
+    result.attribution = merge_attributions(sources);
 
     if !descriptions.is_empty() {
         result.description = Some(descriptions.into_iter().join("\n"));
@@ -158,10 +165,11 @@ pub fn merge_tilejson(sources: &[&dyn Source], tiles_url: String) -> TileJSON {
 pub mod tests {
     use std::collections::BTreeMap;
 
+    use martin_tile_utils::{Encoding, Format, TileInfo};
     use tilejson::{Bounds, VectorLayer};
 
     use super::*;
-    use crate::srv::server::tests::TestSource;
+    use crate::TestSource;
 
     #[test]
     fn test_merge_tilejson() {
@@ -181,6 +189,7 @@ pub mod tests {
                     ]))
                 ],
             },
+            info: TileInfo::new(Format::Mvt, Encoding::Uncompressed),
             data: Vec::default(),
         };
         let tj = merge_tilejson(&[&src1], url.clone());
@@ -207,6 +216,7 @@ pub mod tests {
                     ]))
                 ],
             },
+            info: TileInfo::new(Format::Mvt, Encoding::Uncompressed),
             data: Vec::default(),
         };
 
@@ -230,4 +240,32 @@ pub mod tests {
             ])
         );
     }
+
+    #[test]
+    fn test_merge_attributions() {
+        let src = |id, attribution: Option<&str>| TestSource {
+            id,
+            tj: TileJSON {
+                attribution: attribution.map(ToString::to_string),
+                ..tilejson! { tiles: vec![] }
+            },
+            info: TileInfo::new(Format::Mvt, Encoding::Uncompressed),
+            data: Vec::default(),
+        };
+        let osm = src("osm", Some("© OpenStreetMap"));
+        let osm2 = src("osm2", Some("© OpenStreetMap"));
+        let nasa = src("nasa", Some("NASA"));
+        let empty = src("empty", Some(""));
+        let none = src("none", None);
+
+        assert_eq!(merge_attributions(&[&none, &empty]), None);
+        assert_eq!(
+            merge_attributions(&[&osm, &none, &nasa, &osm2, &empty]),
+            Some("© OpenStreetMap\nNASA".to_string())
+        );
+
+        let url = "http://localhost:8888/foo/{z}/{x}/{y}".to_string();
+        let tj = merge_tilejson(&[&nasa, &osm, &none], url);
+        assert_eq!(tj.attribution, Some("NASA\n© OpenStreetMap".to_string()));
+    }
 }
@@ -0,0 +1,44 @@
+use async_trait::async_trait;
+use martin_tile_utils::{TileCoord, TileInfo};
+use tilejson::TileJSON;
+
+use crate::source::{Source, TileData, TileInfoSource, UrlQuery};
+use crate::MartinResult;
+
+/// A trivial [`Source`] that returns the same tile data for every request,
+/// useful for testing code that works with [`crate::TileSources`] without a database or a file.
+#[derive(Debug, Clone)]
+pub struct TestSource {
+    pub id: &'static str,
+    pub tj: TileJSON,
+    pub info: TileInfo,
+    /// Returned for every tile coordinate
+    pub data: TileData,
+}
+
+#[async_trait]
+impl Source for TestSource {
+    fn get_id(&self) -> &str {
+        self.id
+    }
+
+    fn get_tilejson(&self) -> &TileJSON {
+        &self.tj
+    }
+
+    fn get_tile_info(&self) -> TileInfo {
+        self.info
+    }
+
+    fn clone_source(&self) -> TileInfoSource {
+        Box::new(self.clone())
+    }
+
+    async fn get_tile(
+        &self,
+        _xyz: TileCoord,
+        _url_query: Option<&UrlQuery>,
+    ) -> MartinResult<TileData> {
+        Ok(self.data.clone())
+    }
+}
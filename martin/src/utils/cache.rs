@@ -1,7 +1,7 @@
 use martin_tile_utils::TileCoord;
 use moka::future::Cache;
 
-use crate::TileData;
+use crate::Tile;
 
 pub type MainCache = Cache<CacheKey, CacheValue>;
 pub type OptMainCache = Option<MainCache>;
@@ -15,11 +15,15 @@ pub enum CacheKey {
     Tile(String, TileCoord),
     /// (`source_id`, `xyz`, `url_query`)
     TileWithQuery(String, TileCoord, String),
+    /// (`source_id`, `xyz`, `url_query`), the `url_query` is empty if the source does not use it
+    TileEtag(String, TileCoord, String),
 }
 
 #[derive(Debug, Clone)]
 pub enum CacheValue {
-    Tile(TileData),
+    Tile(Tile),
+    /// Entity tag of a tile, if its source provides one
+    TileEtag(Option<String>),
     #[cfg(feature = "pmtiles")]
     PmtDirectory(pmtiles::Directory),
 }
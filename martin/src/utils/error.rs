@@ -3,6 +3,8 @@ use std::fmt::Write as _;
 use std::io;
 use std::path::PathBuf;
 
+use martin_tile_utils::TileCoord;
+
 /// A convenience [`Result`] for Martin crate.
 pub type MartinResult<T> = Result<T, MartinError>;
 
@@ -49,6 +51,9 @@ pub enum MartinError {
     #[error("No tile sources found. Set sources by giving a database connection string on command line, env variable, or a config file.")]
     NoSources,
 
+    #[error("Found problems in {0} tile source(s), see the log for details")]
+    InvalidSources(usize),
+
     #[error("Unrecognizable connection strings: {0:?}")]
     UnrecognizableConnections(Vec<String>),
 
@@ -67,6 +72,12 @@ pub enum MartinError {
     #[error(transparent)]
     FileError(#[from] crate::file_config::FileError),
 
+    #[error(transparent)]
+    SourceError(#[from] crate::source::SourceError),
+
+    #[error("Unable to get tile {1} of source {0}: {2}")]
+    TileError(String, TileCoord, #[source] Box<MartinError>),
+
     #[cfg(feature = "sprites")]
     #[error(transparent)]
     SpriteError(#[from] crate::sprites::SpriteError),
@@ -1,4 +1,8 @@
-use actix_web::http::header::{ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_TYPE};
+use actix_web::http::header::{
+    ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH,
+    LAST_MODIFIED,
+};
+use actix_web::http::StatusCode;
 use actix_web::test::{call_service, read_body, read_body_json, TestRequest};
 use ctor::ctor;
 use indoc::indoc;
@@ -58,23 +62,83 @@ async fn mbt_get_catalog() {
     sprites: {}
     tiles:
       m_json:
+        bounds:
+          - -123.12359
+          - -37.818085
+          - 174.763027
+          - 59.352706
         content_type: application/json
+        maxzoom: 0
+        minzoom: 0
         name: Dummy json data
+        other:
+          format: json
+        tile_count: 196
+        total_bytes: 24576
       m_mvt:
+        bounds:
+          - -123.12359
+          - -37.818085
+          - 174.763027
+          - 59.352706
         content_encoding: gzip
         content_type: application/x-protobuf
         description: Major cities from Natural Earth data
+        maxzoom: 6
+        minzoom: 0
         name: Major cities from Natural Earth data
+        other:
+          format: pbf
+        tile_count: 196
+        total_bytes: 49152
       m_raw_mvt:
+        bounds:
+          - -123.12359
+          - -37.818085
+          - 174.763027
+          - 59.352706
         content_type: application/x-protobuf
         description: Major cities from Natural Earth data
+        maxzoom: 6
+        minzoom: 0
         name: Major cities from Natural Earth data
+        other:
+          format: pbf
+          generator: tippecanoe v1.32.5
+        tile_count: 196
+        total_bytes: 24576
       m_webp:
+        bounds:
+          - -180
+          - -85.05113
+          - 180
+          - 85.05113
         content_type: image/webp
+        maxzoom: 0
+        minzoom: 0
         name: ne2sr
+        other:
+          format: webp
+        total_bytes: 28672
     ");
 }
 
+#[actix_rt::test]
+async fn mbt_get_catalog_by_content_type() {
+    let app = create_app! { CONFIG };
+
+    let req = test_get("/catalog?content_type=application/x-protobuf").to_request();
+    let response = assert_response(call_service(&app, req).await).await;
+    let body: serde_json::Value = read_body_json(response).await;
+    let tiles = body["tiles"].as_object().unwrap();
+    assert_eq!(tiles.keys().collect::<Vec<_>>(), vec!["m_mvt", "m_raw_mvt"]);
+
+    let req = test_get("/catalog?content_type=image/png").to_request();
+    let response = assert_response(call_service(&app, req).await).await;
+    let body: serde_json::Value = read_body_json(response).await;
+    assert!(body["tiles"].as_object().unwrap().is_empty());
+}
+
 #[actix_rt::test]
 async fn mbt_get_catalog_gzip() {
     let app = create_app! { CONFIG };
@@ -89,20 +153,64 @@ async fn mbt_get_catalog_gzip() {
     sprites: {}
     tiles:
       m_json:
+        bounds:
+          - -123.12359
+          - -37.818085
+          - 174.763027
+          - 59.352706
         content_type: application/json
+        maxzoom: 0
+        minzoom: 0
         name: Dummy json data
+        other:
+          format: json
+        tile_count: 196
+        total_bytes: 24576
       m_mvt:
+        bounds:
+          - -123.12359
+          - -37.818085
+          - 174.763027
+          - 59.352706
         content_encoding: gzip
         content_type: application/x-protobuf
         description: Major cities from Natural Earth data
+        maxzoom: 6
+        minzoom: 0
         name: Major cities from Natural Earth data
+        other:
+          format: pbf
+        tile_count: 196
+        total_bytes: 49152
       m_raw_mvt:
+        bounds:
+          - -123.12359
+          - -37.818085
+          - 174.763027
+          - 59.352706
         content_type: application/x-protobuf
         description: Major cities from Natural Earth data
+        maxzoom: 6
+        minzoom: 0
         name: Major cities from Natural Earth data
+        other:
+          format: pbf
+          generator: tippecanoe v1.32.5
+        tile_count: 196
+        total_bytes: 24576
       m_webp:
+        bounds:
+          - -180
+          - -85.05113
+          - 180
+          - 85.05113
         content_type: image/webp
+        maxzoom: 0
+        minzoom: 0
         name: ne2sr
+        other:
+          format: webp
+        total_bytes: 28672
     ");
 }
 
@@ -306,3 +414,88 @@ async fn mbt_get_json_gzip() {
     let body = decode_gzip(&body).unwrap();
     assert_eq!(body.len(), 13);
 }
+
+#[actix_rt::test]
+async fn mbt_get_tile_etag() {
+    let app = create_app! { indoc! {"
+        mbtiles:
+            sources:
+                m_hash: ../tests/fixtures/mbtiles/zoomed_world_cities.mbtiles
+                m_mvt: ../tests/fixtures/mbtiles/world_cities.mbtiles
+    "} };
+
+    let req = test_get("/m_hash/6/10/25").to_request();
+    let response = assert_response(call_service(&app, req).await).await;
+    let etag = response.headers().get(ETAG).unwrap().clone();
+    assert_eq!(etag, r#"W/"C7DEB11792E128C6D4561F0E6ABE4440""#);
+
+    let req = test_get("/m_hash/6/10/25")
+        .insert_header((IF_NONE_MATCH, etag.clone()))
+        .to_request();
+    let response = call_service(&app, req).await;
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(response.headers().get(ETAG).unwrap(), etag);
+
+    // merged tiles get a combined tag
+    let req = test_get("/m_hash,m_hash/6/10/25").to_request();
+    let response = assert_response(call_service(&app, req).await).await;
+    let merged = response.headers().get(ETAG).unwrap();
+    assert_ne!(merged, etag);
+
+    // flat MBTiles do not store tile hashes
+    let req = test_get("/m_mvt/0/0/0").to_request();
+    let response = assert_response(call_service(&app, req).await).await;
+    assert!(response.headers().get(ETAG).is_none());
+    let req = test_get("/m_hash,m_mvt/6/10/25").to_request();
+    let response = assert_response(call_service(&app, req).await).await;
+    assert!(response.headers().get(ETAG).is_none());
+}
+
+#[actix_rt::test]
+async fn mbt_get_tile_last_modified() {
+    let app = create_app! { CONFIG };
+
+    let req = test_get("/m_mvt/0/0/0").to_request();
+    let response = assert_response(call_service(&app, req).await).await;
+    let last_modified = response.headers().get(LAST_MODIFIED).unwrap().clone();
+
+    let req = test_get("/m_mvt/0/0/0")
+        .insert_header((IF_MODIFIED_SINCE, last_modified.clone()))
+        .to_request();
+    let response = call_service(&app, req).await;
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(
+        response.headers().get(LAST_MODIFIED).unwrap(),
+        last_modified
+    );
+
+    let req = test_get("/m_mvt/0/0/0")
+        .insert_header((IF_MODIFIED_SINCE, "Thu, 01 Jan 1970 00:00:00 GMT"))
+        .to_request();
+    assert_response(call_service(&app, req).await).await;
+
+    // merged tiles use the most recent time of their sources
+    let req = test_get("/m_mvt,m_raw_mvt/0/0/0").to_request();
+    let response = assert_response(call_service(&app, req).await).await;
+    assert!(response.headers().get(LAST_MODIFIED).is_some());
+}
+
+#[actix_rt::test]
+async fn mbt_get_out_of_range_tile() {
+    let app = create_app! { CONFIG };
+
+    let req = test_get("/m_mvt/0/0/0").to_request();
+    let response = call_service(&app, req).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    for path in [
+        "/m_mvt/1/2/0",
+        "/m_mvt/1/0/2",
+        "/m_mvt/1/99999/0",
+        "/m_mvt/0/1/0",
+    ] {
+        let req = test_get(path).to_request();
+        let response = call_service(&app, req).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{path}");
+    }
+}
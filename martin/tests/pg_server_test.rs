@@ -780,15 +780,15 @@ postgres:
 
     let req = test_get("/function_null/0/0/0");
     let response = call_service(&app, req).await;
-    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
 
     let req = test_get("/function_null_row/0/0/0");
     let response = call_service(&app, req).await;
-    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
 
     let req = test_get("/function_null_row2/0/0/0");
     let response = call_service(&app, req).await;
-    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
 }
 
 #[actix_rt::test]
@@ -56,7 +56,16 @@ async fn pmt_get_catalog() {
     sprites: {}
     tiles:
       stamen_toner__raster_CC-BY-ODbL_z3:
+        bounds:
+          - -180
+          - -85
+          - 180
+          - 85
         content_type: image/png
+        maxzoom: 3
+        minzoom: 0
+        tile_count: 85
+        total_bytes: 716052
     ");
 }
 
@@ -74,7 +83,16 @@ async fn pmt_get_catalog_gzip() {
     sprites: {}
     tiles:
       p_png:
+        bounds:
+          - -180
+          - -85
+          - 180
+          - 85
         content_type: image/png
+        maxzoom: 3
+        minzoom: 0
+        tile_count: 85
+        total_bytes: 716052
     ");
 }
 
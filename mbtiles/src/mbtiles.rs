@@ -1,14 +1,19 @@
+use std::collections::HashMap;
 use std::ffi::OsStr;
 use std::fmt::{Display, Formatter};
 use std::path::Path;
 
 use enum_display::EnumDisplay;
 use log::debug;
+use martin_tile_utils::TileCoord;
 use serde::{Deserialize, Serialize};
 use sqlite_compressions::{register_bsdiffraw_functions, register_gzip_functions};
 use sqlite_hashes::register_md5_functions;
 use sqlx::sqlite::SqliteConnectOptions;
-use sqlx::{query, Connection as _, Executor, SqliteConnection, SqliteExecutor, Statement};
+use sqlx::{
+    query, query_scalar, Connection as _, Executor, Row as _, SqliteConnection, SqliteExecutor,
+    Statement,
+};
 
 use crate::bindiff::PatchType;
 use crate::errors::{MbtError, MbtResult};
@@ -152,6 +157,118 @@ impl Mbtiles {
         Ok(None)
     }
 
+    /// Check if a non-empty tile exists without reading its data
+    pub async fn has_tile<T>(&self, conn: &mut T, z: u8, x: u32, y: u32) -> MbtResult<bool>
+    where
+        for<'e> &'e mut T: SqliteExecutor<'e>,
+    {
+        let y = invert_y_value(z, y);
+        Ok(query_scalar(
+            "SELECT EXISTS(SELECT 1 FROM tiles WHERE zoom_level = ? AND tile_column = ? AND tile_row = ? AND length(tile_data) > 0)",
+        )
+        .bind(z)
+        .bind(x)
+        .bind(y)
+        .fetch_one(conn)
+        .await?)
+    }
+
+    /// Get the approximate number of tiles, as recorded in `sqlite_stat1` by the last `ANALYZE`.
+    /// This never scans the tiles, so `None` is returned if the file was never analyzed,
+    /// and the count may be stale if the tiles were modified afterwards.
+    pub async fn get_approx_tile_count<T>(
+        &self,
+        conn: &mut T,
+        mbt_type: MbtType,
+    ) -> MbtResult<Option<u64>>
+    where
+        for<'e> &'e mut T: SqliteExecutor<'e>,
+    {
+        let has_stats: bool = query_scalar(
+            "SELECT COUNT(*) = 1 FROM sqlite_master WHERE name = 'sqlite_stat1' AND type = 'table'",
+        )
+        .fetch_one(&mut *conn)
+        .await?;
+        if !has_stats {
+            return Ok(None);
+        }
+        let table = match mbt_type {
+            MbtType::Flat => "tiles",
+            MbtType::FlatWithHash => "tiles_with_hash",
+            MbtType::Normalized { .. } => "map",
+        };
+        // The first number of each stat row is the number of rows in the table
+        let stat: Option<String> =
+            query_scalar("SELECT stat FROM sqlite_stat1 WHERE tbl = ? LIMIT 1")
+                .bind(table)
+                .fetch_optional(&mut *conn)
+                .await?;
+        Ok(stat.and_then(|v| v.split_whitespace().next()?.parse().ok()))
+    }
+
+    /// Get multiple tiles with as few queries as possible.
+    /// The result has the same order as `coords`, with `None` for each missing tile.
+    pub async fn get_tiles<T>(
+        &self,
+        conn: &mut T,
+        coords: &[TileCoord],
+    ) -> MbtResult<Vec<Option<Vec<u8>>>>
+    where
+        for<'e> &'e mut T: SqliteExecutor<'e>,
+    {
+        let mut found = HashMap::new();
+        // Keep the number of bound variables well below the SQLite limit
+        for chunk in coords.chunks(300) {
+            let values = vec!["(?, ?, ?)"; chunk.len()].join(", ");
+            let sql = format!(
+                "SELECT zoom_level, tile_column, tile_row, tile_data FROM tiles
+                 WHERE (zoom_level, tile_column, tile_row) IN (VALUES {values})"
+            );
+            let mut query = query(&sql);
+            for c in chunk {
+                query = query.bind(c.z).bind(c.x).bind(invert_y_value(c.z, c.y));
+            }
+            for row in query.fetch_all(&mut *conn).await? {
+                let key: (u8, u32, u32) = (row.try_get(0)?, row.try_get(1)?, row.try_get(2)?);
+                if let Some(tile_data) = row.try_get::<Option<Vec<u8>>, _>(3)? {
+                    found.insert(key, tile_data);
+                }
+            }
+        }
+        Ok(coords
+            .iter()
+            .map(|c| found.get(&(c.z, c.x, invert_y_value(c.z, c.y))).cloned())
+            .collect())
+    }
+
+    /// Get the stored hash of a tile. Only `flat-with-hash` and `normalized` types store tile hashes,
+    /// so `None` is returned for the `flat` type, or if the tile does not exist.
+    pub async fn get_tile_hash<T>(
+        &self,
+        conn: &mut T,
+        mbt_type: MbtType,
+        z: u8,
+        x: u32,
+        y: u32,
+    ) -> MbtResult<Option<String>>
+    where
+        for<'e> &'e mut T: SqliteExecutor<'e>,
+    {
+        let sql = match mbt_type {
+            MbtType::Flat => return Ok(None),
+            MbtType::FlatWithHash => "SELECT tile_hash FROM tiles_with_hash WHERE zoom_level = ? AND tile_column = ? AND tile_row = ?",
+            MbtType::Normalized { .. } => "SELECT tile_id FROM map WHERE zoom_level = ? AND tile_column = ? AND tile_row = ?",
+        };
+        let y = invert_y_value(z, y);
+        Ok(query_scalar(sql)
+            .bind(z)
+            .bind(x)
+            .bind(y)
+            .fetch_optional(conn)
+            .await?
+            .flatten())
+    }
+
     pub async fn insert_tiles(
         &self,
         conn: &mut SqliteConnection,
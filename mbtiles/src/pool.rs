@@ -1,9 +1,12 @@
 use std::path::Path;
 
-use sqlx::{Pool, Sqlite, SqlitePool};
+use futures::stream::BoxStream;
+use futures::StreamExt as _;
+use martin_tile_utils::TileCoord;
+use sqlx::{query, Pool, Row, Sqlite, SqlitePool};
 
 use crate::errors::MbtResult;
-use crate::{Mbtiles, Metadata};
+use crate::{invert_y_value, MbtType, Mbtiles, Metadata};
 
 #[derive(Clone, Debug)]
 pub struct MbtilesPool {
@@ -18,6 +21,11 @@ impl MbtilesPool {
         Ok(Self { mbtiles, pool })
     }
 
+    #[must_use]
+    pub fn filepath(&self) -> &str {
+        self.mbtiles.filepath()
+    }
+
     pub async fn get_metadata(&self) -> MbtResult<Metadata> {
         let mut conn = self.pool.acquire().await?;
         self.mbtiles.get_metadata(&mut *conn).await
@@ -27,4 +35,57 @@ impl MbtilesPool {
         let mut conn = self.pool.acquire().await?;
         self.mbtiles.get_tile(&mut *conn, z, x, y).await
     }
+
+    pub async fn has_tile(&self, z: u8, x: u32, y: u32) -> MbtResult<bool> {
+        let mut conn = self.pool.acquire().await?;
+        self.mbtiles.has_tile(&mut *conn, z, x, y).await
+    }
+
+    pub async fn get_approx_tile_count(&self, mbt_type: MbtType) -> MbtResult<Option<u64>> {
+        let mut conn = self.pool.acquire().await?;
+        self.mbtiles
+            .get_approx_tile_count(&mut *conn, mbt_type)
+            .await
+    }
+
+    pub async fn get_tiles(&self, coords: &[TileCoord]) -> MbtResult<Vec<Option<Vec<u8>>>> {
+        let mut conn = self.pool.acquire().await?;
+        self.mbtiles.get_tiles(&mut *conn, coords).await
+    }
+
+    /// Stream all tiles with their XYZ coordinates, in no particular order.
+    #[must_use]
+    pub fn stream_tiles(&self) -> BoxStream<'_, MbtResult<(TileCoord, Vec<u8>)>> {
+        query("SELECT zoom_level, tile_column, tile_row, tile_data FROM tiles WHERE tile_data IS NOT NULL")
+            .fetch(&self.pool)
+            .map(|row| {
+                let row = row?;
+                let z: u8 = row.try_get(0)?;
+                let xyz = TileCoord {
+                    z,
+                    x: row.try_get(1)?,
+                    y: invert_y_value(z, row.try_get(2)?),
+                };
+                Ok((xyz, row.try_get(3)?))
+            })
+            .boxed()
+    }
+
+    pub async fn detect_type(&self) -> MbtResult<MbtType> {
+        let mut conn = self.pool.acquire().await?;
+        self.mbtiles.detect_type(&mut *conn).await
+    }
+
+    pub async fn get_tile_hash(
+        &self,
+        mbt_type: MbtType,
+        z: u8,
+        x: u32,
+        y: u32,
+    ) -> MbtResult<Option<String>> {
+        let mut conn = self.pool.acquire().await?;
+        self.mbtiles
+            .get_tile_hash(&mut *conn, mbt_type, z, x, y)
+            .await
+    }
 }